@@ -0,0 +1,98 @@
+//! Benchmarks for the paths every transaction goes through, run with `cargo bench`.
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use futures::executor::block_on;
+use libp2p::identity::ed25519::Keypair;
+
+use bloackchain_workshop::{block, merkle, storage, transaction};
+
+use merkle::MerkleTree;
+use transaction::Transaction;
+
+// A full block, as the node commits them.
+const BLOCK_SIZE: usize = 10;
+const CHAIN_ID: &str = "bench";
+
+fn transactions(count: usize) -> Vec<Transaction> {
+    let keypair = Keypair::generate();
+    (0..count)
+        .map(|i| {
+            let data = format!("send {} {i}", hex::encode([7u8; 32]));
+            block_on(Transaction::sign(
+                &keypair,
+                CHAIN_ID,
+                data.as_bytes(),
+                i as u64 + 1,
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+fn signature_verification(c: &mut Criterion) {
+    let transaction = &transactions(1)[0];
+    c.bench_function("verify transaction", |b| {
+        b.iter(|| black_box(transaction).verify(CHAIN_ID))
+    });
+}
+
+fn transaction_decoding(c: &mut Criterion) {
+    let encoded = Bytes::from(transactions(1)[0].to_bytes());
+    c.bench_function("decode transaction", |b| {
+        b.iter(|| Transaction::decode(black_box(encoded.clone())).unwrap())
+    });
+}
+
+fn merkle_root(c: &mut Criterion) {
+    let ids: Vec<_> = transactions(1000)
+        .iter()
+        .map(|transaction| *transaction.id().as_bytes())
+        .collect();
+
+    let mut group = c.benchmark_group("merkle root");
+    for leaves in [BLOCK_SIZE, 1000] {
+        group.bench_function(format!("{leaves} leaves"), |b| {
+            b.iter(|| MerkleTree::from_leaves(black_box(&ids[..leaves])).root())
+        });
+    }
+    group.finish();
+}
+
+fn block_assembly(c: &mut Criterion) {
+    let mempool = transactions(BLOCK_SIZE * 10);
+    let encoded: Vec<_> = mempool
+        .iter()
+        .map(|transaction| Bytes::from(transaction.to_bytes()))
+        .collect();
+
+    // take a block's worth from a fresh mempool, hash it into a tree and encode the block file
+    let mut contents = String::new();
+    c.bench_function("assemble block", |b| {
+        b.iter_batched(
+            || {
+                encoded
+                    .iter()
+                    .map(|bytes| Transaction::decode(bytes.clone()).unwrap())
+                    .collect::<Vec<_>>()
+            },
+            |mut mempool| {
+                let transactions: Vec<_> = mempool.drain(..BLOCK_SIZE).collect();
+                let block = block::Block::new(1, 0, [0; 32], transactions);
+                contents.clear();
+                storage::encode_block(&mut contents, &block);
+                block.hash
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    signature_verification,
+    transaction_decoding,
+    merkle_root,
+    block_assembly
+);
+criterion_main!(benches);
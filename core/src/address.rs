@@ -0,0 +1,64 @@
+use libp2p_identity::ed25519::PublicKey;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bech32;
+
+// Human-readable part of bech32 addresses, see `Address::to_bech32`.
+const BECH32_PREFIX: &str = "edu";
+
+/// An account address, the hex encoded ed25519 public key of its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address([u8; 32]);
+
+impl Address {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The address as bech32, e.g. `edu1...`, the form explorers expect.
+    pub fn to_bech32(self) -> String {
+        bech32::encode(BECH32_PREFIX, &self.0)
+    }
+}
+
+impl From<&PublicKey> for Address {
+    fn from(public_key: &PublicKey) -> Self {
+        Address(public_key.to_bytes())
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for Address {
+    type Err = InvalidAddress;
+
+    /// Accepts both forms, hex and bech32.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = match bech32::decode(s) {
+            Some((prefix, bytes)) if prefix == BECH32_PREFIX => bytes,
+            _ => hex::decode(s).map_err(|_| InvalidAddress(s.to_string()))?,
+        };
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| InvalidAddress(s.to_string()))?;
+
+        Ok(Address(bytes))
+    }
+}
+
+/// A string that is neither 32 hex encoded bytes nor a bech32 address.
+#[derive(Debug)]
+pub struct InvalidAddress(pub String);
+
+impl fmt::Display for InvalidAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not an address", self.0)
+    }
+}
+
+impl std::error::Error for InvalidAddress {}
@@ -0,0 +1,152 @@
+//! Bech32 encoding (BIP-173), which explorers expect addresses in: a
+//! human-readable prefix, a `1`, then base32 data with a checksum that
+//! catches typos.
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// Encode `data` under the prefix `hrp`.
+pub fn encode(hrp: &str, data: &[u8]) -> String {
+    encode_base32(hrp, &to_base32(data))
+}
+
+/// Decode a bech32 string into its prefix and data, if the checksum holds.
+pub fn decode(encoded: &str) -> Option<(String, Vec<u8>)> {
+    // either case, but not both
+    if encoded.bytes().any(|byte| byte.is_ascii_uppercase())
+        && encoded.bytes().any(|byte| byte.is_ascii_lowercase())
+    {
+        return None;
+    }
+    let encoded = encoded.to_ascii_lowercase();
+    let (hrp, data) = encoded.rsplit_once('1')?;
+    if hrp.is_empty() || data.len() < 6 {
+        return None;
+    }
+
+    let values = data
+        .bytes()
+        .map(|byte| {
+            CHARSET
+                .iter()
+                .position(|&c| c == byte)
+                .map(|value| value as u8)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let mut checked = expand_hrp(hrp);
+    checked.extend_from_slice(&values);
+    if polymod(&checked) != 1 {
+        return None;
+    }
+    let data = from_base32(&values[..values.len() - 6])?;
+    Some((hrp.to_string(), data))
+}
+
+// Regroup 8-bit bytes into 5-bit values, padding the last one with zeros.
+fn to_base32(data: &[u8]) -> Vec<u8> {
+    let mut values = Vec::with_capacity((data.len() * 8).div_ceil(5));
+    let (mut accumulator, mut bits) = (0u32, 0);
+    for &byte in data {
+        accumulator = (accumulator << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            values.push(((accumulator >> bits) & 0x1f) as u8);
+        }
+    }
+    if bits > 0 {
+        values.push(((accumulator << (5 - bits)) & 0x1f) as u8);
+    }
+    values
+}
+
+// Regroup 5-bit values into bytes, the reverse of `to_base32`. Padding has to be zeros.
+fn from_base32(values: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(values.len() * 5 / 8);
+    let (mut accumulator, mut bits) = (0u32, 0);
+    for &value in values {
+        accumulator = (accumulator << 5) | u32::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((accumulator >> bits) as u8);
+        }
+    }
+    (bits < 5 && accumulator & ((1 << bits) - 1) == 0).then_some(data)
+}
+
+fn encode_base32(hrp: &str, values: &[u8]) -> String {
+    let mut checked: Vec<u8> = expand_hrp(hrp);
+    checked.extend_from_slice(values);
+    checked.extend_from_slice(&[0; 6]);
+    let checksum = polymod(&checked) ^ 1;
+
+    let mut encoded = format!("{hrp}1");
+    let checksum_values = (0..6).map(|i| ((checksum >> (5 * (5 - i))) & 0x1f) as u8);
+    for value in values.iter().copied().chain(checksum_values) {
+        encoded.push(CHARSET[usize::from(value)] as char);
+    }
+    encoded
+}
+
+fn expand_hrp(hrp: &str) -> Vec<u8> {
+    let high = hrp.bytes().map(|byte| byte >> 5);
+    let low = hrp.bytes().map(|byte| byte & 0x1f);
+    high.chain([0]).chain(low).collect()
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum = 1u32;
+    for &value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_bip_173_vectors() {
+        assert_eq!(encode_base32("a", &[]), "a12uel5l");
+        let every_value: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            encode_base32("abcdef", &every_value),
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw"
+        );
+        // the P2WPKH example, witness version 0 followed by the program
+        let program = hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let mut values = vec![0];
+        values.extend(to_base32(&program));
+        assert_eq!(
+            encode_base32("bc", &values),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let data = [7u8; 32];
+        let encoded = encode("edu", &data);
+        assert_eq!(decode(&encoded), Some(("edu".to_string(), data.to_vec())));
+        assert_eq!(
+            decode(&encoded.to_ascii_uppercase()),
+            Some(("edu".to_string(), data.to_vec()))
+        );
+
+        // a typo breaks the checksum
+        let mut typo = encoded.into_bytes();
+        let last = typo.len() - 1;
+        typo[last] = if typo[last] == b'q' { b'p' } else { b'q' };
+        assert_eq!(decode(&String::from_utf8(typo).unwrap()), None);
+        assert_eq!(decode("A12UEL5L"), Some(("a".to_string(), vec![])));
+        assert_eq!(decode("A12uEL5L"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! Hashes as content identifiers, so transaction ids and block roots can be
+//! pasted into libp2p and IPFS tooling, and ids printed by that tooling into
+//! queries here.
+//!
+//! Identifiers are printed as CIDv1 with the `raw` codec over a sha2-256
+//! multihash, in base32 (the `bafkrei…` strings IPFS prints). Any multibase
+//! encoding of such a CID or of the bare multihash is accepted back, as are
+//! CIDv0 (`Qm…`) strings and the plain hex ids of earlier releases.
+
+use multibase::Base;
+use multihash::MultihashGeneric;
+use std::fmt;
+
+type Multihash = MultihashGeneric<32>;
+
+const CID_V1: u8 = 0x01;
+// multicodec codes, both fit in a single varint byte
+const RAW: u8 = 0x55;
+const SHA2_256: u64 = 0x12;
+
+pub fn encode(hash: &[u8; 32]) -> String {
+    let multihash = Multihash::wrap(SHA2_256, hash).expect("32 bytes fit a 32 byte multihash");
+    let mut cid = vec![CID_V1, RAW];
+    cid.extend(multihash.to_bytes());
+    multibase::encode(Base::Base32Lower, cid)
+}
+
+/// The sha2-256 hash named by `s`.
+pub fn decode(s: &str) -> Result<[u8; 32], CidError> {
+    if s.len() == 64 {
+        if let Ok(hash) = hex::decode(s) {
+            return Ok(hash.try_into().expect("64 hex digits are 32 bytes"));
+        }
+    }
+
+    // CIDv0 is a base58 multihash without a multibase prefix
+    let bytes = if s.len() == 46 && s.starts_with("Qm") {
+        Base::Base58Btc.decode(s)
+    } else {
+        multibase::decode(s).map(|(_, bytes)| bytes)
+    }
+    .map_err(|_| CidError::InvalidEncoding)?;
+
+    let multihash = match bytes.as_slice() {
+        [CID_V1, RAW, multihash @ ..] => multihash,
+        [CID_V1, ..] => return Err(CidError::UnsupportedCodec),
+        multihash => multihash,
+    };
+    let multihash = Multihash::from_bytes(multihash).map_err(|_| CidError::InvalidMultihash)?;
+    if multihash.code() != SHA2_256 {
+        return Err(CidError::UnsupportedHash(multihash.code()));
+    }
+
+    multihash
+        .digest()
+        .try_into()
+        .map_err(|_| CidError::InvalidMultihash)
+}
+
+#[derive(Debug)]
+pub enum CidError {
+    InvalidEncoding,
+    UnsupportedCodec,
+    InvalidMultihash,
+    UnsupportedHash(u64),
+}
+
+impl fmt::Display for CidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CidError::InvalidEncoding => write!(f, "neither hex nor a multibase string"),
+            CidError::UnsupportedCodec => write!(f, "only CIDs with the raw codec name hashes"),
+            CidError::InvalidMultihash => write!(f, "not a 32 byte multihash"),
+            CidError::UnsupportedHash(code) => {
+                write!(f, "hash function 0x{code:x} isn't sha2-256")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CidError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn round_trips_through_other_encodings() {
+        let hash: [u8; 32] = Sha256::digest(b"hello").into();
+        // the CID IPFS gives the raw bytes `hello`
+        let cid = "bafkreibm6jg3ux5qumhcn2b3flc3tyu6dmlb4xa7u5bf44yegnrjhc4yeq";
+        assert_eq!(encode(&hash), cid);
+        assert_eq!(decode(cid).unwrap(), hash);
+
+        let multihash = Multihash::wrap(SHA2_256, &hash).unwrap().to_bytes();
+        let bytes = [&[CID_V1, RAW][..], &multihash].concat();
+        for base in [Base::Base58Btc, Base::Base64Url, Base::Base16Upper] {
+            assert_eq!(decode(&multibase::encode(base, &bytes)).unwrap(), hash);
+        }
+        // CIDv0 and the bare multihash
+        let v0 = Base::Base58Btc.encode(&multihash);
+        assert!(v0.starts_with("Qm"));
+        assert_eq!(decode(&v0).unwrap(), hash);
+        assert_eq!(
+            decode(&multibase::encode(Base::Base32Lower, &multihash)).unwrap(),
+            hash
+        );
+        assert_eq!(decode(&hex::encode(hash)).unwrap(), hash);
+    }
+
+    #[test]
+    fn rejects_other_hashes_and_codecs() {
+        let blake3 = Multihash::wrap(0x1e, &[0; 32]).unwrap().to_bytes();
+        assert!(matches!(
+            decode(&multibase::encode(Base::Base32Lower, &blake3)),
+            Err(CidError::UnsupportedHash(0x1e))
+        ));
+
+        let dag_pb = [
+            &[CID_V1, 0x70][..],
+            &Multihash::wrap(SHA2_256, &[0; 32]).unwrap().to_bytes(),
+        ]
+        .concat();
+        assert!(matches!(
+            decode(&multibase::encode(Base::Base32Lower, dag_pb)),
+            Err(CidError::UnsupportedCodec)
+        ));
+        assert!(decode("not an id").is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! The types every EduCoin client has to agree on: addresses, transactions
+//! and their encodings, and the merkle roots of blocks.
+//!
+//! Nothing here touches the network or the disk, so the crate also builds
+//! for `wasm32-unknown-unknown`, for wallets that run in a browser:
+//! `cargo build -p educoin-core --target wasm32-unknown-unknown`.
+
+pub mod address;
+pub mod bech32;
+pub mod cid;
+pub mod merkle;
+pub mod proto;
+pub mod signer;
+pub mod transaction;
@@ -0,0 +1,186 @@
+//! Merkle trees over a block's transactions, shaped like RFC 6962 trees:
+//! leaves hash as `sha256(0x00 || leaf)`, inner nodes as
+//! `sha256(0x01 || left || right)`, and a tree over `n` leaves splits after
+//! the largest power of two below `n`.
+
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+/// A Merkle tree that only grows at the right.
+///
+/// Only the roots of its complete subtrees are kept, so adding a leaf and
+/// computing the root both take O(log n) instead of rebuilding the tree.
+#[derive(Clone, Default)]
+pub struct MerkleTree {
+    // roots of the complete subtrees and their heights, largest first
+    peaks: Vec<(u32, Hash)>,
+}
+
+impl MerkleTree {
+    pub fn from_leaves(leaves: impl IntoIterator<Item = impl AsRef<[u8]>>) -> Self {
+        let mut tree = MerkleTree::default();
+        for leaf in leaves {
+            tree.push(leaf.as_ref());
+        }
+        tree
+    }
+
+    pub fn push(&mut self, leaf: &[u8]) {
+        let mut node = (0, leaf_hash(leaf));
+        // two subtrees of the same height merge, like carrying in binary addition
+        while let Some(&(height, left)) = self.peaks.last() {
+            if height != node.0 {
+                break;
+            }
+            self.peaks.pop();
+            node = (height + 1, node_hash(&left, &node.1));
+        }
+        self.peaks.push(node);
+    }
+
+    /// Root of the tree, `sha256("")` while it's empty.
+    pub fn root(&self) -> Hash {
+        let mut peaks = self.peaks.iter().rev();
+        let Some(&(_, mut root)) = peaks.next() else {
+            return Sha256::digest([]).into();
+        };
+        for (_, left) in peaks {
+            root = node_hash(left, &root);
+        }
+        root
+    }
+}
+
+/// Proof that a leaf is in a tree with a given root: the leaf's index, the
+/// number of leaves and the roots of the subtrees next to the leaf's path to
+/// the root, lowest first (an RFC 6962 audit path).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub index: u64,
+    pub size: u64,
+    pub path: Vec<Hash>,
+}
+
+impl InclusionProof {
+    /// Proof for the leaf at `index` among `leaves`, `None` past the last one.
+    pub fn new(leaves: &[impl AsRef<[u8]>], index: usize) -> Option<Self> {
+        (index < leaves.len()).then(|| InclusionProof {
+            index: index as u64,
+            size: leaves.len() as u64,
+            path: audit_path(leaves, index),
+        })
+    }
+
+    /// Whether `leaf` is the leaf at the proof's index of the tree with `root`,
+    /// as RFC 9162 verifies audit paths.
+    pub fn verify(&self, leaf: &[u8], root: &Hash) -> bool {
+        if self.index >= self.size {
+            return false;
+        }
+        let (mut index, mut last) = (self.index, self.size - 1);
+        let mut hash = leaf_hash(leaf);
+        for sibling in &self.path {
+            if last == 0 {
+                return false;
+            }
+            if index & 1 == 1 || index == last {
+                hash = node_hash(sibling, &hash);
+                // a right edge without a sibling at this height, go up to where it has one
+                while index & 1 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+            index >>= 1;
+            last >>= 1;
+        }
+        last == 0 && hash == *root
+    }
+}
+
+// Roots of the subtrees next to the path from the leaf at `index` to the root, lowest first.
+fn audit_path(leaves: &[impl AsRef<[u8]>], index: usize) -> Vec<Hash> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    // the largest power of two below the number of leaves
+    let split = 1 << (usize::BITS - 1 - (leaves.len() - 1).leading_zeros());
+    let (mut path, sibling) = if index < split {
+        (audit_path(&leaves[..split], index), &leaves[split..])
+    } else {
+        (
+            audit_path(&leaves[split..], index - split),
+            &leaves[..split],
+        )
+    };
+    path.push(MerkleTree::from_leaves(sibling).root());
+    path
+}
+
+fn leaf_hash(leaf: &[u8]) -> Hash {
+    Sha256::new()
+        .chain_update([0x00])
+        .chain_update(leaf)
+        .finalize()
+        .into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update([0x01])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // The tree hash exactly as RFC 6962 defines it, built top-down.
+    fn reference_root(leaves: &[Vec<u8>]) -> Hash {
+        match leaves.len() {
+            0 => Sha256::digest([]).into(),
+            1 => leaf_hash(&leaves[0]),
+            n => {
+                // the largest power of two below n
+                let split = 1 << (usize::BITS - 1 - (n - 1).leading_zeros());
+                node_hash(
+                    &reference_root(&leaves[..split]),
+                    &reference_root(&leaves[split..]),
+                )
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn proves_every_leaf_and_nothing_else(leaves in prop::collection::vec(prop::collection::vec(any::<u8>(), 1..8), 1..40)) {
+            let root = reference_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = InclusionProof::new(&leaves, index).unwrap();
+                prop_assert!(proof.verify(leaf, &root));
+
+                let mut forged = leaf.clone();
+                forged.push(0);
+                prop_assert!(!proof.verify(&forged, &root));
+                prop_assert!(!proof.verify(leaf, &reference_root(&[leaves.clone(), vec![forged]].concat())));
+            }
+            prop_assert!(InclusionProof::new(&leaves, leaves.len()).is_none());
+        }
+
+        #[test]
+        fn incremental_root_matches_the_full_tree(leaves in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..8), 0..40)) {
+            let mut tree = MerkleTree::default();
+            for (index, leaf) in leaves.iter().enumerate() {
+                tree.push(leaf);
+                prop_assert_eq!(tree.root(), reference_root(&leaves[..=index]));
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The wire messages of `proto/educoin.proto`, for nodes written in other
+//! languages to implement against.
+//!
+//! Written out with prost's derive rather than generated, so building the
+//! node doesn't need `protoc`. Any change here has to be made to the schema
+//! as well, and the other way around; the golden vectors catch drift in the
+//! encoding.
+
+use bytes::Bytes;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Transaction {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub public_key: Bytes,
+    #[prost(bytes = "bytes", tag = "2")]
+    pub signature: Bytes,
+    #[prost(bytes = "bytes", tag = "3")]
+    pub data: Bytes,
+    #[prost(uint64, tag = "4")]
+    pub fee: u64,
+    #[prost(uint64, tag = "5")]
+    pub nonce: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionMessage {
+    #[prost(message, optional, tag = "1")]
+    pub transaction: Option<Transaction>,
+    #[prost(bytes = "bytes", optional, tag = "2")]
+    pub delegation: Option<Bytes>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockProposal {
+    #[prost(string, tag = "1")]
+    pub chain_id: String,
+    #[prost(uint32, tag = "2")]
+    pub height: u32,
+    #[prost(message, repeated, tag = "3")]
+    pub transactions: Vec<Transaction>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub proposer: Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "6")]
+    pub prev_hash: Vec<u8>,
+    #[prost(uint32, tag = "7")]
+    pub round: u32,
+    #[prost(uint64, tag = "8")]
+    pub nonce: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Vote {
+    #[prost(string, tag = "1")]
+    pub chain_id: String,
+    #[prost(uint32, tag = "2")]
+    pub height: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    pub voter: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub block_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: Vec<u8>,
+    #[prost(uint32, tag = "6")]
+    pub round: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FaucetRequest {
+    #[prost(string, tag = "1")]
+    pub recipient: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Block {
+    #[prost(uint32, tag = "1")]
+    pub height: u32,
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub prev_hash: Vec<u8>,
+    #[prost(message, repeated, tag = "4")]
+    pub transactions: Vec<Transaction>,
+    #[prost(uint64, tag = "5")]
+    pub nonce: u64,
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    pub votes: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SyncMessage {
+    #[prost(oneof = "sync_message::Message", tags = "1, 2")]
+    pub message: Option<sync_message::Message>,
+}
+
+pub mod sync_message {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Message {
+        #[prost(message, tag = "1")]
+        GetBlocks(super::GetBlocks),
+        #[prost(message, tag = "2")]
+        Blocks(super::Blocks),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBlocks {
+    #[prost(string, tag = "1")]
+    pub chain_id: String,
+    #[prost(uint32, tag = "2")]
+    pub from: u32,
+    #[prost(uint32, tag = "3")]
+    pub to: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Blocks {
+    #[prost(message, repeated, tag = "1")]
+    pub blocks: Vec<Block>,
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use libp2p_identity::ed25519::{Keypair, PublicKey};
+use std::sync::Arc;
+
+/// Anything that can produce ed25519 signatures on behalf of an account.
+///
+/// Transactions are signed only through this trait, so a remote signing
+/// service or a hardware wallet simulator can back an account without the
+/// mempool or consensus code knowing about it.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    fn public_key(&self) -> PublicKey;
+
+    async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignerError>;
+}
+
+/// Local in-memory keys, the default backend.
+#[async_trait]
+impl Signer for Keypair {
+    fn public_key(&self) -> PublicKey {
+        self.public()
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignerError> {
+        Ok(Keypair::sign(self, message))
+    }
+}
+
+/// Keys shared with whoever else signs with them, e.g. the node identity key.
+#[async_trait]
+impl<S: Signer + ?Sized> Signer for Arc<S> {
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SignerError> {
+        (**self).sign(message).await
+    }
+}
+
+// Local keys never fail, these are produced by external backends.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum SignerError {
+    /// The backend refused to sign, e.g. a user rejected the request on a device.
+    Rejected(String),
+    /// The backend could not be reached.
+    Unavailable(String),
+}
+
+impl std::fmt::Display for SignerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerError::Rejected(reason) => write!(f, "signer rejected the request: {reason}"),
+            SignerError::Unavailable(reason) => write!(f, "signer is unavailable: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SignerError {}
@@ -0,0 +1,221 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use libp2p_identity::ed25519::PublicKey;
+use prost::Message;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cid::{self, CidError};
+use crate::proto;
+use crate::signer::{Signer, SignerError};
+
+const SIGNATURE_LEN: usize = 64;
+
+// Prefix of what a transaction signature covers, followed by the chain id and a newline, so the
+// signature can't pass for a vote or message signature, or be replayed on another chain. The
+// nonce and fee follow the newline, so the signature can't be replayed on this chain either.
+const TRANSACTION_CONTEXT: &[u8] = b"educoin/tx/v3\n";
+
+/// Hash of a transaction's public key, signature, data, fee and nonce, used to
+/// refer to it in logs and queries and to recognise it when it arrives
+/// again. It doesn't depend on how the transaction was
+/// encoded on the wire, and is printed as a CID, see [`crate::cid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransactionId([u8; 32]);
+
+impl TransactionId {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", cid::encode(&self.0))
+    }
+}
+
+impl FromStr for TransactionId {
+    type Err = CidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        cid::decode(s).map(TransactionId)
+    }
+}
+
+/// A signed transaction. Decoded ones share the buffer they arrived in, see
+/// [`Transaction::decode`].
+pub struct Transaction {
+    pub public_key: PublicKey,
+    pub signature: Bytes,
+    pub data: Bytes,
+    /// Coins the sender pays to get the transaction into a block sooner.
+    pub fee: u64,
+    /// Counts the sender's transactions up: each has to be one above the
+    /// last one committed, so a committed transaction can't be replayed.
+    pub nonce: u64,
+}
+
+// Written by hand to print the bytes as a list, the way `Vec` fields would,
+// rather than as `Bytes` escapes them.
+impl fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("public_key", &self.public_key)
+            .field("signature", &&self.signature[..])
+            .field("data", &&self.data[..])
+            .field("fee", &self.fee)
+            .field("nonce", &self.nonce)
+            .finish()
+    }
+}
+
+impl Transaction {
+    /// Sign `data` with `signer` for the chain `chain_id` as the signer's
+    /// transaction `nonce`, producing a transaction ready to be published.
+    pub async fn sign(
+        signer: &dyn Signer,
+        chain_id: &str,
+        data: &[u8],
+        nonce: u64,
+    ) -> Result<Self, SignerError> {
+        Transaction::sign_with_fee(signer, chain_id, data, nonce, 0).await
+    }
+
+    /// Like [`Transaction::sign`], offering `fee` for the transaction.
+    pub async fn sign_with_fee(
+        signer: &dyn Signer,
+        chain_id: &str,
+        data: &[u8],
+        nonce: u64,
+        fee: u64,
+    ) -> Result<Self, SignerError> {
+        let signature = signer
+            .sign(&signing_payload(chain_id, data, fee, nonce))
+            .await?;
+        // signature and data share one allocation, like they do in decoded transactions
+        let mut buffer = BytesMut::with_capacity(signature.len() + data.len());
+        buffer.put_slice(&signature);
+        buffer.put_slice(data);
+        let mut data = buffer.freeze();
+        let signature = data.split_to(signature.len());
+
+        Ok(Transaction {
+            public_key: signer.public_key(),
+            signature,
+            data,
+            fee,
+            nonce,
+        })
+    }
+
+    pub fn id(&self) -> TransactionId {
+        let mut hasher = Sha256::new();
+        hasher.update(self.public_key.to_bytes());
+        hasher.update(&self.signature);
+        hasher.update(&self.data);
+        hasher.update(self.fee.to_be_bytes());
+        hasher.update(self.nonce.to_be_bytes());
+        TransactionId(hasher.finalize().into())
+    }
+
+    /// Whether the signature is valid for a transaction on the chain `chain_id`.
+    pub fn verify(&self, chain_id: &str) -> bool {
+        self.public_key.verify(
+            &signing_payload(chain_id, &self.data, self.fee, self.nonce),
+            &self.signature,
+        )
+    }
+
+    /// Encode the transaction as it travels over the wire, a protobuf
+    /// `Transaction`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        proto::Transaction::from(self).encode_to_vec()
+    }
+
+    /// Decode the wire encoding, see [`Transaction::to_bytes`]. The signature
+    /// and data point into `bytes` rather than being copied out of it.
+    pub fn decode(bytes: Bytes) -> Result<Self, DecodeError> {
+        proto::Transaction::decode(bytes)
+            .map_err(DecodeError::Malformed)?
+            .try_into()
+    }
+}
+
+impl From<&Transaction> for proto::Transaction {
+    fn from(transaction: &Transaction) -> Self {
+        proto::Transaction {
+            public_key: Bytes::copy_from_slice(&transaction.public_key.to_bytes()),
+            signature: transaction.signature.clone(),
+            data: transaction.data.clone(),
+            fee: transaction.fee,
+            nonce: transaction.nonce,
+        }
+    }
+}
+
+impl TryFrom<proto::Transaction> for Transaction {
+    type Error = DecodeError;
+
+    fn try_from(transaction: proto::Transaction) -> Result<Self, Self::Error> {
+        let public_key = PublicKey::try_from_bytes(&transaction.public_key)
+            .map_err(|_| DecodeError::InvalidPublicKey)?;
+        if transaction.signature.len() != SIGNATURE_LEN {
+            return Err(DecodeError::InvalidSignatureLength(
+                transaction.signature.len(),
+            ));
+        }
+
+        Ok(Transaction {
+            public_key,
+            signature: transaction.signature,
+            data: transaction.data,
+            fee: transaction.fee,
+            nonce: transaction.nonce,
+        })
+    }
+}
+
+fn signing_payload(chain_id: &str, data: &[u8], fee: u64, nonce: u64) -> Vec<u8> {
+    [
+        TRANSACTION_CONTEXT,
+        chain_id.as_bytes(),
+        b"\n",
+        &nonce.to_be_bytes(),
+        &fee.to_be_bytes(),
+        data,
+    ]
+    .concat()
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Malformed(prost::DecodeError),
+    InvalidPublicKey,
+    InvalidSignatureLength(usize),
+    InvalidHex,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed(e) => write!(f, "transaction is malformed: {e}"),
+            DecodeError::InvalidPublicKey => write!(f, "transaction carries an invalid public key"),
+            DecodeError::InvalidSignatureLength(len) => {
+                write!(
+                    f,
+                    "transaction signature is {len} bytes instead of {SIGNATURE_LEN}"
+                )
+            }
+            DecodeError::InvalidHex => write!(f, "transaction blob is not valid hex"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decode a hex blob as printed by the `sign-tx` command.
+pub fn from_hex(blob: &str) -> Result<Transaction, DecodeError> {
+    let bytes = hex::decode(blob.trim()).map_err(|_| DecodeError::InvalidHex)?;
+    Transaction::decode(bytes.into())
+}
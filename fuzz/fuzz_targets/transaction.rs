@@ -0,0 +1,20 @@
+#![no_main]
+
+use bytes::Bytes;
+use educoin_core::transaction::{self, Transaction};
+use libfuzzer_sys::fuzz_target;
+
+// Gossiped transactions arrive as raw bytes, pre-signed ones as hex on stdin.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transaction) = Transaction::decode(Bytes::copy_from_slice(data)) {
+        // whatever decodes has to survive the rest of the pipeline, and its re-encoding has to
+        // decode to the same transaction (protobuf allows more than one encoding of it)
+        let _ = transaction.verify("fuzz");
+        let reencoded = Transaction::decode(transaction.to_bytes().into()).unwrap();
+        assert_eq!(reencoded.id(), transaction.id());
+    }
+
+    if let Ok(blob) = std::str::from_utf8(data) {
+        let _ = transaction::from_hex(blob);
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::identity::Keypair;
+use std::sync::OnceLock;
+
+// vote.rs expects the core crate's modules at the crate root, like the node has them
+use educoin_core::{merkle, proto};
+#[path = "../../src/vote.rs"]
+mod vote;
+
+const CHAIN_ID: &str = "fuzz";
+
+fn voter() -> &'static Keypair {
+    static VOTER: OnceLock<Keypair> = OnceLock::new();
+    VOTER.get_or_init(Keypair::generate_ed25519)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let voter = voter().public().to_peer_id();
+    if let Some(vote) = vote::decode(CHAIN_ID, data, &voter) {
+        // whatever parses as a vote re-encodes to a vote for the same height, round and block
+        let reencoded =
+            vote::encode(CHAIN_ID, vote.height, vote.round, &vote.block_hash, voter()).unwrap();
+        assert_eq!(vote::decode(CHAIN_ID, &reencoded, &voter), Some(vote));
+    }
+});
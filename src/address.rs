@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+pub use educoin_core::address::{Address, InvalidAddress};
+
+const ADDRESS_BOOK_FILE: &str = "address_book.txt";
+
+/// Human friendly labels for addresses, persisted as `label address` lines in the data dir.
+pub struct AddressBook {
+    path: PathBuf,
+    entries: BTreeMap<String, Address>,
+}
+
+impl AddressBook {
+    /// Open the address book in `data_dir`, starting empty if none was saved yet.
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        let path = data_dir.join(ADDRESS_BOOK_FILE);
+        let mut entries = BTreeMap::new();
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                    let parsed = line
+                        .split_once(' ')
+                        .and_then(|(label, address)| Some((label, address.parse().ok()?)));
+                    let Some((label, address)) = parsed else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("malformed address book entry: {line}"),
+                        ));
+                    };
+                    entries.insert(label.to_string(), address);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(AddressBook { path, entries })
+    }
+
+    /// Register `label` for `address`, replacing any previous address with that label.
+    pub fn insert(&mut self, label: &str, address: Address) -> Result<(), AddressError> {
+        if label.parse::<Address>().is_ok() || label.contains(char::is_whitespace) {
+            return Err(AddressError::InvalidLabel(label.to_string()));
+        }
+
+        self.entries.insert(label.to_string(), address);
+        self.save().map_err(AddressError::Io)
+    }
+
+    /// Accept either a raw address or a registered label.
+    pub fn resolve(&self, address_or_label: &str) -> Result<Address, AddressError> {
+        if let Some(address) = self.entries.get(address_or_label) {
+            return Ok(*address);
+        }
+
+        Ok(address_or_label.parse()?)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &Address)> {
+        self.entries.iter()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(label, address)| format!("{label} {address}\n"))
+            .collect();
+
+        fs::write(&self.path, contents)
+    }
+}
+
+#[derive(Debug)]
+pub enum AddressError {
+    Invalid(String),
+    InvalidLabel(String),
+    UnregisteredName(String),
+    Io(io::Error),
+}
+
+impl From<InvalidAddress> for AddressError {
+    fn from(InvalidAddress(s): InvalidAddress) -> Self {
+        AddressError::Invalid(s)
+    }
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Invalid(s) => write!(f, "`{s}` is neither a known label nor an address"),
+            AddressError::InvalidLabel(label) => write!(f, "`{label}` can't be used as a label"),
+            AddressError::UnregisteredName(name) => {
+                write!(f, "nobody registered the name `{name}`")
+            }
+            AddressError::Io(e) => write!(f, "failed to save address book: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::process::Command;
+use tracing::{error, warn};
+
+/// Consensus anomalies worth waking somebody up for.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// A peer voted for a different height than ours, so our chains diverged.
+    Fork,
+    /// A full batch contained invalid transactions and this node refused to vote.
+    FailedRound,
+    /// A block about to be persisted failed validation and was dropped.
+    RejectedBlock,
+    /// Every peer went away, no block can be committed until they come back.
+    QuorumLost,
+    /// A validator voted for two different blocks in the same round.
+    Equivocation,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub height: u32,
+    pub detail: String,
+}
+
+/// Notifies the outside world about [`Alert`]s by POSTing them as JSON to a
+/// webhook and/or running a shell command with the alert in `EDUCOIN_ALERT_*`
+/// environment variables.
+///
+/// Both run on a background thread, a slow or dead endpoint never holds up
+/// the node.
+pub struct Alerts {
+    webhook: Option<String>,
+    command: Option<String>,
+}
+
+impl Alerts {
+    pub fn new(webhook: Option<String>, command: Option<String>) -> Self {
+        Alerts { webhook, command }
+    }
+
+    pub fn fire(&self, alert: Alert) {
+        error!(alert = ?alert.kind, height = alert.height, detail = %alert.detail, "consensus anomaly");
+
+        if let Some(webhook) = self.webhook.clone() {
+            let alert = alert.clone();
+            async_std::task::spawn_blocking(move || {
+                if let Err(e) = ureq::post(&webhook).send_json(&alert) {
+                    warn!(%webhook, error = %e, "failed to deliver alert to webhook");
+                }
+            });
+        }
+
+        if let Some(command) = self.command.clone() {
+            async_std::task::spawn_blocking(move || {
+                let kind = serde_json::to_value(alert.kind)
+                    .ok()
+                    .and_then(|kind| kind.as_str().map(str::to_string))
+                    .unwrap_or_default();
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .env("EDUCOIN_ALERT_KIND", kind)
+                    .env("EDUCOIN_ALERT_HEIGHT", alert.height.to_string())
+                    .env("EDUCOIN_ALERT_DETAIL", &alert.detail)
+                    .status();
+                match status {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => warn!(%command, %status, "alert command failed"),
+                    Err(e) => warn!(%command, error = %e, "failed to run alert command"),
+                }
+            });
+        }
+    }
+}
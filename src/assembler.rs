@@ -0,0 +1,662 @@
+use libp2p::PeerId;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::ops::Bound::{Excluded, Unbounded};
+
+use crate::address::Address;
+use crate::block::{self, Block, Tip};
+use crate::ledger::Ledger;
+use crate::merkle::Hash;
+use crate::transaction::{Transaction, TransactionId};
+use crate::validators::ValidatorKeys;
+
+/// Transactions per block, until governance changes it, see [`crate::governance`].
+pub const BLOCK_SIZE: usize = 10;
+
+/// What a block is checked against when it is voted for, and again from
+/// scratch before it is persisted, whatever was checked on the way.
+pub struct BlockRules<'a> {
+    pub chain_id: &'a str,
+    /// The block has to follow it, and be timed after it.
+    pub tip: Tip,
+    /// Latest timestamp a block may have: the local time plus the drift
+    /// allowed between clocks, see [`crate::cli::Cli::max_clock_drift`].
+    pub latest: u64,
+    pub block_size: usize,
+    pub blacklist: &'a HashSet<Address>,
+    pub validator_keys: &'a ValidatorKeys,
+    /// Balances the transactions spend from.
+    pub ledger: &'a Ledger,
+    /// Those who may propose a block to vote on.
+    pub validators: &'a BTreeSet<PeerId>,
+}
+
+impl BlockRules<'_> {
+    /// Check that `block` is the one with `agreed_hash`, the hash voted on or
+    /// the one its proof-of-work is for, and its transactions correctly signed,
+    /// see [`check_sealed`], and that it [fits on the tip](Self::check_on_tip).
+    pub fn check(
+        &self,
+        block: &Block,
+        agreed_hash: &Hash,
+        proposer: Option<&PeerId>,
+    ) -> Result<(), InvalidBlock> {
+        check_sealed(self.chain_id, block, agreed_hash)?;
+        self.check_on_tip(block, proposer)
+    }
+
+    /// Check that `block` follows the tip, is timed after it but not in the
+    /// future, and fits in a block, that its `proposer` (for blocks voted on)
+    /// is a validator, and that no transaction is from a sender that is
+    /// blacklisted, retired or overdrawn, or has a nonce other than the one
+    /// that follows the sender's last.
+    pub fn check_on_tip(
+        &self,
+        block: &Block,
+        proposer: Option<&PeerId>,
+    ) -> Result<(), InvalidBlock> {
+        if block.height != self.tip.height + 1 || block.prev_hash != self.tip.hash {
+            return Err(InvalidBlock::NotOnTip);
+        }
+        if block.timestamp <= self.tip.timestamp || block.timestamp > self.latest {
+            return Err(InvalidBlock::BadTimestamp(block.timestamp));
+        }
+        if block.transactions.len() > self.block_size {
+            return Err(InvalidBlock::TooLarge(block.transactions.len()));
+        }
+        if let Some(proposer) = proposer.filter(|proposer| !self.validators.contains(proposer)) {
+            return Err(InvalidBlock::NotAValidator(*proposer));
+        }
+
+        let overdrawn = self.ledger.overdrafts(&block.transactions);
+        let bad_nonces = self.ledger.bad_nonces(&block.transactions);
+        for transaction in &block.transactions {
+            let sender = Address::from(&transaction.public_key);
+            if self.blacklist.contains(&sender) {
+                return Err(InvalidBlock::BlacklistedSender(transaction.id()));
+            }
+            if self.validator_keys.is_retired(&sender) {
+                return Err(InvalidBlock::RetiredKey(transaction.id()));
+            }
+            if bad_nonces.contains(&transaction.id()) {
+                return Err(InvalidBlock::BadNonce(transaction.id()));
+            }
+            if overdrawn.contains(&transaction.id()) {
+                return Err(InvalidBlock::Overdraft(transaction.id()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that `block` is the one with `agreed_hash` and that every transaction
+/// is correctly signed for `chain_id`: the part of [`BlockRules::check`] that
+/// doesn't depend on the blocks before it, so stored blocks can be checked
+/// side by side.
+pub fn check_sealed(chain_id: &str, block: &Block, agreed_hash: &Hash) -> Result<(), InvalidBlock> {
+    // recomputed rather than taken from the block, which may have been put together from anything
+    let root = block::merkle_root(&block.transactions);
+    if block::hash(block.height, block.timestamp, &block.prev_hash, &root) != *agreed_hash {
+        return Err(InvalidBlock::HashMismatch);
+    }
+    match block
+        .transactions
+        .iter()
+        .find(|transaction| !transaction.verify(chain_id))
+    {
+        Some(transaction) => Err(InvalidBlock::BadSignature(transaction.id())),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidBlock {
+    HashMismatch,
+    NotOnTip,
+    BadTimestamp(u64),
+    TooLarge(usize),
+    NotAValidator(PeerId),
+    BadSignature(TransactionId),
+    BlacklistedSender(TransactionId),
+    RetiredKey(TransactionId),
+    BadNonce(TransactionId),
+    Overdraft(TransactionId),
+}
+
+impl fmt::Display for InvalidBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidBlock::HashMismatch => write!(f, "the block isn't the one agreed on"),
+            InvalidBlock::NotOnTip => write!(f, "the block doesn't follow our tip"),
+            InvalidBlock::BadTimestamp(timestamp) => {
+                write!(
+                    f,
+                    "timestamp {timestamp} is before the tip's or in the future"
+                )
+            }
+            InvalidBlock::TooLarge(size) => write!(f, "{size} transactions don't fit in a block"),
+            InvalidBlock::NotAValidator(proposer) => {
+                write!(f, "proposer {proposer} isn't a validator")
+            }
+            InvalidBlock::BadSignature(id) => write!(f, "transaction {id} has a bad signature"),
+            InvalidBlock::BlacklistedSender(id) => {
+                write!(f, "transaction {id} is signed by a blacklisted address")
+            }
+            InvalidBlock::RetiredKey(id) => {
+                write!(f, "transaction {id} is signed by a retired key")
+            }
+            InvalidBlock::BadNonce(id) => {
+                write!(f, "transaction {id} doesn't follow its sender's last nonce")
+            }
+            InvalidBlock::Overdraft(id) => write!(f, "transaction {id} overdraws its sender"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidBlock {}
+
+/// Holds the mempool and decides which transactions go into the next block.
+///
+/// The mempool is keyed by sender and nonce, with an index by transaction
+/// id, so taking transactions in and out is logarithmic in its size. A
+/// transaction already waiting is never stored twice, however many peers
+/// relay it, and neither is a second one with the same sender and nonce.
+///
+/// Blocks are put together by priority, highest fee first and oldest first
+/// among equal fees, except that each sender's transactions go in nonce
+/// order, starting after its last committed nonce: a transaction is passed
+/// over until its sender's previous nonce is committed or taken before it.
+/// Votes and transactions arrive independently, so a node can have its
+/// quorum before it has the block's transactions, or more transactions than
+/// fit in one block. The assembler only hands out a block once all of it is
+/// present, and offers each height's block for validation exactly once,
+/// however many transactions are queued behind it.
+pub struct BlockAssembler {
+    mempool: BTreeMap<(Address, u64), Waiting>,
+    // sender and nonce of each transaction in the mempool, by id
+    ids: HashMap<TransactionId, (Address, u64)>,
+    // arrival of the next transaction admitted
+    arrivals: u64,
+    // height whose block was last handed out by `candidate`
+    offered: Option<u32>,
+    block_size: usize,
+}
+
+// A transaction in the mempool, and when it arrived to break ties between equal fees.
+struct Waiting {
+    transaction: Transaction,
+    arrival: u64,
+}
+
+impl Waiting {
+    // Ordered highest fee first, then oldest first.
+    fn priority(&self) -> (u64, Reverse<u64>) {
+        (self.transaction.fee, Reverse(self.arrival))
+    }
+}
+
+impl BlockAssembler {
+    pub fn with_capacity(capacity: usize) -> Self {
+        BlockAssembler {
+            mempool: BTreeMap::new(),
+            ids: HashMap::with_capacity(capacity),
+            arrivals: 0,
+            offered: None,
+            block_size: BLOCK_SIZE,
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Take `size` transactions into the blocks from the next one on.
+    pub fn set_block_size(&mut self, size: usize) {
+        self.block_size = size;
+    }
+
+    /// Add `transaction` to the mempool. Returns `false`, leaving the mempool
+    /// as it was, if it or another transaction with the same sender and nonce
+    /// is already waiting.
+    pub fn admit(&mut self, transaction: Transaction) -> bool {
+        let key = (Address::from(&transaction.public_key), transaction.nonce);
+        let id = transaction.id();
+        if self.ids.contains_key(&id) || self.mempool.contains_key(&key) {
+            return false;
+        }
+
+        self.ids.insert(id, key);
+        let arrival = self.arrivals;
+        self.arrivals += 1;
+        self.mempool.insert(
+            key,
+            Waiting {
+                transaction,
+                arrival,
+            },
+        );
+        true
+    }
+
+    /// Drop the transactions whose nonce `ledger` has seen committed since
+    /// they were admitted.
+    pub fn drop_stale(&mut self, ledger: &Ledger) {
+        let ids = &mut self.ids;
+        self.mempool.retain(|(sender, nonce), waiting| {
+            let stale = *nonce <= ledger.nonce(sender);
+            if stale {
+                ids.remove(&waiting.transaction.id());
+            }
+            !stale
+        });
+    }
+
+    /// Whether the transaction `id` is waiting in the mempool.
+    pub fn holds(&self, id: &TransactionId) -> bool {
+        self.ids.contains_key(id)
+    }
+
+    /// Whether a transaction with the sender and nonce of `transaction` is
+    /// waiting in the mempool, it or another one.
+    pub fn holds_nonce(&self, transaction: &Transaction) -> bool {
+        self.mempool
+            .contains_key(&(Address::from(&transaction.public_key), transaction.nonce))
+    }
+
+    /// The transactions waiting, by sender and then nonce.
+    pub fn mempool(&self) -> impl ExactSizeIterator<Item = &Transaction> + Clone {
+        self.mempool.values().map(|waiting| &waiting.transaction)
+    }
+
+    /// The transactions of the block at `height` on top of `ledger`, once
+    /// they are all present. Returns them only the first time for each
+    /// height, so the block is proposed once.
+    pub fn candidate(&mut self, height: u32, ledger: &Ledger) -> Option<Vec<&Transaction>> {
+        let next_block = self.next_block(ledger);
+        if next_block.len() < self.block_size || self.offered == Some(height) {
+            return None;
+        }
+
+        self.offered = Some(height);
+        Some(
+            next_block
+                .iter()
+                .map(|key| &self.mempool[key].transaction)
+                .collect(),
+        )
+    }
+
+    /// Offer the block at the current height once more, when the turn to
+    /// propose it came back after a round that timed out.
+    pub fn reoffer(&mut self) {
+        self.offered = None;
+    }
+
+    /// Whether every one of `transactions` is waiting in the mempool.
+    pub fn holds_all(&self, transactions: impl IntoIterator<Item = TransactionId>) -> bool {
+        transactions.into_iter().all(|id| self.holds(&id))
+    }
+
+    /// Take the block made of `transactions` out of the mempool, as they were
+    /// proposed, whichever of them are in it.
+    pub fn take_proposed(&mut self, transactions: Vec<Transaction>) -> Vec<Transaction> {
+        for transaction in &transactions {
+            if let Some(key) = self.ids.remove(&transaction.id()) {
+                self.mempool.remove(&key);
+            }
+        }
+        transactions
+    }
+
+    /// Take the next block on top of `ledger` out of the mempool: a full
+    /// one, or whatever there is (even nothing) when `partial` is set. `None`
+    /// while a full block isn't there yet.
+    pub fn take(&mut self, partial: bool, ledger: &Ledger) -> Option<Vec<Transaction>> {
+        let next_block = self.next_block(ledger);
+        if !partial && next_block.len() < self.block_size {
+            return None;
+        }
+
+        let transactions: Vec<_> = next_block
+            .iter()
+            .filter_map(|key| self.mempool.remove(key))
+            .map(|waiting| waiting.transaction)
+            .collect();
+        for transaction in &transactions {
+            self.ids.remove(&transaction.id());
+        }
+        Some(transactions)
+    }
+
+    // Sender and nonce of the transactions of the next block on top of `ledger`, in block order:
+    // each time the transaction with the highest priority among every sender's next nonce.
+    fn next_block(&self, ledger: &Ledger) -> Vec<(Address, u64)> {
+        let mut next = BinaryHeap::new();
+        let mut sender = self.mempool.keys().next().map(|&(sender, _)| sender);
+        while let Some(current) = sender {
+            let key = (current, ledger.nonce(&current) + 1);
+            if let Some(waiting) = self.mempool.get(&key) {
+                next.push((waiting.priority(), key));
+            }
+            sender = self
+                .mempool
+                .range((Excluded((current, u64::MAX)), Unbounded))
+                .next()
+                .map(|(&(sender, _), _)| sender);
+        }
+
+        let mut next_block = Vec::with_capacity(self.block_size);
+        while next_block.len() < self.block_size {
+            let Some((_, (sender, nonce))) = next.pop() else {
+                break;
+            };
+            next_block.push((sender, nonce));
+            let key = (sender, nonce + 1);
+            if let Some(waiting) = self.mempool.get(&key) {
+                next.push((waiting.priority(), key));
+            }
+        }
+        next_block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Chain;
+    use crate::genesis::Genesis;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    fn assembler_with(count: usize) -> BlockAssembler {
+        let keypair = Keypair::generate();
+        let mut assembler = BlockAssembler::with_capacity(count);
+        for i in 0..count {
+            let data = format!("transaction {i}");
+            let nonce = i as u64 + 1;
+            assembler.admit(
+                block_on(Transaction::sign(&keypair, "test", data.as_bytes(), nonce)).unwrap(),
+            );
+        }
+        assembler
+    }
+
+    #[test]
+    fn waits_for_a_full_block() {
+        let mut assembler = assembler_with(BLOCK_SIZE - 1);
+        assert!(assembler.candidate(1, &Ledger::default()).is_none());
+        assert!(assembler.take(false, &Ledger::default()).is_none());
+
+        let block = assembler.take(true, &Ledger::default()).unwrap();
+        assert_eq!(block.len(), BLOCK_SIZE - 1);
+        assert_eq!(assembler.mempool().len(), 0);
+    }
+
+    #[test]
+    fn takes_the_oldest_transactions() {
+        let mut assembler = assembler_with(BLOCK_SIZE + 3);
+        let expected: Vec<_> = assembler
+            .mempool()
+            .take(BLOCK_SIZE)
+            .map(Transaction::id)
+            .collect();
+
+        let block = assembler.take(false, &Ledger::default()).unwrap();
+        let taken: Vec<_> = block.iter().map(Transaction::id).collect();
+        assert_eq!(taken, expected);
+        assert_eq!(assembler.mempool().len(), 3);
+    }
+
+    #[test]
+    fn checks_blocks_from_scratch() {
+        let keypair = Keypair::generate();
+        let (sender, proposer) = (Address::from(&keypair.public()), PeerId::random());
+        let (blacklist, validator_keys) = (HashSet::new(), ValidatorKeys::default());
+        let ledger = Ledger::with_genesis(&Genesis::with_allocations(&[(sender, 10)]));
+        let validators = BTreeSet::from([proposer]);
+        let rules = BlockRules {
+            chain_id: "test",
+            tip: Chain::new().tip(),
+            latest: 100,
+            block_size: 2,
+            blacklist: &blacklist,
+            validator_keys: &validator_keys,
+            ledger: &ledger,
+            validators: &validators,
+        };
+        let block_with_nonces = |data: &[(&str, u64)]| {
+            let transactions = data
+                .iter()
+                .map(|(data, nonce)| {
+                    block_on(Transaction::sign(&keypair, "test", data.as_bytes(), *nonce)).unwrap()
+                })
+                .collect();
+            Block::new(1, 60, rules.tip.hash, transactions)
+        };
+        let block = |data: &[&str]| {
+            let numbered: Vec<_> = data.iter().zip(1..).map(|(data, i)| (*data, i)).collect();
+            block_with_nonces(&numbered)
+        };
+        let check = |block: &Block, rules: &BlockRules| rules.check(block, &block.hash, None);
+
+        let valid = block(&["hello", &format!("send {sender} 10")]);
+        assert!(rules.check(&valid, &valid.hash, Some(&proposer)).is_ok());
+        assert!(matches!(
+            rules.check(&valid, &[0; 32], None),
+            Err(InvalidBlock::HashMismatch)
+        ));
+        assert!(matches!(
+            rules.check(&valid, &valid.hash, Some(&PeerId::random())),
+            Err(InvalidBlock::NotAValidator(_))
+        ));
+        let mut moved = valid;
+        moved.transactions.pop();
+        assert!(
+            matches!(check(&moved, &rules), Err(InvalidBlock::HashMismatch)),
+            "the hash is recomputed from the transactions"
+        );
+
+        let orphan = Block::new(1, 60, [9; 32], Vec::new());
+        assert!(matches!(
+            check(&orphan, &rules),
+            Err(InvalidBlock::NotOnTip)
+        ));
+        let parent = Block::new(1, 60, rules.tip.hash, Vec::new());
+        let tip = Tip {
+            height: 1,
+            hash: parent.hash,
+            timestamp: parent.timestamp,
+        };
+        let on_parent = |timestamp| Block::new(2, timestamp, parent.hash, Vec::new());
+        for timestamp in [59, 60, 101] {
+            assert!(matches!(
+                check(&on_parent(timestamp), &BlockRules { tip, ..rules }),
+                Err(InvalidBlock::BadTimestamp(_))
+            ));
+        }
+        for timestamp in [61, 100] {
+            assert!(check(&on_parent(timestamp), &BlockRules { tip, ..rules }).is_ok());
+        }
+        let large = block(&["a", "b", "c"]);
+        assert!(matches!(
+            check(&large, &rules),
+            Err(InvalidBlock::TooLarge(3))
+        ));
+        for nonces in [[0, 1], [1, 1], [2, 1], [1, 3]] {
+            assert!(matches!(
+                check(
+                    &block_with_nonces(&[("a", nonces[0]), ("b", nonces[1])]),
+                    &rules
+                ),
+                Err(InvalidBlock::BadNonce(_))
+            ));
+        }
+        let overdraft = block(&[&format!("send {sender} 11")]);
+        assert!(matches!(
+            check(&overdraft, &rules),
+            Err(InvalidBlock::Overdraft(_))
+        ));
+        assert!(matches!(
+            check(
+                &block(&["hello"]),
+                &BlockRules {
+                    chain_id: "another-chain",
+                    ..rules
+                }
+            ),
+            Err(InvalidBlock::BadSignature(_))
+        ));
+        let blacklist = HashSet::from([sender]);
+        assert!(matches!(
+            check(
+                &block(&["hello"]),
+                &BlockRules {
+                    blacklist: &blacklist,
+                    ..rules
+                }
+            ),
+            Err(InvalidBlock::BlacklistedSender(_))
+        ));
+    }
+
+    #[test]
+    fn blocks_take_the_new_size_once_resized() {
+        let mut assembler = assembler_with(BLOCK_SIZE + 3);
+        assembler.set_block_size(BLOCK_SIZE + 2);
+        assert_eq!(
+            assembler.candidate(1, &Ledger::default()).unwrap().len(),
+            BLOCK_SIZE + 2
+        );
+
+        assert_eq!(
+            assembler.take(false, &Ledger::default()).unwrap().len(),
+            BLOCK_SIZE + 2
+        );
+        assert_eq!(assembler.mempool().len(), 1);
+    }
+
+    #[test]
+    fn takes_proposed_blocks_out_of_the_mempool() {
+        let mut assembler = assembler_with(3);
+        let foreign = assembler_with(1)
+            .take(true, &Ledger::default())
+            .unwrap()
+            .remove(0);
+        let last =
+            Transaction::decode(assembler.mempool().nth(2).unwrap().to_bytes().into()).unwrap();
+        assert!(assembler.holds_all([last.id()]));
+        assert!(!assembler.holds_all([last.id(), foreign.id()]));
+
+        // another node proposed our last transaction and one we never saw
+        let proposed = vec![last, foreign];
+        let ids: Vec<_> = proposed.iter().map(Transaction::id).collect();
+        let block = assembler.take_proposed(proposed);
+        assert!(block.iter().map(Transaction::id).eq(ids));
+        assert_eq!(assembler.mempool().len(), 2);
+    }
+
+    #[test]
+    fn takes_the_highest_fees_first() {
+        let mut assembler = assembler_with(BLOCK_SIZE);
+        for (i, fee) in [1, 5, 1].into_iter().enumerate() {
+            // each from its own sender, so nonces don't hold them back
+            let (keypair, data) = (Keypair::generate(), format!("paying {i}"));
+            let transaction = Transaction::sign_with_fee(&keypair, "test", data.as_bytes(), 1, fee);
+            assembler.admit(block_on(transaction).unwrap());
+        }
+        let candidate = assembler.candidate(1, &Ledger::default()).unwrap();
+        let fees: Vec<_> = candidate[..4].iter().map(|t| t.fee).collect();
+        assert_eq!(fees, [5, 1, 1, 0]);
+
+        assert_eq!(
+            assembler.take(false, &Ledger::default()).unwrap().len(),
+            BLOCK_SIZE
+        );
+        assert_eq!(assembler.mempool().len(), 3, "the latest free ones wait");
+    }
+
+    #[test]
+    fn keeps_each_senders_nonces_in_order() {
+        let mut assembler = BlockAssembler::with_capacity(4);
+        let keypair = Keypair::generate();
+        let sign = |data: &str, nonce, fee| {
+            block_on(Transaction::sign_with_fee(
+                &keypair,
+                "test",
+                data.as_bytes(),
+                nonce,
+                fee,
+            ))
+            .unwrap()
+        };
+        for (nonce, fee) in [(2, 0), (3, 9), (1, 0)] {
+            assert!(assembler.admit(sign("paying", nonce, fee)));
+        }
+        assert!(
+            !assembler.admit(sign("double spend", 3, 20)),
+            "the nonce is taken"
+        );
+        assembler.set_block_size(3);
+        let candidate = assembler.candidate(1, &Ledger::default()).unwrap();
+        let nonces: Vec<_> = candidate.iter().map(|t| t.nonce).collect();
+        assert_eq!(nonces, [1, 2, 3], "a higher fee doesn't jump a lower nonce");
+
+        let mut ledger = Ledger::default();
+        ledger.apply(1, &[sign("elsewhere", 1, 0), sign("elsewhere", 2, 0)]);
+        assembler.drop_stale(&ledger);
+        let nonces: Vec<_> = assembler.mempool().map(|t| t.nonce).collect();
+        assert_eq!(nonces, [3]);
+        assert!(assembler.admit(sign("paying again", 4, 0)));
+    }
+
+    #[test]
+    fn blocks_wait_for_nonces_missing_before() {
+        let mut assembler = assembler_with(BLOCK_SIZE - 1);
+        let keypair = Keypair::generate();
+        let sign = |nonce: u64| {
+            let data = format!("nonce {nonce}");
+            block_on(Transaction::sign(&keypair, "test", data.as_bytes(), nonce)).unwrap()
+        };
+        assembler.admit(sign(2));
+        assert!(
+            assembler.candidate(1, &Ledger::default()).is_none(),
+            "nonce 1 is missing"
+        );
+
+        assembler.admit(sign(1));
+        let candidate = assembler.candidate(1, &Ledger::default()).unwrap();
+        assert_eq!(candidate.len(), BLOCK_SIZE);
+        assembler.take(true, &Ledger::default()).unwrap();
+        let left: Vec<_> = assembler.mempool().map(|t| t.nonce).collect();
+        assert_eq!(left, [2], "the other sender's second one");
+    }
+
+    #[test]
+    fn stores_each_transaction_once() {
+        let mut assembler = assembler_with(2);
+        let echo =
+            Transaction::decode(assembler.mempool().next().unwrap().to_bytes().into()).unwrap();
+        assert!(assembler.holds(&echo.id()));
+        assert!(!assembler.admit(echo));
+        assert_eq!(assembler.mempool().len(), 2);
+
+        // once taken into a block it is no longer waiting
+        let block = assembler.take(true, &Ledger::default()).unwrap();
+        let taken = Transaction::decode(block[0].to_bytes().into()).unwrap();
+        assert!(!assembler.holds(&taken.id()));
+        assert!(assembler.admit(taken));
+    }
+
+    #[test]
+    fn offers_each_height_once() {
+        let (mut assembler, mut ledger) = (assembler_with(2 * BLOCK_SIZE), Ledger::default());
+        assert!(assembler.candidate(1, &ledger).is_some());
+        assert!(assembler.candidate(1, &ledger).is_none());
+
+        // a full block is still queued behind the one just committed
+        let block = assembler.take(false, &ledger).unwrap();
+        ledger.apply(1, &block);
+        assert!(assembler.candidate(2, &ledger).is_some());
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const AUDIT_LOG_FILE: &str = "audit.jsonl";
+
+/// Significant things that happened on this node, kept for reviewing a session afterwards.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    BlockCommitted {
+        height: u32,
+        merkle_root: String,
+        transactions: Vec<String>,
+    },
+    VoteReceived {
+        height: u32,
+        voter: String,
+    },
+    /// Both signed votes of a validator that voted for two blocks in one round, hex encoded.
+    Equivocation {
+        height: u32,
+        round: u32,
+        voter: String,
+        votes: [String; 2],
+    },
+    TransactionRejected {
+        tx_id: Option<String>,
+        peer_id: Option<String>,
+        reason: String,
+    },
+    PeerBlacklisted {
+        peer_id: String,
+        reason: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    timestamp_ms: u128,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+/// Append-only JSONL log of [`AuditEvent`]s in the data dir, one event per line.
+pub struct AuditLog {
+    file: File,
+}
+
+impl AuditLog {
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(data_dir.join(AUDIT_LOG_FILE))?;
+
+        Ok(AuditLog { file })
+    }
+
+    pub fn record(&mut self, event: &AuditEvent) -> io::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let mut line = serde_json::to_vec(&Entry {
+            timestamp_ms,
+            event,
+        })?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+}
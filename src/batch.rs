@@ -0,0 +1,161 @@
+//! Batches of transactions for `/submit --file`, to seed a demo or replay
+//! an `/export --format json` file.
+//!
+//! The file is a JSON array of entries, each either pre-signed (as printed
+//! by `sign-tx`) or data for the wallet to sign, e.g.
+//!
+//! ```text
+//! [
+//!   { "blob": "0a20...", "delegation": "5f3c..." },
+//!   { "data": "send alice 5", "from": "bob" }
+//! ]
+//! ```
+//!
+//! An exported file works too: its transactions are signed again by the
+//! node's default account. Every entry becomes the line that would have
+//! been typed for it, so it goes through the same admission and gossip.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Signed {
+        blob: String,
+        delegation: Option<String>,
+    },
+    Unsigned {
+        data: String,
+        from: Option<String>,
+    },
+}
+
+// A block of an exported file, only its transactions matter here.
+#[derive(Deserialize)]
+struct ExportedBlock {
+    transactions: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum File {
+    Entries(Vec<Entry>),
+    Exported(Vec<ExportedBlock>),
+}
+
+/// Read the batch at `path`, returning the input line for each entry.
+pub fn load(path: &Path) -> Result<Vec<String>, BatchError> {
+    let file: File = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let entries = match file {
+        File::Entries(entries) => entries,
+        File::Exported(blocks) => blocks
+            .into_iter()
+            .flat_map(|block| block.transactions)
+            .collect(),
+    };
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| match entry {
+            Entry::Signed { blob, delegation } => Ok(match delegation {
+                Some(delegation) => format!("/submit {blob} {delegation}"),
+                None => format!("/submit {blob}"),
+            }),
+            // data is typed in as is, it must not turn into a command
+            Entry::Unsigned { data, .. } if data.starts_with('/') || data.contains('\n') => {
+                Err(BatchError::NotTransactionData(index))
+            }
+            Entry::Unsigned { data, from } => Ok(match from {
+                Some(label) => format!("--from {label} {data}"),
+                None => data,
+            }),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    NotTransactionData(usize),
+}
+
+impl From<io::Error> for BatchError {
+    fn from(e: io::Error) -> Self {
+        BatchError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BatchError {
+    fn from(e: serde_json::Error) -> Self {
+        BatchError::Json(e)
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Io(e) => write!(f, "{e}"),
+            BatchError::Json(e) => write!(f, "not a batch of transactions: {e}"),
+            BatchError::NotTransactionData(index) => {
+                write!(
+                    f,
+                    "entry {index} holds a command or line break, not transaction data"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(contents: &str) -> Result<Vec<String>, BatchError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch.json");
+        fs::write(&path, contents).unwrap();
+        load(&path)
+    }
+
+    #[test]
+    fn turns_entries_into_input_lines() {
+        let lines = load_str(
+            r#"[
+                { "blob": "0a01", "delegation": "ff" },
+                { "blob": "0a02" },
+                { "data": "send alice 5", "from": "bob" },
+                { "data": "hello" }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "/submit 0a01 ff",
+                "/submit 0a02",
+                "--from bob send alice 5",
+                "hello"
+            ]
+        );
+
+        let exported =
+            r#"[{ "height": 1, "transactions": [{ "id": "00", "data": "send 00 1" }] }]"#;
+        assert_eq!(load_str(exported).unwrap(), ["send 00 1"]);
+    }
+
+    #[test]
+    fn refuses_commands_in_data() {
+        assert!(matches!(
+            load_str(r#"[{ "data": "hello" }, { "data": "/faucet" }]"#),
+            Err(BatchError::NotTransactionData(1))
+        ));
+    }
+}
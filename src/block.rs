@@ -0,0 +1,237 @@
+//! Committed blocks and the chain they form. Every block names the hash of
+//! the one before it, back to a genesis block that is the same on every node.
+
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::merkle::{Hash, MerkleTree};
+use crate::transaction::{Transaction, TransactionId};
+
+// Prefix of the block hash, so it can't be mistaken for any other hash.
+const BLOCK_HASH_CONTEXT: &[u8] = b"educoin/block/v1\n";
+
+pub struct Block {
+    pub height: u32,
+    /// Seconds since the Unix epoch, as set by the proposer.
+    pub timestamp: u64,
+    pub prev_hash: Hash,
+    /// In block order.
+    pub transactions: Vec<Transaction>,
+    /// Merkle root of the transaction ids, see [`merkle_root`].
+    pub merkle_root: Hash,
+    pub hash: Hash,
+    /// Proof-of-work of a mined block, 0 for voted ones. Not part of the
+    /// block hash, see [`crate::pow`].
+    pub nonce: u64,
+    /// The signed votes a voted block was committed with, see
+    /// [`crate::vote`], empty for mined ones. Not part of the block hash.
+    pub votes: Vec<Vec<u8>>,
+}
+
+impl Block {
+    pub fn new(
+        height: u32,
+        timestamp: u64,
+        prev_hash: Hash,
+        transactions: Vec<Transaction>,
+    ) -> Self {
+        let merkle_root = merkle_root(&transactions);
+        let hash = hash(height, timestamp, &prev_hash, &merkle_root);
+        Block {
+            height,
+            timestamp,
+            prev_hash,
+            transactions,
+            merkle_root,
+            hash,
+            nonce: 0,
+            votes: Vec::new(),
+        }
+    }
+
+    /// The block at height 0: no transactions, no time and nothing before it.
+    pub fn genesis() -> Self {
+        Block::new(0, 0, [0; 32], Vec::new())
+    }
+
+    pub fn transaction_ids(&self) -> impl Iterator<Item = TransactionId> + '_ {
+        self.transactions.iter().map(Transaction::id)
+    }
+}
+
+/// Merkle root of the ids of `transactions`.
+pub fn merkle_root<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> Hash {
+    MerkleTree::from_leaves(
+        transactions
+            .into_iter()
+            .map(|transaction| *transaction.id().as_bytes()),
+    )
+    .root()
+}
+
+/// Hash of a block: its height, timestamp, the hash of the block before it
+/// and the merkle root of its transaction ids.
+pub fn hash(height: u32, timestamp: u64, prev_hash: &Hash, merkle_root: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update(BLOCK_HASH_CONTEXT)
+        .chain_update(height.to_be_bytes())
+        .chain_update(timestamp.to_be_bytes())
+        .chain_update(prev_hash)
+        .chain_update(merkle_root)
+        .finalize()
+        .into()
+}
+
+/// Seconds since the Unix epoch at `time`, as block timestamps count them.
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Where the chain ends: the height, hash and timestamp of the last
+/// committed block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tip {
+    pub height: u32,
+    pub hash: Hash,
+    pub timestamp: u64,
+}
+
+impl Tip {
+    /// Timestamp for the block on this tip made at `now`: the local time,
+    /// unless that isn't past the tip's, as a block's time never goes back.
+    pub fn next_timestamp(&self, now: SystemTime) -> u64 {
+        unix_time(now).max(self.timestamp + 1)
+    }
+}
+
+/// The committed chain, as far as the next block is concerned.
+pub struct Chain {
+    tip: Tip,
+}
+
+impl Default for Chain {
+    fn default() -> Self {
+        Chain::new()
+    }
+}
+
+impl Chain {
+    /// A chain holding only the genesis block.
+    pub fn new() -> Self {
+        let genesis = Block::genesis();
+        Chain {
+            tip: Tip {
+                height: genesis.height,
+                hash: genesis.hash,
+                timestamp: genesis.timestamp,
+            },
+        }
+    }
+
+    /// Pick up at `tip`, as it was before a restart.
+    pub fn resume(tip: Tip) -> Self {
+        Chain { tip }
+    }
+
+    pub fn tip(&self) -> Tip {
+        self.tip
+    }
+
+    /// Append `block` if it is the next one on the tip.
+    pub fn append(&mut self, block: &Block) -> Result<(), ChainError> {
+        if block.height != self.tip.height + 1 {
+            return Err(ChainError::WrongHeight {
+                height: block.height,
+                tip: self.tip.height,
+            });
+        }
+        if block.prev_hash != self.tip.hash {
+            return Err(ChainError::NotOnTip {
+                prev_hash: block.prev_hash,
+                tip: self.tip.hash,
+            });
+        }
+
+        self.tip = Tip {
+            height: block.height,
+            hash: block.hash,
+            timestamp: block.timestamp,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ChainError {
+    WrongHeight { height: u32, tip: u32 },
+    NotOnTip { prev_hash: Hash, tip: Hash },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::WrongHeight { height, tip } => {
+                write!(
+                    f,
+                    "block at height {height} doesn't follow the tip at {tip}"
+                )
+            }
+            ChainError::NotOnTip { prev_hash, tip } => write!(
+                f,
+                "block follows {}, not the tip {}",
+                hex::encode(prev_hash),
+                hex::encode(tip)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn blocks_append_to_the_tip_only() {
+        let keypair = Keypair::generate();
+        let transaction = block_on(Transaction::sign(&keypair, "test", b"send", 1)).unwrap();
+        let mut chain = Chain::new();
+        assert_eq!(chain.tip().hash, Block::genesis().hash);
+
+        let first = Block::new(1, 60, chain.tip().hash, vec![transaction]);
+        let sibling = Block::new(1, 61, chain.tip().hash, Vec::new());
+        assert_ne!(first.hash, sibling.hash);
+        chain.append(&first).unwrap();
+
+        assert!(matches!(
+            chain.append(&Block::new(2, 62, sibling.hash, Vec::new())),
+            Err(ChainError::NotOnTip { .. })
+        ));
+        assert!(matches!(
+            chain.append(&Block::new(3, 62, first.hash, Vec::new())),
+            Err(ChainError::WrongHeight { .. })
+        ));
+        chain
+            .append(&Block::new(2, 62, first.hash, Vec::new()))
+            .unwrap();
+        assert_eq!(chain.tip().height, 2);
+    }
+
+    #[test]
+    fn timestamps_move_past_the_tip() {
+        let mut chain = Chain::new();
+        chain
+            .append(&Block::new(1, 60, chain.tip().hash, Vec::new()))
+            .unwrap();
+        let at = |secs| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+
+        assert_eq!(chain.tip().next_timestamp(at(90)), 90);
+        assert_eq!(chain.tip().next_timestamp(at(60)), 61);
+        assert_eq!(chain.tip().next_timestamp(at(30)), 61);
+    }
+}
@@ -0,0 +1,125 @@
+//! A minimal, trust-based bridge between two workshop chains.
+//!
+//! Coins leave the source chain with `bridge-out <destination chain>
+//! <recipient> <amount>`, which takes them out of the sender's balance and
+//! out of the supply. A relayer, a node of the destination chain started
+//! with `--relay-from`, watches the source chain's blocks over NATS and
+//! submits `mint <withdrawal>` for each withdrawal to its chain, carrying the
+//! signed source transaction in hex as proof.
+//!
+//! The destination credits the recipient once per withdrawal, if the mint is
+//! signed by one of the relayers its genesis trusts and the withdrawal is
+//! validly signed for the source chain. Nothing proves the withdrawal was
+//! committed on the source chain, that is what the relayers are trusted with.
+
+use bytes::Bytes;
+use libp2p::identity::ed25519::PublicKey;
+
+use crate::address::Address;
+use crate::export;
+use crate::transaction::{self, Transaction};
+
+/// Coins leaving the chain for `destination`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub destination: String,
+    pub recipient: Address,
+    pub amount: u64,
+}
+
+impl Withdrawal {
+    pub fn parse(data: &[u8]) -> Option<Withdrawal> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.split_whitespace();
+
+        match (
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+        ) {
+            (Some("bridge-out"), Some(destination), Some(recipient), Some(amount), None) => {
+                Some(Withdrawal {
+                    destination: destination.to_string(),
+                    recipient: recipient.parse().ok()?,
+                    amount: amount.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The `bridge` section of the genesis file, on the destination chain.
+#[derive(Debug, Clone)]
+pub struct BridgeGenesis {
+    /// Chain the withdrawals are signed for.
+    pub source_chain: String,
+    /// Addresses whose mints are accepted.
+    pub relayers: Vec<Address>,
+}
+
+/// The withdrawal carried by `mint <withdrawal hex>`, if `data` is a mint.
+pub fn parse_mint(data: &[u8]) -> Option<Transaction> {
+    let data = std::str::from_utf8(data).ok()?;
+    match data.split_whitespace().collect::<Vec<_>>()[..] {
+        ["mint", withdrawal] => transaction::from_hex(withdrawal).ok(),
+        _ => None,
+    }
+}
+
+/// The `mint` lines a relayer for `chain_id` submits for a source chain
+/// block, as exported to NATS.
+pub fn mints(block: &export::Block, chain_id: &str) -> Vec<String> {
+    block
+        .transactions
+        .iter()
+        .filter_map(|exported| {
+            let withdrawal = Withdrawal::parse(exported.data.as_bytes())?;
+            (withdrawal.destination == chain_id).then_some(())?;
+            let sender: Address = exported.sender.parse().ok()?;
+            let source = Transaction {
+                public_key: PublicKey::try_from_bytes(sender.as_bytes()).ok()?,
+                signature: Bytes::from(hex::decode(&exported.signature).ok()?),
+                data: Bytes::from(exported.data.clone()),
+                fee: exported.fee,
+                nonce: exported.nonce,
+            };
+            Some(format!("mint {}", hex::encode(source.to_bytes())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn relays_withdrawals_to_this_chain_only() {
+        let keypair = Keypair::generate();
+        let recipient = Address::from(&Keypair::generate().public());
+        let sign = |nonce, data: String| {
+            block_on(Transaction::sign(
+                &keypair,
+                "class-a",
+                data.as_bytes(),
+                nonce,
+            ))
+            .unwrap()
+        };
+        let transactions = [
+            sign(1, format!("bridge-out class-b {recipient} 25")),
+            sign(2, format!("bridge-out class-c {recipient} 5")),
+            sign(3, format!("send {recipient} 1")),
+        ];
+
+        let mints = mints(&export::Block::new(3, &transactions), "class-b");
+        assert_eq!(mints.len(), 1);
+        let withdrawal = parse_mint(mints[0].as_bytes()).unwrap();
+        assert_eq!(withdrawal.id(), transactions[0].id());
+        assert!(withdrawal.verify("class-a"));
+    }
+}
@@ -0,0 +1,55 @@
+use clap::ValueEnum;
+
+use crate::merkle::Hash;
+use crate::transaction::Transaction;
+
+/// Ways a node can misbehave on purpose, so the class can watch how the
+/// honest nodes cope and tests can check safety under attack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Corrupt the signature of every transaction this node signs.
+    InvalidSignatures,
+    /// Vote for a second, made-up block too, at the same height and round.
+    DoubleVotes,
+    /// Never vote.
+    Withholding,
+    /// Propose two different blocks for the same height and round, one
+    /// without the last transaction of the other.
+    ConflictingProposals,
+}
+
+impl Strategy {
+    /// Blocks voted for once the proposed `block` checks out.
+    pub fn vote_hashes(self, block: Hash) -> Vec<Hash> {
+        match self {
+            Strategy::DoubleVotes => {
+                let mut other = block;
+                other[0] ^= 0xff;
+                vec![block, other]
+            }
+            Strategy::Withholding => Vec::new(),
+            Strategy::InvalidSignatures | Strategy::ConflictingProposals => vec![block],
+        }
+    }
+
+    /// Transactions of a second block to propose along with the `candidate`.
+    pub fn conflicting_block<'a>(
+        self,
+        candidate: &[&'a Transaction],
+    ) -> Option<Vec<&'a Transaction>> {
+        match (self, candidate.split_last()) {
+            (Strategy::ConflictingProposals, Some((_, rest))) => Some(rest.to_vec()),
+            _ => None,
+        }
+    }
+
+    pub fn tamper(self, transaction: &mut Transaction) {
+        if self == Strategy::InvalidSignatures {
+            let mut signature = transaction.signature.to_vec();
+            if let Some(byte) = signature.first_mut() {
+                *byte ^= 0xff;
+            }
+            transaction.signature = signature.into();
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use clap::{Parser, Subcommand};
+use libp2p::identity::ed25519::{Keypair, PublicKey};
+use libp2p::{Multiaddr, PeerId};
+use std::error::Error;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::address::Address;
+use crate::byzantine::Strategy;
+use crate::consensus::{Mode, Quorum};
+use crate::logfile::Rotation;
+use crate::transaction::Transaction;
+use crate::{explorer, export, message, network, relay, wallet};
+
+pub const DEFAULT_CHAIN_ID: &str = "educoin-workshop";
+
+#[derive(Parser)]
+#[command(about = "A simple educational blockchain node")]
+pub struct Cli {
+    /// Directory where the node keeps its local state, committed blocks included
+    #[arg(long, default_value = "educoin-data")]
+    pub data_dir: PathBuf,
+
+    /// TCP port to listen on, any free one by default
+    #[arg(long, default_value_t = 0)]
+    pub listen_port: u16,
+
+    /// Dial this peer at startup and always gossip with it, for peers mDNS doesn't find, e.g. on
+    /// cloud VMs or in containers (repeatable)
+    ///
+    /// The address has to name the peer, as in `/ip4/1.2.3.4/tcp/4001/p2p/<peer id>`.
+    #[arg(long = "bootstrap", value_parser = parse_bootstrap_peer)]
+    pub bootstrap_peers: Vec<Multiaddr>,
+
+    /// Key file holding the node's identity key, which also backs its default account. One is
+    /// generated there on the first run, so the peer id and address survive restarts
+    #[arg(long, conflicts_with = "seed")]
+    pub key_file: Option<PathBuf>,
+
+    /// Prefix of every gossipsub topic, as `<prefix>/<topic>`, so several networks can share one
+    /// local network without hearing each other
+    #[arg(long, default_value = "")]
+    pub topic_prefix: String,
+
+    /// Chain signed into every transaction and vote, nodes only accept those of their own chain
+    #[arg(long, default_value = DEFAULT_CHAIN_ID)]
+    pub chain_id: String,
+
+    /// Genesis file defining the native token, its allocations, max supply and block rewards
+    ///
+    /// Every node on the network has to be started with the same file.
+    #[arg(long)]
+    pub genesis: Option<PathBuf>,
+
+    /// Balance credited to an address at genesis, as `<address>=<amount>` (repeatable)
+    ///
+    /// Every node on the network has to be started with the same allocations.
+    #[arg(long = "genesis-balance", value_parser = parse_allocation)]
+    pub genesis_balances: Vec<(Address, u64)>,
+
+    /// Show a live terminal dashboard instead of reading transactions from stdin
+    #[arg(long)]
+    pub dashboard: bool,
+
+    /// Export spans over OTLP/HTTP to this collector, e.g. `http://localhost:4318/v1/traces`
+    #[arg(long)]
+    pub otlp_endpoint: Option<String>,
+
+    /// Also write logs to `logs/node.log` in the data dir
+    #[arg(long)]
+    pub log_file: bool,
+
+    /// Rotate the log file once it grows past this many megabytes
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub log_max_size: u64,
+
+    /// Rotate the log file on a schedule as well
+    #[arg(long, value_enum, default_value_t = Rotation::Daily)]
+    pub log_rotation: Rotation,
+
+    /// POST consensus anomalies (fork, failed round, lost quorum) as JSON to this URL
+    #[arg(long)]
+    pub alert_webhook: Option<String>,
+
+    /// Run this shell command on consensus anomalies, with the alert in `EDUCOIN_ALERT_*` variables
+    #[arg(long)]
+    pub alert_command: Option<String>,
+
+    /// Mirror committed blocks, transactions and balances into `chain.sql` in the data dir, a
+    /// script that builds an SQLite database: `sqlite3 chain.db < chain.sql`
+    #[arg(long)]
+    pub sql_mirror: bool,
+
+    /// POST every committed block as JSON to this URL (repeatable)
+    #[arg(long = "block-webhook")]
+    pub block_webhooks: Vec<String>,
+
+    /// Also POST every transaction accepted into the mempool to the block webhooks
+    #[arg(long)]
+    pub webhook_transactions: bool,
+
+    /// Bridge to the NATS server at this `host:port`, see `--nats-prefix`
+    #[arg(long)]
+    pub nats: Option<String>,
+
+    /// Subjects of the NATS bridge: blocks and accepted transactions are published as JSON on
+    /// `<prefix>.blocks` and `<prefix>.transactions`, pre-signed transactions (hex, as `sign-tx`
+    /// prints them) are ingested from `<prefix>.submit`
+    #[arg(long, default_value = "educoin")]
+    pub nats_prefix: String,
+
+    /// Relay withdrawals to this chain from the chain bridged to NATS under this prefix, by
+    /// submitting a `mint` for each, signed with the default account
+    ///
+    /// The account has to be one of the relayers in the genesis file's `bridge` section.
+    #[arg(long, requires = "nats")]
+    pub relay_from: Option<String>,
+
+    /// Answer JSON-RPC requests on this `host:port`, a line of JSON each, e.g.
+    /// `{"id": 1, "method": "balance", "params": ["alice"]}`
+    #[arg(long)]
+    pub rpc_listen: Option<SocketAddr>,
+
+    /// How blocks get agreed on: proposed and voted on by the validators, or mined
+    #[arg(long, value_enum, default_value_t = Mode::Vote)]
+    pub consensus: Mode,
+
+    /// Leading zero bits the header hash of a mined block needs at first, with `--consensus pow`
+    #[arg(long, default_value_t = 20)]
+    pub difficulty: u32,
+
+    /// Seconds mined blocks should be apart, the difficulty is adjusted to keep them there
+    #[arg(long, default_value_t = 10)]
+    pub target_block_time: u64,
+
+    /// Blocks mined between difficulty adjustments
+    #[arg(long, default_value_t = 10)]
+    pub retarget_interval: u32,
+
+    /// Seconds a block's timestamp may be ahead of the local clock, later blocks are rejected
+    #[arg(long, default_value_t = 15)]
+    pub max_clock_drift: u64,
+
+    /// Share of the validators, every connected peer and this node, that has to vote for a block
+    /// to commit it, rounded up. It has to be more than half, and `1/1` stalls the chain whenever
+    /// a single validator is missing
+    #[arg(long, default_value_t = Quorum::TWO_THIRDS)]
+    pub quorum: Quorum,
+
+    /// Seconds a round of voting on a proposed block may take before its votes are dropped and
+    /// the block is proposed and voted on again in the next round
+    #[arg(long, default_value_t = 30)]
+    pub round_timeout: u64,
+
+    /// Blocks per staking epoch. When set, the nodes with stake bonded at the last epoch boundary
+    /// are the validators whose votes count, once there are any, rather than every connected peer
+    #[arg(long)]
+    pub epoch_length: Option<u32>,
+
+    /// Seconds without any block, vote or gossip message before the node is considered stalled
+    #[arg(long, default_value_t = 120)]
+    pub stall_timeout: u64,
+
+    /// Drop every connection when a stall is detected, so peers are dialed and the mesh rebuilt afresh
+    #[arg(long)]
+    pub restart_on_stall: bool,
+
+    /// Malformed gossip messages a peer may send before it is disconnected and blacklisted
+    #[arg(long, default_value_t = 5)]
+    pub malformed_limit: u32,
+
+    /// Reject transactions signed by this address, both from the mempool and in blocks (repeatable)
+    #[arg(long = "blacklist-address")]
+    pub blacklisted_addresses: Vec<Address>,
+
+    /// Derive the identity key from this seed instead of generating a random one, so a run can be replayed
+    ///
+    /// Every node needs its own seed.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Misbehave on purpose, to see how the honest nodes respond
+    #[arg(long, value_enum)]
+    pub byzantine: Option<Strategy>,
+
+    /// Hand out test coins from the account in this key file to whoever asks with `/faucet`
+    ///
+    /// The faucet address has to be funded with `--genesis-balance` on every node.
+    #[arg(long)]
+    pub faucet_key: Option<PathBuf>,
+
+    /// Coins the faucet sends to each address
+    #[arg(long, default_value_t = 100)]
+    pub faucet_amount: u64,
+
+    /// Gossiped transactions that may wait for their signature check before new ones are dropped
+    #[arg(long, default_value_t = 1024)]
+    pub inbound_queue: usize,
+
+    /// Write every inbound gossip message to this file, with timestamps, for replaying it later
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Feed the messages recorded in this file into the node again, at their original pace
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Generate a new signing key and write it to a key file
+    Keygen {
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Sign a transaction offline and print the signed blob as hex
+    ///
+    /// The blob can be pasted into a running node with `/submit <blob>`. Nodes only publish
+    /// transactions of other keys than their own with a delegation from the key, printed on a
+    /// second line with `--relay` and pasted as `/submit <blob> <delegation>`.
+    SignTx {
+        #[arg(long)]
+        key_file: PathBuf,
+        /// Chain the transaction is meant for
+        #[arg(long, default_value = DEFAULT_CHAIN_ID)]
+        chain_id: String,
+        /// Peer id of the node that will publish the transaction
+        #[arg(long)]
+        relay: Option<PeerId>,
+        /// One above the nonce of the key's last transaction, see `/nonce`
+        #[arg(long)]
+        nonce: u64,
+        data: String,
+    },
+    /// Sign an arbitrary message to prove control of an address
+    SignMessage {
+        #[arg(long)]
+        key_file: PathBuf,
+        message: String,
+    },
+    /// Check a signature produced by `sign-message`
+    VerifyMessage {
+        #[arg(long)]
+        address: Address,
+        #[arg(long)]
+        signature: String,
+        message: String,
+    },
+    /// Static block explorer pages
+    Explorer {
+        #[command(subcommand)]
+        command: ExplorerCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExplorerCommand {
+    /// Render the blocks of an `/export --format json` file into HTML pages, to open in a browser
+    Export {
+        /// File written by `/export --format json`
+        #[arg(long)]
+        blocks: PathBuf,
+        /// Directory the site is written to, `index.html` is its start page
+        #[arg(long)]
+        out: PathBuf,
+    },
+}
+
+// Offline commands never touch the network, so they can be run on an air-gapped machine.
+pub async fn run_offline(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Keygen { out } => {
+            let keypair = Keypair::generate();
+            wallet::save_key_file(&out, &keypair)?;
+            println!("{}", hex::encode(keypair.public().to_bytes()));
+        }
+        Command::SignTx {
+            key_file,
+            chain_id,
+            relay,
+            nonce,
+            data,
+        } => {
+            let keypair = wallet::load_key_file(&key_file)?;
+            let transaction =
+                Transaction::sign(&keypair, &chain_id, data.as_bytes(), nonce).await?;
+            println!("{}", hex::encode(transaction.to_bytes()));
+            if let Some(relay) = relay {
+                println!("{}", hex::encode(relay::delegate(&keypair, &relay).await?));
+            }
+        }
+        Command::SignMessage { key_file, message } => {
+            let keypair = wallet::load_key_file(&key_file)?;
+            let signature = message::sign(&keypair, message.as_bytes()).await?;
+            println!("{}", hex::encode(signature));
+        }
+        Command::VerifyMessage {
+            address,
+            signature,
+            message,
+        } => {
+            let public_key = PublicKey::try_from_bytes(address.as_bytes())?;
+            let signature = hex::decode(signature)?;
+
+            if message::verify(&public_key, message.as_bytes(), &signature) {
+                println!("valid signature by {address}");
+            } else {
+                return Err(format!("signature is not valid for {address}").into());
+            }
+        }
+        Command::Explorer {
+            command: ExplorerCommand::Export { blocks, out },
+        } => {
+            let blocks: Vec<export::Block> = serde_json::from_str(&fs::read_to_string(blocks)?)?;
+            let pages = explorer::render(&blocks, &out)?;
+            println!(
+                "wrote {pages} pages to {}",
+                out.join("index.html").display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_bootstrap_peer(address: &str) -> Result<Multiaddr, String> {
+    let address: Multiaddr = address.parse().map_err(|e| format!("{e}"))?;
+    network::peer_id(&address).ok_or("expected an address ending in `/p2p/<peer id>`")?;
+
+    Ok(address)
+}
+
+fn parse_allocation(allocation: &str) -> Result<(Address, u64), String> {
+    let (address, amount) = allocation
+        .split_once('=')
+        .ok_or("expected `<address>=<amount>`")?;
+    let address = address.parse().map_err(|e| format!("{e}"))?;
+    let amount = amount.parse().map_err(|e| format!("invalid amount: {e}"))?;
+
+    Ok((address, amount))
+}
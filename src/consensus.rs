@@ -0,0 +1,606 @@
+use clap::ValueEnum;
+use libp2p::PeerId;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::merkle::Hash;
+use crate::vote::Vote;
+
+/// The commit rule, kept apart from networking so it can be reasoned about
+/// and tested on its own.
+///
+/// Votes are collected per height, round and validator, the local node's
+/// own vote included, each for the hash of a proposed block. A height starts
+/// at round 0 and moves to the next round when its votes never added up, see
+/// [`Consensus::next_round`]. Only a validator's first vote for a round
+/// counts, a vote for another block after it is kept as evidence of an
+/// [`Equivocation`]. Votes for a later round or height (from a peer that
+/// moved on before us) are kept for when we get there, votes for an earlier
+/// one are dropped.
+pub struct Consensus {
+    height: u32,
+    round: u32,
+    votes: BTreeMap<(u32, u32), HashMap<PeerId, Hash>>,
+    equivocations: Vec<Equivocation>,
+}
+
+/// A validator voting for two different blocks in the same round of a height.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equivocation {
+    pub height: u32,
+    pub round: u32,
+    pub voter: PeerId,
+    /// The block of the vote that was counted.
+    pub first: Hash,
+    pub second: Hash,
+}
+
+/// What became of a vote, see [`Consensus::record_vote`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    /// The voter's first vote for the round.
+    Counted,
+    /// A stale vote, or one the voter cast before.
+    Ignored,
+    /// A vote for another block than the one counted for the voter, which
+    /// stays the only one counted.
+    Conflicting(Equivocation),
+}
+
+impl Default for Consensus {
+    fn default() -> Self {
+        Consensus::resume(1, 0, [])
+    }
+}
+
+impl Consensus {
+    /// Pick up at `round` of `height` with the votes collected before a restart.
+    pub fn resume(
+        height: u32,
+        round: u32,
+        votes: impl IntoIterator<Item = (u32, u32, PeerId, Hash)>,
+    ) -> Self {
+        let mut consensus = Consensus {
+            height,
+            round,
+            votes: BTreeMap::new(),
+            equivocations: Vec::new(),
+        };
+        for (height, round, voter, block) in votes {
+            consensus.record_vote(height, round, voter, block);
+        }
+        consensus
+    }
+
+    /// Height of the block currently being voted on.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Round of voting the current height is in.
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    /// Number of votes collected in the current round.
+    pub fn votes(&self) -> usize {
+        self.votes
+            .get(&(self.height, self.round))
+            .map_or(0, HashMap::len)
+    }
+
+    /// Number of votes for `block` in the current round.
+    pub fn votes_for(&self, block: &Hash) -> usize {
+        self.votes
+            .get(&(self.height, self.round))
+            .map_or(0, |votes| {
+                votes.values().filter(|voted| *voted == block).count()
+            })
+    }
+
+    /// Every vote kept, as `(height, round, voter, block)`, for the current
+    /// round and later ones, earliest first.
+    pub fn recorded_votes(&self) -> impl Iterator<Item = (u32, u32, PeerId, Hash)> + '_ {
+        self.votes.iter().flat_map(|(&(height, round), votes)| {
+            votes
+                .iter()
+                .map(move |(&voter, &block)| (height, round, voter, block))
+        })
+    }
+
+    /// Count `voter`'s vote for `block` in `round` of `height`, unless it is
+    /// stale or the voter already voted in the round. A vote for another
+    /// block than the counted one is kept as an [`Equivocation`], once per
+    /// voter and round.
+    pub fn record_vote(&mut self, height: u32, round: u32, voter: PeerId, block: Hash) -> Recorded {
+        if (height, round) < (self.height, self.round) {
+            return Recorded::Ignored;
+        }
+
+        let votes = self.votes.entry((height, round)).or_default();
+        let Some(&first) = votes.get(&voter) else {
+            votes.insert(voter, block);
+            return Recorded::Counted;
+        };
+        let caught = self.equivocations.iter().any(|equivocation| {
+            (equivocation.height, equivocation.round, equivocation.voter) == (height, round, voter)
+        });
+        if first == block || caught {
+            return Recorded::Ignored;
+        }
+        let equivocation = Equivocation {
+            height,
+            round,
+            voter,
+            first,
+            second: block,
+        };
+        self.equivocations.push(equivocation.clone());
+        Recorded::Conflicting(equivocation)
+    }
+
+    /// Every equivocation caught so far, earliest first.
+    pub fn equivocations(&self) -> &[Equivocation] {
+        &self.equivocations
+    }
+
+    /// Voters for `block` in the current round.
+    pub fn voters_for<'a>(&'a self, block: &'a Hash) -> impl Iterator<Item = PeerId> + 'a {
+        self.votes
+            .get(&(self.height, self.round))
+            .into_iter()
+            .flatten()
+            .filter(move |(_, voted)| *voted == block)
+            .map(|(&voter, _)| voter)
+    }
+
+    /// Whether `block` has votes from a `quorum` of the `validators`, our
+    /// peers and ourselves, in the current round. A node on its own never
+    /// has a quorum.
+    pub fn has_quorum(&self, quorum: Quorum, validators: usize, block: &Hash) -> bool {
+        validators > 1 && self.votes_for(block) >= quorum.of(validators)
+    }
+
+    /// Like [`Consensus::has_quorum`], counting only the votes of `validators`.
+    pub fn has_quorum_of(
+        &self,
+        quorum: Quorum,
+        validators: &BTreeSet<PeerId>,
+        block: &Hash,
+    ) -> bool {
+        let votes = self
+            .votes
+            .get(&(self.height, self.round))
+            .map_or(0, |votes| {
+                votes
+                    .iter()
+                    .filter(|(voter, voted)| validators.contains(voter) && *voted == block)
+                    .count()
+            });
+        validators.len() > 1 && votes >= quorum.of(validators.len())
+    }
+
+    /// Give up on the current round, discarding its votes, and start the
+    /// next one of the same height. Returns the new round.
+    pub fn next_round(&mut self) -> u32 {
+        self.votes.remove(&(self.height, self.round));
+        self.round += 1;
+        self.round
+    }
+
+    /// Move on to the next height, returning the one just committed.
+    pub fn commit(&mut self) -> u32 {
+        let committed = self.height;
+        self.votes.retain(|&(height, _), _| height > committed);
+        self.height += 1;
+        self.round = 0;
+        committed
+    }
+}
+
+/// Whether `votes` hold a quorum of the `validators` for `block` at `height`,
+/// all in one round, e.g. the votes a synced block was committed with.
+pub fn certifies(
+    quorum: Quorum,
+    validators: &BTreeSet<PeerId>,
+    height: u32,
+    block: &Hash,
+    votes: impl IntoIterator<Item = Vote>,
+) -> bool {
+    let mut voters = BTreeMap::<u32, BTreeSet<PeerId>>::new();
+    for vote in votes {
+        if vote.height == height && vote.block_hash == *block && validators.contains(&vote.voter) {
+            voters.entry(vote.round).or_default().insert(vote.voter);
+        }
+    }
+    validators.len() > 1
+        && voters
+            .values()
+            .any(|voters| voters.len() >= quorum.of(validators.len()))
+}
+
+/// How the network agrees on the next block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Validators take turns proposing blocks and vote on them, see [`Consensus`].
+    Vote,
+    /// Every node mines, the first block with enough work wins, see [`crate::pow`].
+    Pow,
+}
+
+/// Share of the validators that has to vote for a block to commit it, as
+/// `<numerator>/<denominator>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quorum {
+    numerator: usize,
+    denominator: usize,
+}
+
+impl Quorum {
+    /// Every validator, so a single one missing stalls the chain.
+    pub const ALL: Quorum = Quorum {
+        numerator: 1,
+        denominator: 1,
+    };
+    /// Tolerates up to a third of the validators being offline.
+    pub const TWO_THIRDS: Quorum = Quorum {
+        numerator: 2,
+        denominator: 3,
+    };
+
+    /// Votes needed out of `validators`, rounded up.
+    pub fn of(self, validators: usize) -> usize {
+        (validators * self.numerator).div_ceil(self.denominator)
+    }
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Quorum::TWO_THIRDS
+    }
+}
+
+impl fmt::Display for Quorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl FromStr for Quorum {
+    type Err = String;
+
+    fn from_str(quorum: &str) -> Result<Self, Self::Err> {
+        let (numerator, denominator) = quorum
+            .split_once('/')
+            .ok_or("expected `<numerator>/<denominator>`, e.g. 2/3")?;
+        let numerator = numerator
+            .parse()
+            .map_err(|e| format!("invalid numerator: {e}"))?;
+        let denominator = denominator
+            .parse()
+            .map_err(|e| format!("invalid denominator: {e}"))?;
+        // two blocks of the same height could each get half the votes or less
+        if numerator <= denominator / 2 || numerator > denominator {
+            return Err("the quorum has to be more than half and at most all validators".into());
+        }
+
+        Ok(Quorum {
+            numerator,
+            denominator,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const VOTERS: usize = 4;
+    const BLOCK: Hash = [1; 32];
+    // competing blocks proposed at every height
+    const BLOCKS: [Hash; 3] = [[1; 32], [2; 32], [3; 32]];
+    const HEIGHTS: u32 = 3;
+
+    #[derive(Debug, Clone)]
+    enum Step {
+        // voter index and how many heights ahead of ours the vote is for
+        Vote(usize, u32),
+        // commit if the validators that are connected right now all voted
+        TryCommit(usize),
+    }
+
+    fn step() -> impl Strategy<Value = Step> {
+        prop_oneof![
+            (0..VOTERS, 0..3u32).prop_map(|(voter, ahead)| Step::Vote(voter, ahead)),
+            (0..=VOTERS).prop_map(Step::TryCommit),
+        ]
+    }
+
+    // any share of the validators over half
+    fn quorum() -> impl Strategy<Value = Quorum> {
+        (1..=12usize)
+            .prop_flat_map(|denominator| (denominator / 2 + 1..=denominator, Just(denominator)))
+            .prop_map(|(numerator, denominator)| Quorum {
+                numerator,
+                denominator,
+            })
+    }
+
+    fn run(steps: &[Step], voters: &[PeerId]) -> Vec<u32> {
+        let mut consensus = Consensus::default();
+        let mut committed = Vec::new();
+        for step in steps {
+            match *step {
+                Step::Vote(voter, ahead) => {
+                    consensus.record_vote(consensus.height() + ahead, 0, voters[voter], BLOCK);
+                }
+                Step::TryCommit(peers) => {
+                    if consensus.has_quorum(Quorum::default(), peers, &BLOCK) {
+                        committed.push(consensus.commit());
+                    }
+                }
+            }
+        }
+        committed
+    }
+
+    #[test]
+    fn needs_our_own_vote_too() {
+        let (us, peer) = (PeerId::random(), PeerId::random());
+        let mut consensus = Consensus::default();
+
+        consensus.record_vote(1, 0, us, BLOCK);
+        assert!(
+            !consensus.has_quorum(Quorum::ALL, 1, &BLOCK),
+            "a lone node has no quorum"
+        );
+        consensus.record_vote(1, 0, peer, BLOCK);
+        assert!(consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+        assert!(!consensus.has_quorum(Quorum::ALL, 3, &BLOCK));
+    }
+
+    #[test]
+    fn votes_for_another_block_dont_count() {
+        let (us, peer) = (PeerId::random(), PeerId::random());
+        let mut consensus = Consensus::default();
+
+        consensus.record_vote(1, 0, us, BLOCK);
+        assert_eq!(
+            consensus.record_vote(1, 0, peer, [2; 32]),
+            Recorded::Counted
+        );
+        assert_eq!(consensus.votes(), 2);
+        assert!(!consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+
+        // changing its mind doesn't help either
+        assert!(matches!(
+            consensus.record_vote(1, 0, peer, BLOCK),
+            Recorded::Conflicting(_)
+        ));
+        assert!(!consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+    }
+
+    #[test]
+    fn conflicting_votes_are_kept_as_evidence_once() {
+        let peer = PeerId::random();
+        let mut consensus = Consensus::default();
+
+        consensus.record_vote(1, 0, peer, BLOCK);
+        assert_eq!(consensus.record_vote(1, 0, peer, BLOCK), Recorded::Ignored);
+        let equivocation = Equivocation {
+            height: 1,
+            round: 0,
+            voter: peer,
+            first: BLOCK,
+            second: [2; 32],
+        };
+        assert_eq!(
+            consensus.record_vote(1, 0, peer, [2; 32]),
+            Recorded::Conflicting(equivocation.clone())
+        );
+        assert_eq!(
+            consensus.record_vote(1, 0, peer, [3; 32]),
+            Recorded::Ignored
+        );
+        assert_eq!(consensus.equivocations(), [equivocation]);
+        assert_eq!(consensus.voters_for(&BLOCK).collect::<Vec<_>>(), [peer]);
+
+        // a vote in another round is no equivocation
+        assert_eq!(
+            consensus.record_vote(1, 1, peer, [2; 32]),
+            Recorded::Counted
+        );
+        assert_eq!(consensus.equivocations().len(), 1);
+    }
+
+    #[test]
+    fn a_new_round_starts_the_count_over() {
+        let (us, peer) = (PeerId::random(), PeerId::random());
+        let mut consensus = Consensus::default();
+
+        consensus.record_vote(1, 0, us, BLOCK);
+        // the peer gave up on round 0 before we did
+        assert_eq!(consensus.record_vote(1, 1, peer, BLOCK), Recorded::Counted);
+        assert!(!consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+
+        assert_eq!(consensus.next_round(), 1);
+        assert_eq!(consensus.votes(), 1);
+        assert_eq!(
+            consensus.record_vote(1, 0, peer, BLOCK),
+            Recorded::Ignored,
+            "votes of a round given up on are stale"
+        );
+        consensus.record_vote(1, 1, us, BLOCK);
+        assert!(consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+
+        consensus.commit();
+        assert_eq!((consensus.height(), consensus.round()), (2, 0));
+    }
+
+    #[test]
+    fn only_validators_votes_count() {
+        let (us, peer, outsider) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let validators = BTreeSet::from([us, peer]);
+        let mut consensus = Consensus::default();
+
+        consensus.record_vote(1, 0, us, BLOCK);
+        consensus.record_vote(1, 0, outsider, BLOCK);
+        assert!(consensus.has_quorum(Quorum::ALL, 2, &BLOCK));
+        assert!(!consensus.has_quorum_of(Quorum::ALL, &validators, &BLOCK));
+
+        consensus.record_vote(1, 0, peer, BLOCK);
+        assert!(consensus.has_quorum_of(Quorum::ALL, &validators, &BLOCK));
+    }
+
+    #[test]
+    fn certificates_need_a_quorum_in_one_round() {
+        let validators: BTreeSet<_> = (0..3).map(|_| PeerId::random()).collect();
+        let vote = |voter: &PeerId, round, block_hash| Vote {
+            height: 1,
+            round,
+            block_hash,
+            voter: *voter,
+        };
+        let [a, b, _] = validators.iter().collect::<Vec<_>>()[..] else {
+            unreachable!()
+        };
+        let certifies =
+            |votes: Vec<Vote>| certifies(Quorum::TWO_THIRDS, &validators, 1, &BLOCK, votes);
+
+        assert!(certifies(vec![vote(a, 0, BLOCK), vote(b, 0, BLOCK)]));
+        assert!(
+            !certifies(vec![vote(a, 0, BLOCK), vote(a, 0, BLOCK)]),
+            "one voter twice"
+        );
+        assert!(
+            !certifies(vec![vote(a, 0, BLOCK), vote(b, 1, BLOCK)]),
+            "rounds apart"
+        );
+        assert!(
+            !certifies(vec![vote(a, 0, BLOCK), vote(b, 0, [2; 32])]),
+            "another block"
+        );
+        let outsider = PeerId::random();
+        assert!(!certifies(vec![
+            vote(a, 0, BLOCK),
+            vote(&outsider, 0, BLOCK)
+        ]));
+        assert!(!certifies(vec![
+            Vote {
+                height: 2,
+                ..vote(a, 0, BLOCK)
+            },
+            vote(b, 0, BLOCK)
+        ]));
+    }
+
+    #[test]
+    fn two_thirds_round_up() {
+        let quorum: Quorum = "2/3".parse().unwrap();
+        assert_eq!(quorum, Quorum::default());
+        let needed: Vec<usize> = (2..=7).map(|validators| quorum.of(validators)).collect();
+        assert_eq!(needed, [2, 2, 3, 4, 4, 5]);
+
+        let mut consensus = Consensus::default();
+        for _ in 0..3 {
+            consensus.record_vote(1, 0, PeerId::random(), BLOCK);
+        }
+        assert!(consensus.has_quorum(quorum, 4, &BLOCK));
+        assert!(!consensus.has_quorum(Quorum::ALL, 4, &BLOCK));
+
+        assert!("0/3".parse::<Quorum>().is_err());
+        assert!("4/3".parse::<Quorum>().is_err());
+        assert!("1/2".parse::<Quorum>().is_err(), "not more than half");
+        assert!("3/5".parse::<Quorum>().is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn commits_every_height_once_in_order(steps in prop::collection::vec(step(), 0..200)) {
+            let voters: Vec<PeerId> = (0..VOTERS).map(|_| PeerId::random()).collect();
+            let committed = run(&steps, &voters);
+
+            let expected: Vec<u32> = (1..=committed.len() as u32).collect();
+            prop_assert_eq!(committed, expected);
+        }
+
+        #[test]
+        fn quorum_is_monotonic_in_votes(
+            votes in prop::collection::vec(0..VOTERS, 0..20),
+            extra in 0..VOTERS,
+            peers in 0..=VOTERS,
+        ) {
+            let voters: Vec<PeerId> = (0..VOTERS).map(|_| PeerId::random()).collect();
+            let mut consensus = Consensus::default();
+            for voter in votes {
+                consensus.record_vote(1, 0, voters[voter], BLOCK);
+            }
+
+            let had_quorum = consensus.has_quorum(Quorum::default(), peers, &BLOCK);
+            consensus.record_vote(1, 0, voters[extra], BLOCK);
+            prop_assert!(!had_quorum || consensus.has_quorum(Quorum::default(), peers, &BLOCK));
+        }
+
+        #[test]
+        fn vote_order_does_not_change_the_outcome(
+            (votes, shuffled) in prop::collection::vec((0..VOTERS, 1..4u32), 0..40)
+                .prop_flat_map(|votes| (Just(votes.clone()), Just(votes).prop_shuffle())),
+        ) {
+            let voters: Vec<PeerId> = (0..VOTERS).map(|_| PeerId::random()).collect();
+
+            // two replicas seeing the same votes in a different order, committing as soon as they can
+            let replay = |votes: &[(usize, u32)]| {
+                let mut consensus = Consensus::default();
+                for &(voter, height) in votes {
+                    consensus.record_vote(height, 0, voters[voter], BLOCK);
+                    while consensus.has_quorum(Quorum::default(), VOTERS, &BLOCK) {
+                        consensus.commit();
+                    }
+                }
+                consensus.height()
+            };
+            prop_assert_eq!(replay(&votes), replay(&shuffled));
+        }
+
+        #[test]
+        fn replicas_never_commit_different_blocks_at_a_height(
+            quorum in prop_oneof![Just(Quorum::default()), quorum()],
+            // the block each validator votes for at each height
+            choices in prop::collection::vec(prop::collection::vec(0..BLOCKS.len(), HEIGHTS as usize), VOTERS),
+            // the votes each replica gets, in the order it gets them
+            delivered in prop::collection::vec(
+                prop::collection::vec((0..VOTERS, 1..=HEIGHTS), 0..40),
+                2,
+            ),
+        ) {
+            let voters: Vec<PeerId> = (0..VOTERS).map(|_| PeerId::random()).collect();
+            let validators: BTreeSet<PeerId> = voters.iter().copied().collect();
+
+            let replay = |votes: &[(usize, u32)]| -> Result<Vec<Hash>, TestCaseError> {
+                let mut consensus = Consensus::default();
+                let mut committed = Vec::new();
+                for &(voter, height) in votes {
+                    let block = BLOCKS[choices[voter][height as usize - 1]];
+                    consensus.record_vote(height, 0, voters[voter], block);
+                    loop {
+                        let agreed: Vec<_> = BLOCKS
+                            .iter()
+                            .filter(|block| consensus.has_quorum_of(quorum, &validators, block))
+                            .collect();
+                        prop_assert!(agreed.len() <= 1, "{} blocks agreed on at once", agreed.len());
+                        let Some(block) = agreed.first() else { break };
+                        committed.push(**block);
+                        consensus.commit();
+                    }
+                }
+                Ok(committed)
+            };
+            let (first, second) = (replay(&delivered[0])?, replay(&delivered[1])?);
+            for (height, (a, b)) in first.iter().zip(&second).enumerate() {
+                prop_assert_eq!(a, b, "replicas committed different blocks at height {}", height + 1);
+            }
+        }
+    }
+}
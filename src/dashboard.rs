@@ -0,0 +1,192 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use libp2p::PeerId;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::VecDeque;
+use std::io::{self, Stdout, Write};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::error::lock;
+
+/// How many committed blocks the dashboard keeps around.
+pub const RECENT_BLOCKS: usize = 10;
+
+// Log lines kept for the log pane, older ones are dropped.
+const LOG_LINES: usize = 200;
+
+pub struct BlockSummary {
+    pub height: u32,
+    pub transactions: usize,
+}
+
+/// Everything the dashboard shows, captured from the node on every redraw.
+pub struct DashboardState<'a> {
+    pub local_peer_id: PeerId,
+    pub height: u32,
+    pub recent_blocks: &'a VecDeque<BlockSummary>,
+    pub mempool: Vec<String>,
+    pub peers: Vec<PeerId>,
+    pub votes: usize,
+}
+
+/// Live terminal view of the node, a friendlier alternative to scrolling logs.
+///
+/// Takes over the terminal until it is dropped, so stdin commands are not
+/// available while it runs; press `q` or `Esc` to quit.
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    logs: LogBuffer,
+}
+
+impl Dashboard {
+    pub fn start(logs: LogBuffer) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        io::stdout().execute(EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+        Ok(Dashboard { terminal, logs })
+    }
+
+    pub fn draw(&mut self, state: &DashboardState) -> io::Result<()> {
+        let logs = self.logs.lines();
+        self.terminal.draw(|frame| render(frame, state, &logs))?;
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // best effort, there is nothing sensible to do if restoring the terminal fails
+        let _ = terminal::disable_raw_mode();
+        let _ = io::stdout().execute(LeaveAlternateScreen);
+    }
+}
+
+/// Whether a terminal event asks the dashboard to quit.
+pub fn is_quit(event: &Event) -> bool {
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers,
+            ..
+        }) => modifiers.contains(KeyModifiers::CONTROL),
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('q') | KeyCode::Esc,
+            ..
+        }) => true,
+        _ => false,
+    }
+}
+
+fn render(frame: &mut Frame, state: &DashboardState, logs: &[String]) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(10),
+        ])
+        .split(frame.area());
+
+    let header = Paragraph::new(Line::from(format!(
+        "peer {}   height {}   mempool {}   peers {}",
+        state.local_peer_id,
+        state.height,
+        state.mempool.len(),
+        state.peers.len()
+    )))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" EduCoin node "),
+    );
+    frame.render_widget(header, rows[0]);
+
+    let quorum = state.peers.len().max(1);
+    let votes = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(" Votes "))
+        .gauge_style(Style::default().fg(Color::Green))
+        .label(format!("{}/{}", state.votes, state.peers.len()))
+        .ratio((state.votes as f64 / quorum as f64).min(1.0));
+    frame.render_widget(votes, rows[1]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(25),
+            Constraint::Percentage(45),
+            Constraint::Percentage(30),
+        ])
+        .split(rows[2]);
+
+    let blocks = state
+        .recent_blocks
+        .iter()
+        .rev()
+        .map(|block| ListItem::new(format!("#{} - {} txs", block.height, block.transactions)));
+    frame.render_widget(list(" Recent blocks ", blocks), columns[0]);
+
+    let mempool = state.mempool.iter().map(|tx| ListItem::new(tx.as_str()));
+    frame.render_widget(list(" Mempool ", mempool), columns[1]);
+
+    let peers = state
+        .peers
+        .iter()
+        .map(|peer| ListItem::new(peer.to_string()));
+    frame.render_widget(list(" Peers ", peers), columns[2]);
+
+    let visible = rows[3].height.saturating_sub(2) as usize;
+    let log_lines = logs
+        .iter()
+        .skip(logs.len().saturating_sub(visible))
+        .map(|line| ListItem::new(line.as_str()));
+    frame.render_widget(list(" Log ", log_lines), rows[3]);
+}
+
+fn list<'a>(title: &'a str, items: impl Iterator<Item = ListItem<'a>>) -> List<'a> {
+    List::new(items.collect::<Vec<_>>()).block(Block::default().borders(Borders::ALL).title(title))
+}
+
+/// Tracing writer that keeps the latest log lines in memory for the log pane,
+/// since anything written to the terminal would tear up the dashboard.
+#[derive(Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<VecDeque<String>>>);
+
+impl LogBuffer {
+    fn lines(&self) -> Vec<String> {
+        lock(&self.0).iter().cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = lock(&self.0);
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::io;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tracing::warn;
+
+use crate::address::AddressError;
+use crate::signer::SignerError;
+use crate::transaction::DecodeError;
+use crate::wallet::WalletError;
+
+/// Errors the node can recover from by logging them and carrying on with the
+/// next event, rather than taking the whole node down.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Wallet(WalletError),
+    Address(AddressError),
+    Signer(SignerError),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {e}"),
+            Error::Wallet(e) => write!(f, "wallet error: {e}"),
+            Error::Address(e) => write!(f, "address book error: {e}"),
+            Error::Signer(e) => write!(f, "signing error: {e}"),
+            Error::Decode(e) => write!(f, "decoding error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<WalletError> for Error {
+    fn from(e: WalletError) -> Self {
+        Error::Wallet(e)
+    }
+}
+
+impl From<AddressError> for Error {
+    fn from(e: AddressError) -> Self {
+        Error::Address(e)
+    }
+}
+
+impl From<SignerError> for Error {
+    fn from(e: SignerError) -> Self {
+        Error::Signer(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+/// Lock `mutex`, recovering the data if a previous holder panicked.
+///
+/// Everything we keep behind a mutex stays consistent between statements, so
+/// a poisoned lock is worth a warning but not a crash.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned: PoisonError<_>| {
+        warn!("recovering from a poisoned lock");
+        poisoned.into_inner()
+    })
+}
@@ -0,0 +1,243 @@
+//! A static block explorer: `explorer export` renders the blocks of an
+//! `/export --format json` file into HTML pages that open straight from
+//! disk, with an index of blocks and a page per block, transaction and
+//! address, linked to each other.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::export::{Block, Transaction};
+
+/// Render `blocks` into a site in `out`, returning how many pages were written.
+pub fn render(blocks: &[Block], out: &Path) -> io::Result<usize> {
+    for dir in ["block", "tx", "address"] {
+        fs::create_dir_all(out.join(dir))?;
+    }
+
+    let mut pages = BTreeMap::new();
+    let mut rows = String::new();
+    // newest first, like every explorer
+    for block in blocks.iter().rev() {
+        rows += &format!(
+            "<tr><td><a href=\"block/{0}.html\">{0}</a></td><td>{1}</td><td>{2}</td></tr>\n",
+            block.height,
+            escape(&block.merkle_root),
+            block.transactions.len()
+        );
+    }
+    pages.insert(
+        "index.html".to_string(),
+        page(
+            "",
+            "Blocks",
+            &table(&["Height", "Merkle root", "Transactions"], &rows),
+        ),
+    );
+
+    let mut by_address: BTreeMap<&str, Vec<(&Block, &Transaction)>> = BTreeMap::new();
+    for block in blocks {
+        let mut rows = String::new();
+        for transaction in &block.transactions {
+            rows += &transaction_row("../", block, transaction);
+            by_address
+                .entry(&transaction.sender)
+                .or_default()
+                .push((block, transaction));
+            if let Some(transfer) = &transaction.transfer {
+                if transfer.recipient != transaction.sender {
+                    by_address
+                        .entry(&transfer.recipient)
+                        .or_default()
+                        .push((block, transaction));
+                }
+            }
+            pages.insert(
+                format!("tx/{}.html", file_name(&transaction.id)),
+                transaction_page(block, transaction),
+            );
+        }
+        let body = format!(
+            "<p>Merkle root <code>{}</code></p>{}",
+            escape(&block.merkle_root),
+            table(&["Transaction", "From", "To", "Amount"], &rows)
+        );
+        pages.insert(
+            format!("block/{}.html", block.height),
+            page("../", &format!("Block {}", block.height), &body),
+        );
+    }
+
+    for (address, transactions) in by_address {
+        let rows: String = transactions
+            .iter()
+            .map(|(block, transaction)| transaction_row("../", block, transaction))
+            .collect();
+        pages.insert(
+            format!("address/{}.html", file_name(address)),
+            page(
+                "../",
+                &format!("Address {}", escape(address)),
+                &table(&["Transaction", "From", "To", "Amount"], &rows),
+            ),
+        );
+    }
+
+    for (path, contents) in &pages {
+        fs::write(out.join(path), contents)?;
+    }
+    Ok(pages.len())
+}
+
+fn transaction_page(block: &Block, transaction: &Transaction) -> String {
+    let mut body = format!(
+        "<dl>\n<dt>Block</dt><dd><a href=\"../block/{0}.html\">{0}</a></dd>\n<dt>From</dt><dd>{1}</dd>\n",
+        block.height,
+        address_link("../", &transaction.sender)
+    );
+    if let Some(transfer) = &transaction.transfer {
+        body += &format!(
+            "<dt>To</dt><dd>{}</dd>\n<dt>Amount</dt><dd>{}</dd>\n",
+            address_link("../", &transfer.recipient),
+            transfer.amount
+        );
+    }
+    if transaction.fee > 0 {
+        body += &format!("<dt>Fee</dt><dd>{}</dd>\n", transaction.fee);
+    }
+    body += &format!(
+        "<dt>Data</dt><dd><code>{}</code></dd>\n<dt>Signature</dt><dd><code>{}</code></dd>\n</dl>",
+        escape(&transaction.data),
+        escape(&transaction.signature)
+    );
+
+    page(
+        "../",
+        &format!("Transaction {}", escape(&transaction.id)),
+        &body,
+    )
+}
+
+fn transaction_row(root: &str, block: &Block, transaction: &Transaction) -> String {
+    let (to, amount) = match &transaction.transfer {
+        Some(transfer) => (
+            address_link(root, &transfer.recipient),
+            transfer.amount.to_string(),
+        ),
+        None => (String::new(), String::new()),
+    };
+    format!(
+        "<tr><td><a href=\"{root}tx/{file}.html\">{id}</a> in <a href=\"{root}block/{height}.html\">{height}</a></td><td>{from}</td><td>{to}</td><td>{amount}</td></tr>\n",
+        file = file_name(&transaction.id),
+        id = escape(&transaction.id),
+        height = block.height,
+        from = address_link(root, &transaction.sender),
+    )
+}
+
+fn address_link(root: &str, address: &str) -> String {
+    format!(
+        "<a href=\"{root}address/{}.html\">{}</a>",
+        file_name(address),
+        escape(address)
+    )
+}
+
+fn table(headers: &[&str], rows: &str) -> String {
+    let headers: String = headers
+        .iter()
+        .map(|header| format!("<th>{header}</th>"))
+        .collect();
+    format!("<table><thead><tr>{headers}</tr></thead><tbody>\n{rows}</tbody></table>")
+}
+
+fn page(root: &str, title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title} - EduCoin explorer</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+td, th {{ border-bottom: 1px solid #ddd; padding: 0.3em 0.8em; text-align: left; }}
+code, td {{ word-break: break-all; }}
+</style>
+</head>
+<body>
+<p><a href=\"{root}index.html\">All blocks</a></p>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"
+    )
+}
+
+// Ids and addresses are CIDs and bech32, but the export file may have been edited by hand.
+fn file_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '&' => escaped += "&amp;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&#39;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::TransferTo;
+
+    #[test]
+    fn renders_linked_pages() {
+        let transaction = |id: &str, data: &str, transfer| Transaction {
+            id: id.to_string(),
+            sender: "edu1alice".to_string(),
+            signature: "00".to_string(),
+            data: data.to_string(),
+            fee: 0,
+            nonce: 1,
+            transfer,
+        };
+        let blocks = [Block {
+            height: 1,
+            merkle_root: "bafkroot".to_string(),
+            transactions: vec![
+                transaction(
+                    "bafkone",
+                    "send bob 5",
+                    Some(TransferTo {
+                        recipient: "edu1bob".to_string(),
+                        amount: 5,
+                    }),
+                ),
+                transaction("bafktwo", "<script>alert(1)</script>", None),
+            ],
+        }];
+
+        let dir = tempfile::tempdir().unwrap();
+        // index, block, two transactions, two addresses
+        assert_eq!(render(&blocks, dir.path()).unwrap(), 6);
+
+        let read = |path: &str| fs::read_to_string(dir.path().join(path)).unwrap();
+        assert!(read("index.html").contains("<a href=\"block/1.html\">1</a>"));
+        assert!(read("block/1.html").contains("href=\"../tx/bafkone.html\""));
+        let bob = read("address/edu1bob.html");
+        assert!(bob.contains("bafkone") && !bob.contains("bafktwo"));
+        assert!(read("address/edu1alice.html").contains("bafktwo"));
+        assert!(read("tx/bafktwo.html").contains("&lt;script&gt;"));
+    }
+}
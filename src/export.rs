@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use crate::address::Address;
+use crate::cid;
+use crate::history::TransactionIndex;
+use crate::ledger::Transfer;
+use crate::merkle::MerkleTree;
+use crate::transaction::{self, TransactionId};
+
+/// Formats committed blocks can be exported in with `/export`.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    /// An array of blocks with their transactions, hashes as CIDs and
+    /// addresses as bech32, for block explorers and visualisations.
+    Json,
+    /// One line per transaction: height, commit time in seconds since the
+    /// Unix epoch, sender, recipient, amount and fee. Recipient and amount
+    /// are empty for transactions that don't move coins.
+    Csv,
+    /// One block per line, [`crate::rlp`] encoded and in hex.
+    #[cfg(feature = "rlp")]
+    Rlp,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            #[cfg(feature = "rlp")]
+            "rlp" => Ok(Format::Rlp),
+            other => Err(format!(
+                "unknown export format `{other}`, use `json` or `csv`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "json"),
+            Format::Csv => write!(f, "csv"),
+            #[cfg(feature = "rlp")]
+            Format::Rlp => write!(f, "rlp"),
+        }
+    }
+}
+
+/// A block as `/export --format json` writes it, also sent to block webhooks
+/// and read back by `explorer export`.
+#[derive(Serialize, Deserialize)]
+pub struct Block {
+    pub height: u32,
+    pub merkle_root: String,
+    pub transactions: Vec<Transaction>,
+}
+
+impl Block {
+    pub fn new<'a>(
+        height: u32,
+        transactions: impl IntoIterator<Item = &'a transaction::Transaction>,
+    ) -> Self {
+        let mut tree = MerkleTree::default();
+        let transactions = transactions
+            .into_iter()
+            .map(|transaction| {
+                let id = transaction.id();
+                tree.push(id.as_bytes());
+                Transaction::with_id(id, transaction)
+            })
+            .collect();
+
+        Block {
+            height,
+            merkle_root: cid::encode(&tree.root()),
+            transactions,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub sender: String,
+    pub signature: String,
+    pub data: String,
+    #[serde(default)]
+    pub fee: u64,
+    #[serde(default)]
+    pub nonce: u64,
+    /// Set for transactions that move coins.
+    pub transfer: Option<TransferTo>,
+}
+
+impl Transaction {
+    pub fn new(transaction: &transaction::Transaction) -> Self {
+        Transaction::with_id(transaction.id(), transaction)
+    }
+
+    fn with_id(id: TransactionId, transaction: &transaction::Transaction) -> Self {
+        Transaction {
+            id: id.to_string(),
+            sender: Address::from(&transaction.public_key).to_bech32(),
+            signature: hex::encode(&transaction.signature),
+            data: String::from_utf8_lossy(&transaction.data).into_owned(),
+            fee: transaction.fee,
+            nonce: transaction.nonce,
+            transfer: Transfer::parse(&transaction.data).map(|transfer| TransferTo {
+                recipient: transfer.recipient.to_bech32(),
+                amount: transfer.amount,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransferTo {
+    pub recipient: String,
+    pub amount: u64,
+}
+
+/// Write every block in `index` to `path` as `format`, returning how many
+/// blocks were written. Only blocks with at least one transaction are known
+/// to the index.
+pub fn export(path: &Path, format: Format, index: &TransactionIndex) -> io::Result<usize> {
+    let contents = match format {
+        Format::Json => {
+            let blocks: Vec<Block> = index
+                .blocks()
+                .map(|block| {
+                    let transactions = block.iter().map(|committed| &committed.transaction);
+                    Block::new(block[0].height, transactions)
+                })
+                .collect();
+            serde_json::to_string_pretty(&blocks)? + "\n"
+        }
+        Format::Csv => csv(index),
+        #[cfg(feature = "rlp")]
+        Format::Rlp => index
+            .blocks()
+            .map(|block| {
+                let transactions = block.iter().map(|committed| &committed.transaction);
+                hex::encode(crate::rlp::encode_block(block[0].height, transactions)) + "\n"
+            })
+            .collect(),
+    };
+
+    fs::write(path, contents)?;
+    Ok(index.blocks().count())
+}
+
+fn csv(index: &TransactionIndex) -> String {
+    let mut csv = "height,time,from,to,amount,fee\n".to_string();
+    for committed in index.blocks().flatten() {
+        let time = committed
+            .committed_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let from = Address::from(&committed.transaction.public_key);
+        let (to, amount) = match Transfer::parse(&committed.transaction.data) {
+            Some(transfer) => (transfer.recipient.to_string(), transfer.amount.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            csv,
+            "{},{time},{from},{to},{amount},{}",
+            committed.height, committed.transaction.fee
+        )
+        .expect("writing to a String never fails");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+    use std::time::Duration;
+
+    #[test]
+    fn exports_blocks_as_json_and_csv() {
+        let (sender, recipient) = (Keypair::generate(), Keypair::generate());
+        let recipient = Address::from(&recipient.public());
+        let data = format!("send {recipient} 25");
+        let sent = block_on(transaction::Transaction::sign(
+            &sender,
+            "test",
+            data.as_bytes(),
+            1,
+        ))
+        .unwrap();
+        let id = sent.id();
+        let mut index = TransactionIndex::default();
+        index.insert_block(4, UNIX_EPOCH + Duration::from_secs(90), vec![sent]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.json");
+        assert_eq!(export(&path, Format::Json, &index).unwrap(), 1);
+
+        let blocks: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        let block = &blocks[0];
+        assert_eq!(block["height"], 4);
+        assert_eq!(
+            block["merkle_root"],
+            cid::encode(&MerkleTree::from_leaves([id.as_bytes()]).root())
+        );
+        let exported = &block["transactions"][0];
+        assert_eq!(exported["id"], id.to_string());
+        assert_eq!(
+            exported["sender"],
+            Address::from(&sender.public()).to_bech32()
+        );
+        assert_eq!(exported["transfer"]["recipient"], recipient.to_bech32());
+        assert_eq!(exported["transfer"]["amount"], 25);
+
+        let path = dir.path().join("transactions.csv");
+        export(&path, Format::Csv, &index).unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            format!(
+                "height,time,from,to,amount,fee\n4,90,{},{recipient},25,0\n",
+                Address::from(&sender.public())
+            )
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use prost::Message;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::address::Address;
+use crate::ledger::Ledger;
+use crate::proto;
+use crate::transaction::Transaction;
+
+/// Label of the wallet account holding the faucet key.
+pub const FAUCET_ACCOUNT: &str = "faucet";
+
+/// Hands out test coins from an account funded at genesis.
+///
+/// Every address is served once per run, so one participant can't drain the
+/// faucet for everybody else.
+pub struct Faucet {
+    address: Address,
+    amount: u64,
+    served: HashSet<Address>,
+}
+
+impl Faucet {
+    pub fn new(address: Address, amount: u64) -> Self {
+        Faucet {
+            address,
+            amount,
+            served: HashSet::new(),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Input line sending the faucet amount to `recipient` from the faucet
+    /// account, to be signed and published like any other transaction.
+    pub fn dispense<'a>(
+        &mut self,
+        recipient: Address,
+        ledger: &Ledger,
+        mempool: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Result<String, FaucetError> {
+        if self.served.contains(&recipient) {
+            return Err(FaucetError::AlreadyServed(recipient));
+        }
+        let available = ledger.pending_balance(&self.address, mempool);
+        if available < self.amount {
+            return Err(FaucetError::Empty(available));
+        }
+
+        self.served.insert(recipient);
+        Ok(format!(
+            "--from {FAUCET_ACCOUNT} send {recipient} {}",
+            self.amount
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum FaucetError {
+    AlreadyServed(Address),
+    Empty(u64),
+}
+
+impl fmt::Display for FaucetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaucetError::AlreadyServed(address) => {
+                write!(f, "{address} already received coins from the faucet")
+            }
+            FaucetError::Empty(available) => {
+                write!(f, "the faucet is running dry, only {available} coins left")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FaucetError {}
+
+/// Ask the faucet on the network to send coins to `recipient`.
+pub fn encode_request(recipient: &Address) -> Vec<u8> {
+    proto::FaucetRequest {
+        recipient: recipient.to_string(),
+    }
+    .encode_to_vec()
+}
+
+pub fn decode_request(data: &[u8]) -> Option<Address> {
+    proto::FaucetRequest::decode(data)
+        .ok()?
+        .recipient
+        .parse()
+        .ok()
+}
@@ -0,0 +1,96 @@
+use libp2p::PeerId;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::lock;
+
+/// How gossip from one peer reaches us.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Link {
+    /// Chance in `0.0..=1.0` that a message is lost.
+    pub drop_rate: f64,
+    pub latency: Duration,
+    /// Extra random delay on top of `latency`, up to this much. Messages
+    /// overtake each other once it is larger than the gap between them.
+    pub jitter: Duration,
+}
+
+/// What to do with an incoming gossip message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Deliver,
+    Drop,
+    Delay(Duration),
+}
+
+struct FaultState {
+    default: Link,
+    links: HashMap<PeerId, Link>,
+    partitioned: HashSet<PeerId>,
+    rng: StdRng,
+}
+
+/// Faults injected into the gossip a node receives, so partitions and flaky
+/// links can be demonstrated and tested in-process.
+///
+/// The handle is shared: changing it while the node runs takes effect from
+/// the next message on.
+#[derive(Clone)]
+pub struct Faults(Arc<Mutex<FaultState>>);
+
+// only the test harness injects faults so far
+#[allow(dead_code)]
+impl Faults {
+    /// No faults until configured, with randomness drawn from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Faults(Arc::new(Mutex::new(FaultState {
+            default: Link::default(),
+            links: HashMap::new(),
+            partitioned: HashSet::new(),
+            rng: StdRng::seed_from_u64(seed),
+        })))
+    }
+
+    /// Link used for every peer without one of its own.
+    pub fn set_default(&self, link: Link) {
+        lock(&self.0).default = link;
+    }
+
+    pub fn set_link(&self, from: PeerId, link: Link) {
+        lock(&self.0).links.insert(from, link);
+    }
+
+    /// Drop everything `from` sends us, until [`Faults::heal`].
+    pub fn partition(&self, from: PeerId) {
+        lock(&self.0).partitioned.insert(from);
+    }
+
+    pub fn heal(&self) {
+        lock(&self.0).partitioned.clear();
+    }
+
+    pub fn inbound(&self, from: &PeerId) -> Verdict {
+        let mut state = lock(&self.0);
+        if state.partitioned.contains(from) {
+            return Verdict::Drop;
+        }
+
+        let link = state.links.get(from).copied().unwrap_or(state.default);
+        if link.drop_rate > 0.0 && state.rng.gen_bool(link.drop_rate.min(1.0)) {
+            return Verdict::Drop;
+        }
+
+        let jitter = if link.jitter.is_zero() {
+            Duration::ZERO
+        } else {
+            state.rng.gen_range(Duration::ZERO..=link.jitter)
+        };
+        match link.latency + jitter {
+            delay if delay.is_zero() => Verdict::Deliver,
+            delay => Verdict::Delay(delay),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::address::{Address, InvalidAddress};
+use crate::bridge::BridgeGenesis;
+
+/// The native asset and how its coins come into existence: allocated at
+/// genesis, and minted by every block after that according to
+/// [`Emission`]. Every node on a network has to start from the same one.
+///
+/// Read from the `--genesis` file, e.g.
+///
+/// ```text
+/// {
+///   "token": { "name": "EduCoin", "symbol": "EDU" },
+///   "allocations": { "<address>": 1000 },
+///   "max_supply": 21000,
+///   "emission": { "block_reward": 50, "halving_interval": 100, "recipient": "<address>" },
+///   "unbonding_period": 10,
+///   "bridge": { "source_chain": "<chain id>", "relayers": ["<address>"] }
+/// }
+/// ```
+///
+/// `--genesis-balance` allocations are added on top.
+#[derive(Debug, Clone, Default)]
+pub struct Genesis {
+    pub token: Token,
+    pub allocations: Vec<(Address, u64)>,
+    /// Emission stops once the supply reaches this, no limit if unset.
+    pub max_supply: Option<u64>,
+    pub emission: Option<Emission>,
+    /// Blocks unbonded stake stays locked for, see [`crate::staking`].
+    pub unbonding_period: Option<u32>,
+    /// Where coins can be bridged in from, see [`crate::bridge`].
+    pub bridge: Option<BridgeGenesis>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Token {
+    pub name: String,
+    pub symbol: String,
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Token {
+            name: "EduCoin".to_string(),
+            symbol: "EDU".to_string(),
+        }
+    }
+}
+
+/// Coins minted by each block, credited to `recipient`, halving every
+/// `halving_interval` blocks (never, if unset).
+#[derive(Debug, Clone)]
+pub struct Emission {
+    pub block_reward: u64,
+    pub halving_interval: Option<u32>,
+    pub recipient: Address,
+}
+
+impl Emission {
+    /// Reward of the block at `height`, counted from 1, before the supply cap.
+    pub fn reward(&self, height: u32) -> u64 {
+        let halvings = match self.halving_interval {
+            Some(interval) if interval > 0 => height.saturating_sub(1) / interval,
+            _ => 0,
+        };
+        self.block_reward.checked_shr(halvings).unwrap_or(0)
+    }
+}
+
+// The file format, with addresses still as strings.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisFile {
+    #[serde(default)]
+    token: Token,
+    #[serde(default)]
+    allocations: BTreeMap<String, u64>,
+    max_supply: Option<u64>,
+    emission: Option<EmissionFile>,
+    unbonding_period: Option<u32>,
+    bridge: Option<BridgeFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmissionFile {
+    block_reward: u64,
+    halving_interval: Option<u32>,
+    recipient: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BridgeFile {
+    source_chain: String,
+    relayers: Vec<String>,
+}
+
+impl Genesis {
+    #[cfg(test)]
+    pub fn with_allocations(allocations: &[(Address, u64)]) -> Self {
+        Genesis {
+            allocations: allocations.to_vec(),
+            ..Genesis::default()
+        }
+    }
+
+    /// The genesis in `file` if there is one, plus `allocations`.
+    pub fn load(file: Option<&Path>, allocations: &[(Address, u64)]) -> Result<Self, GenesisError> {
+        let mut genesis = match file {
+            Some(file) => Genesis::parse(&fs::read_to_string(file)?)?,
+            None => Genesis::default(),
+        };
+        genesis.allocations.extend_from_slice(allocations);
+
+        // the sum only overflows far past any max supply
+        let allocated = genesis
+            .allocations
+            .iter()
+            .map(|(_, amount)| *amount)
+            .try_fold(0u64, u64::checked_add);
+        let max_supply = genesis.max_supply.unwrap_or(u64::MAX);
+        match allocated {
+            Some(allocated) if allocated <= max_supply => Ok(genesis),
+            allocated => Err(GenesisError::OverAllocated {
+                allocated: allocated.unwrap_or(u64::MAX),
+                max_supply,
+            }),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Self, GenesisError> {
+        let file: GenesisFile = serde_json::from_str(contents)?;
+        let allocations = file
+            .allocations
+            .into_iter()
+            .map(|(address, amount)| Ok((address.parse()?, amount)))
+            .collect::<Result<_, InvalidAddress>>()?;
+        let emission = file
+            .emission
+            .map(|emission| -> Result<_, InvalidAddress> {
+                Ok(Emission {
+                    block_reward: emission.block_reward,
+                    halving_interval: emission.halving_interval,
+                    recipient: emission.recipient.parse()?,
+                })
+            })
+            .transpose()?;
+        let bridge = file
+            .bridge
+            .map(|bridge| -> Result<_, InvalidAddress> {
+                Ok(BridgeGenesis {
+                    source_chain: bridge.source_chain,
+                    relayers: bridge
+                        .relayers
+                        .iter()
+                        .map(|relayer| relayer.parse())
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .transpose()?;
+
+        Ok(Genesis {
+            token: file.token,
+            allocations,
+            max_supply: file.max_supply,
+            emission,
+            unbonding_period: file.unbonding_period,
+            bridge,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum GenesisError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    InvalidAddress(InvalidAddress),
+    OverAllocated { allocated: u64, max_supply: u64 },
+}
+
+impl From<io::Error> for GenesisError {
+    fn from(e: io::Error) -> Self {
+        GenesisError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for GenesisError {
+    fn from(e: serde_json::Error) -> Self {
+        GenesisError::Json(e)
+    }
+}
+
+impl From<InvalidAddress> for GenesisError {
+    fn from(e: InvalidAddress) -> Self {
+        GenesisError::InvalidAddress(e)
+    }
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenesisError::Io(e) => write!(f, "failed to read genesis: {e}"),
+            GenesisError::Json(e) => write!(f, "malformed genesis: {e}"),
+            GenesisError::InvalidAddress(e) => write!(f, "malformed genesis: {e}"),
+            GenesisError::OverAllocated {
+                allocated,
+                max_supply,
+            } => write!(
+                f,
+                "genesis allocates {allocated} coins, more than the max supply of {max_supply}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenesisError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_genesis_file() {
+        let (alice, bob) = (hex::encode([1; 32]), hex::encode([2; 32]));
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("genesis.json");
+        fs::write(
+            &file,
+            format!(
+                r#"{{
+                    "token": {{ "name": "Classcoin", "symbol": "CLS" }},
+                    "allocations": {{ "{alice}": 700 }},
+                    "max_supply": 1000,
+                    "emission": {{ "block_reward": 40, "halving_interval": 10, "recipient": "{bob}" }}
+                }}"#
+            ),
+        )
+        .unwrap();
+
+        let extra = [(bob.parse().unwrap(), 300)];
+        let genesis = Genesis::load(Some(&file), &extra).unwrap();
+        assert_eq!(genesis.token.symbol, "CLS");
+        assert_eq!(genesis.allocations.len(), 2);
+        let emission = genesis.emission.unwrap();
+        assert_eq!(emission.recipient, bob.parse().unwrap());
+        assert_eq!(
+            [1, 10, 11, 21, 400].map(|height| emission.reward(height)),
+            [40, 40, 20, 10, 0]
+        );
+
+        let over = [(bob.parse().unwrap(), 301)];
+        assert!(matches!(
+            Genesis::load(Some(&file), &over),
+            Err(GenesisError::OverAllocated {
+                allocated: 1001,
+                max_supply: 1000
+            })
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! Golden vectors: canonical encodings of transactions, votes and blocks,
+//! checked in under `tests/vectors`.
+//!
+//! A failing test here means the wire or storage format changed, and nodes
+//! from the previous workshop release won't understand this one. If the
+//! change is intended, regenerate the vectors with
+//! `UPDATE_GOLDEN=1 cargo test --all-features golden`, so the vectors behind
+//! features are rewritten too, and commit them with the change.
+
+use libp2p::identity::{self, ed25519};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::block::Block;
+use crate::storage;
+use crate::transaction::{self, Transaction};
+use crate::vote;
+
+// Chain the vectors are signed for.
+const CHAIN_ID: &str = "educoin-golden";
+
+// Fixed keys, so signatures (ed25519 is deterministic) and peer ids never change.
+fn keypair(byte: u8) -> ed25519::Keypair {
+    ed25519::SecretKey::try_from_bytes([byte; 32])
+        .expect("32 bytes are always a valid ed25519 secret key")
+        .into()
+}
+
+fn transaction(byte: u8, data: &str) -> Transaction {
+    let transaction = async_std::task::block_on(Transaction::sign(
+        &keypair(byte),
+        CHAIN_ID,
+        data.as_bytes(),
+        1,
+    ));
+    transaction.expect("local keys never fail to sign")
+}
+
+fn vector_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(name)
+}
+
+/// Compare `actual` with the vector called `name`, or overwrite the vector
+/// when `UPDATE_GOLDEN` is set.
+fn check(name: &str, actual: &str) {
+    let path = vector_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, format!("{actual}\n")).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing golden vector {}: {e}", path.display()));
+    assert_eq!(
+        actual,
+        expected.trim_end(),
+        "encoding no longer matches {}",
+        path.display()
+    );
+}
+
+fn vector(name: &str) -> String {
+    fs::read_to_string(vector_path(name))
+        .unwrap()
+        .trim_end()
+        .to_string()
+}
+
+#[test]
+fn transaction_encoding_is_stable() {
+    let transaction = transaction(1, "send 1f2e3d4c 25");
+
+    check("transaction.hex", &hex::encode(transaction.to_bytes()));
+    check("transaction.id", &transaction.id().to_string());
+}
+
+#[test]
+fn transaction_vector_round_trips() {
+    let blob = vector("transaction.hex");
+    let transaction = transaction::from_hex(&blob).unwrap();
+
+    assert!(transaction.verify(CHAIN_ID));
+    assert!(
+        !transaction.verify("another-chain"),
+        "signed for one chain only"
+    );
+    assert_eq!(hex::encode(transaction.to_bytes()), blob);
+    assert_eq!(transaction.id().to_string(), vector("transaction.id"));
+}
+
+#[test]
+fn vote_encoding_is_stable() {
+    let keypair = keypair(2);
+    let voter = identity::PublicKey::from(keypair.public()).to_peer_id();
+
+    check(
+        "vote.hex",
+        &hex::encode(vote::encode(CHAIN_ID, 3, 1, &[7; 32], &keypair)),
+    );
+    let data = hex::decode(vector("vote.hex")).unwrap();
+    let vote = vote::decode(CHAIN_ID, &data, &voter).unwrap();
+    assert_eq!((vote.height, vote.round, vote.block_hash), (3, 1, [7; 32]));
+    assert_eq!(vote::decode("another-chain", &data, &voter), None);
+}
+
+#[test]
+fn block_encoding_is_stable() {
+    let transactions = [
+        transaction(1, "send 1f2e3d4c 25"),
+        transaction(3, "send 5b6a7988 7"),
+    ];
+
+    let block = Block::new(5, 1_700_000_000, [3; 32], transactions.into());
+
+    let mut contents = String::new();
+    storage::encode_block(&mut contents, &block);
+    check("block.txt", contents.trim_end());
+}
+
+#[cfg(feature = "rlp")]
+#[test]
+fn rlp_block_encoding_is_stable() {
+    let transactions = [
+        transaction(1, "send 1f2e3d4c 25"),
+        transaction(3, "send 5b6a7988 7"),
+    ];
+
+    check(
+        "block.rlp.hex",
+        &hex::encode(crate::rlp::encode_block(5, &transactions)),
+    );
+}
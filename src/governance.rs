@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::address::Address;
+use crate::staking::Stakes;
+use crate::transaction::{Transaction, TransactionId};
+
+/// Largest block size a proposal may ask for.
+pub const MAX_BLOCK_SIZE: usize = 1000;
+
+/// A chain parameter and the value a proposal wants to give it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterChange {
+    /// Transactions per block, up to [`MAX_BLOCK_SIZE`].
+    BlockSize(usize),
+}
+
+impl fmt::Display for ParameterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterChange::BlockSize(size) => write!(f, "block-size {size}"),
+        }
+    }
+}
+
+/// A proposal to change a chain parameter, carried in transaction data as
+/// `propose <parameter> <value> at <activation height>`, e.g.
+/// `propose block-size 20 at 50`. It is known by the id of its transaction.
+#[derive(Debug, Clone)]
+pub struct Proposal {
+    pub change: ParameterChange,
+    pub activation_height: u32,
+    // latest ballot of each voter
+    ballots: HashMap<Address, bool>,
+}
+
+impl Proposal {
+    pub fn parse(data: &[u8]) -> Option<Proposal> {
+        let data = std::str::from_utf8(data).ok()?;
+        let words: Vec<_> = data.split_whitespace().collect();
+
+        let (change, activation_height) = match words[..] {
+            ["propose", "block-size", size, "at", activation_height] => match size.parse().ok()? {
+                size @ 1..=MAX_BLOCK_SIZE => (ParameterChange::BlockSize(size), activation_height),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(Proposal {
+            change,
+            activation_height: activation_height.parse().ok()?,
+            ballots: HashMap::new(),
+        })
+    }
+
+    /// Stake voting for and against the proposal.
+    pub fn tally(&self, stakes: &Stakes) -> (u64, u64) {
+        self.ballots
+            .iter()
+            .fold((0, 0), |(yes, no), (voter, in_favour)| {
+                let stake = stakes.stake(voter);
+                if *in_favour {
+                    (yes + stake, no)
+                } else {
+                    (yes, no + stake)
+                }
+            })
+    }
+}
+
+// `vote <proposal id> yes|no`
+fn parse_ballot(data: &[u8]) -> Option<(TransactionId, bool)> {
+    let data = std::str::from_utf8(data).ok()?;
+    let mut words = data.split_whitespace();
+
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("vote"), Some(proposal), Some(ballot), None) => {
+            let in_favour = match ballot {
+                "yes" => true,
+                "no" => false,
+                _ => return None,
+            };
+            Some((proposal.parse().ok()?, in_favour))
+        }
+        _ => None,
+    }
+}
+
+/// How a proposal fared once voting on it closed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub proposal: TransactionId,
+    pub change: ParameterChange,
+    pub passed: bool,
+}
+
+/// Proposals still open for votes.
+///
+/// Any address may propose or vote, and ballots weigh as much as the
+/// voter's bonded stake when voting closes, the block before the
+/// activation height. A proposal passes with more than half of all bonded
+/// stake in favour, so not voting counts against it, and the change is in
+/// effect from the block at its activation height on.
+#[derive(Default)]
+pub struct Governance {
+    // in the order they were committed, so changes to the same parameter
+    // activating together are applied in the same order everywhere
+    proposals: Vec<(TransactionId, Proposal)>,
+}
+
+impl Governance {
+    pub fn proposals(&self) -> impl Iterator<Item = (&TransactionId, &Proposal)> {
+        self.proposals.iter().map(|(id, proposal)| (id, proposal))
+    }
+
+    /// Record the proposals and votes of the block committed at `height`,
+    /// after the ledger has applied it, and close the votes on the proposals
+    /// activating at the next height. Proposals activating any earlier are
+    /// ignored.
+    pub fn apply(
+        &mut self,
+        height: u32,
+        transactions: &[Transaction],
+        stakes: &Stakes,
+    ) -> Vec<Outcome> {
+        for transaction in transactions {
+            let voter = Address::from(&transaction.public_key);
+
+            if let Some(proposal) = Proposal::parse(&transaction.data) {
+                if proposal.activation_height > height + 1 {
+                    self.proposals.push((transaction.id(), proposal));
+                }
+            } else if let Some((id, in_favour)) = parse_ballot(&transaction.data) {
+                if let Some((_, proposal)) = self.proposals.iter_mut().find(|(open, _)| *open == id)
+                {
+                    proposal.ballots.insert(voter, in_favour);
+                }
+            }
+        }
+
+        let total = stakes.total();
+        let mut outcomes = Vec::new();
+        self.proposals.retain(|(id, proposal)| {
+            if proposal.activation_height > height + 1 {
+                return true;
+            }
+            let (yes, _) = proposal.tally(stakes);
+            outcomes.push(Outcome {
+                proposal: *id,
+                change: proposal.change,
+                passed: yes > total / 2,
+            });
+            false
+        });
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    fn sign(keypair: &Keypair, data: &str) -> Transaction {
+        block_on(Transaction::sign(keypair, "test", data.as_bytes(), 1)).unwrap()
+    }
+
+    #[test]
+    fn proposals_pass_with_a_majority_of_the_stake() {
+        let (alice, bob, carol) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+        let mut stakes = Stakes::new(10);
+        stakes.bond(Address::from(&alice.public()), 60);
+        stakes.bond(Address::from(&bob.public()), 30);
+        stakes.bond(Address::from(&carol.public()), 10);
+        let mut governance = Governance::default();
+
+        let proposals = [
+            sign(&carol, "propose block-size 20 at 4"),
+            sign(&carol, "propose block-size 5 at 4"),
+            sign(&carol, "propose block-size 30 at 2"),
+        ];
+        let (bigger, smaller) = (proposals[0].id(), proposals[1].id());
+        assert!(governance.apply(1, &proposals, &stakes).is_empty());
+        assert_eq!(governance.proposals().count(), 2);
+
+        let ballots = [
+            sign(&alice, &format!("vote {bigger} no")),
+            sign(&bob, &format!("vote {bigger} yes")),
+            sign(&carol, &format!("vote {bigger} yes")),
+            sign(&bob, &format!("vote {smaller} yes")),
+            sign(&carol, &format!("vote {smaller} yes")),
+        ];
+        assert!(governance.apply(2, &ballots, &stakes).is_empty());
+
+        // alice changes sides before voting closes
+        let change_of_mind = sign(&alice, &format!("vote {bigger} yes"));
+        assert_eq!(
+            governance.apply(3, &[change_of_mind], &stakes),
+            [
+                Outcome {
+                    proposal: bigger,
+                    change: ParameterChange::BlockSize(20),
+                    passed: true
+                },
+                Outcome {
+                    proposal: smaller,
+                    change: ParameterChange::BlockSize(5),
+                    passed: false
+                },
+            ]
+        );
+        assert_eq!(governance.proposals().count(), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_proposals() {
+        for data in [
+            "propose block-size 0 at 5",
+            "propose block-size 1001 at 5",
+            "propose block-size 20",
+            "propose block-interval 20 at 5",
+        ] {
+            assert!(Proposal::parse(data.as_bytes()).is_none(), "{data}");
+        }
+    }
+}
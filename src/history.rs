@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::address::Address;
+use crate::ledger::Transfer;
+use crate::transaction::Transaction;
+
+/// Number of entries returned per page of `/history`.
+pub const PAGE_SIZE: usize = 10;
+
+pub struct CommittedTransaction {
+    pub height: u32,
+    /// When this node committed the block, by its own clock.
+    pub committed_at: SystemTime,
+    pub transaction: Transaction,
+}
+
+/// Index of committed transactions by the addresses that sent or received them.
+#[derive(Default)]
+pub struct TransactionIndex {
+    committed: Vec<CommittedTransaction>,
+    by_address: HashMap<Address, Vec<usize>>,
+}
+
+impl TransactionIndex {
+    /// Index the transactions committed in the block at `height`.
+    pub fn insert_block(
+        &mut self,
+        height: u32,
+        committed_at: SystemTime,
+        transactions: Vec<Transaction>,
+    ) {
+        for transaction in transactions {
+            let position = self.committed.len();
+            let sender = Address::from(&transaction.public_key);
+            self.by_address.entry(sender).or_default().push(position);
+
+            if let Some(transfer) = Transfer::parse(&transaction.data) {
+                if transfer.recipient != sender {
+                    self.by_address
+                        .entry(transfer.recipient)
+                        .or_default()
+                        .push(position);
+                }
+            }
+
+            self.committed.push(CommittedTransaction {
+                height,
+                committed_at,
+                transaction,
+            });
+        }
+    }
+
+    /// The transactions of each block committed so far, lowest height first.
+    /// Blocks without transactions are missing.
+    pub fn blocks(&self) -> impl Iterator<Item = &[CommittedTransaction]> {
+        self.committed
+            .chunk_by(|previous, next| previous.height == next.height)
+    }
+
+    /// Committed transactions sent or received by `address`, oldest first.
+    /// `page` is zero based and holds up to [`PAGE_SIZE`] entries.
+    pub fn history(&self, address: &Address, page: usize) -> Vec<&CommittedTransaction> {
+        self.by_address
+            .get(address)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .skip(page.saturating_mul(PAGE_SIZE))
+                    .take(PAGE_SIZE)
+                    .map(|position| &self.committed[*position])
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn history_len(&self, address: &Address) -> usize {
+        self.by_address.get(address).map_or(0, Vec::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    // `count` transfers from one account to another, one per block
+    fn index_with(count: u32) -> (TransactionIndex, Address, Address) {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (from, to) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let mut index = TransactionIndex::default();
+        for height in 1..=count {
+            let data = format!("send {to} {height}");
+            let transaction = block_on(Transaction::sign(
+                &alice,
+                "test",
+                data.as_bytes(),
+                height.into(),
+            ))
+            .unwrap();
+            index.insert_block(height, SystemTime::UNIX_EPOCH, vec![transaction]);
+        }
+        (index, from, to)
+    }
+
+    #[test]
+    fn pages_through_sent_and_received_transactions() {
+        let (index, from, to) = index_with(PAGE_SIZE as u32 + 3);
+        assert_eq!(index.history_len(&from), PAGE_SIZE + 3);
+        assert_eq!(index.history_len(&to), PAGE_SIZE + 3);
+
+        let first = index.history(&to, 0);
+        assert_eq!(first.len(), PAGE_SIZE);
+        assert_eq!(first[0].height, 1, "oldest first");
+
+        let last = index.history(&from, 1);
+        let heights: Vec<_> = last.iter().map(|committed| committed.height).collect();
+        assert_eq!(heights, [11, 12, 13], "the last page is partial");
+    }
+
+    #[test]
+    fn pages_past_the_end_are_empty() {
+        let (index, from, _) = index_with(3);
+        assert!(index.history(&from, 1).is_empty());
+        assert!(index.history(&from, usize::MAX).is_empty());
+
+        let stranger = Address::from(&Keypair::generate().public());
+        assert!(index.history(&stranger, 0).is_empty());
+        assert_eq!(index.history_len(&stranger), 0);
+    }
+}
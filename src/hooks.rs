@@ -0,0 +1,79 @@
+//! Shortcuts into a running node for tests that don't want to wait on
+//! gossip, available with the `test-utils` feature.
+
+// nothing in the node itself drives these, only tests do
+#![cfg_attr(not(test), allow(dead_code))]
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use libp2p::PeerId;
+use std::time::Duration;
+
+use crate::address::Address;
+use crate::transaction::Transaction;
+
+#[derive(Debug)]
+pub enum Hook {
+    /// Commit the next block from whatever is in the mempool, up to a full
+    /// block, without waiting for votes.
+    ProduceBlock,
+    /// Put a transaction straight into the mempool, without verifying or gossiping it.
+    InjectTransaction(Transaction),
+    /// Count a vote for the block proposed at `height` as if it had been
+    /// received from `voter`.
+    InjectVote { height: u32, voter: PeerId },
+    /// Pretend this much time passed without any progress. Consensus has no
+    /// timers of its own yet, so this only moves the stall watchdog.
+    AdvanceClock(Duration),
+    /// Publish `data` on `topic` as is, e.g. to send messages no honest node would.
+    PublishRaw { topic: String, data: Vec<u8> },
+    /// Send the committed balance of `address` back on `reply`.
+    Balance {
+        address: Address,
+        reply: oneshot::Sender<u64>,
+    },
+}
+
+/// Sends [`Hook`]s to the node given the matching receiver in its `Environment`.
+#[derive(Clone)]
+pub struct Hooks(UnboundedSender<Hook>);
+
+pub fn channel() -> (Hooks, UnboundedReceiver<Hook>) {
+    let (sender, receiver) = mpsc::unbounded();
+    (Hooks(sender), receiver)
+}
+
+impl Hooks {
+    pub fn produce_block(&self) {
+        self.send(Hook::ProduceBlock);
+    }
+
+    pub fn inject_transaction(&self, transaction: Transaction) {
+        self.send(Hook::InjectTransaction(transaction));
+    }
+
+    pub fn inject_vote(&self, height: u32, voter: PeerId) {
+        self.send(Hook::InjectVote { height, voter });
+    }
+
+    pub fn advance_clock(&self, by: Duration) {
+        self.send(Hook::AdvanceClock(by));
+    }
+
+    pub fn publish_raw(&self, topic: &str, data: &[u8]) {
+        self.send(Hook::PublishRaw {
+            topic: topic.to_string(),
+            data: data.to_vec(),
+        });
+    }
+
+    pub async fn balance(&self, address: Address) -> u64 {
+        let (reply, balance) = oneshot::channel();
+        self.send(Hook::Balance { address, reply });
+        balance.await.expect("node is running")
+    }
+
+    fn send(&self, hook: Hook) {
+        self.0.unbounded_send(hook).expect("node is running");
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::address::Address;
+use crate::bridge::{self, BridgeGenesis, Withdrawal};
+use crate::genesis::{Emission, Genesis};
+use crate::script::{Script, Witness};
+use crate::staking::{StakeChange, Stakes, DEFAULT_UNBONDING_PERIOD};
+use crate::transaction::{Transaction, TransactionId};
+
+/// A value transfer carried in a transaction's data as `send <recipient> <amount>`.
+///
+/// Transactions with any other data are still valid, they just don't move coins.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub recipient: Address,
+    pub amount: u64,
+}
+
+impl Transfer {
+    pub fn parse(data: &[u8]) -> Option<Transfer> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.split_whitespace();
+
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("send"), Some(recipient), Some(amount), None) => Some(Transfer {
+                recipient: recipient.parse().ok()?,
+                amount: amount.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Coins locked under a script by `lock <recipient> <amount> <script>`, see [`crate::script`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lock {
+    pub recipient: Address,
+    pub amount: u64,
+    pub script: Script,
+}
+
+impl Lock {
+    pub fn parse(data: &[u8]) -> Option<Lock> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.trim().splitn(4, char::is_whitespace);
+
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("lock"), Some(recipient), Some(amount), Some(script)) => Some(Lock {
+                recipient: recipient.parse().ok()?,
+                amount: amount.parse().ok()?,
+                script: script.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// `claim <lock id> [<witness>...]`
+fn parse_claim(data: &[u8]) -> Option<(TransactionId, Witness)> {
+    let data = std::str::from_utf8(data).ok()?;
+    let mut words = data.trim().splitn(3, char::is_whitespace);
+
+    match (words.next(), words.next(), words.next()) {
+        (Some("claim"), Some(lock_id), witness) => Some((
+            lock_id.parse().ok()?,
+            witness.unwrap_or_default().parse().ok()?,
+        )),
+        _ => None,
+    }
+}
+
+// Coins `transaction` takes out of its sender's balance, its fee included.
+fn spent(transaction: &Transaction) -> u64 {
+    transaction.fee.saturating_add(spent_by(&transaction.data))
+}
+
+fn spent_by(data: &[u8]) -> u64 {
+    if let Some(transfer) = Transfer::parse(data) {
+        transfer.amount
+    } else if let Some(lock) = Lock::parse(data) {
+        lock.amount
+    } else if let Some(StakeChange::Bond(amount)) = StakeChange::parse(data) {
+        amount
+    } else if let Some(withdrawal) = Withdrawal::parse(data) {
+        withdrawal.amount
+    } else {
+        0
+    }
+}
+
+/// Account balances derived from the committed chain, the coins locked
+/// under scripts, the coins staked and the withdrawals bridged in.
+#[derive(Clone, Default)]
+pub struct Ledger {
+    balances: HashMap<Address, u64>,
+    locks: HashMap<TransactionId, Lock>,
+    stakes: Stakes,
+    // height of the last block applied
+    height: u32,
+    // coins in existence, allocated at genesis or minted since
+    supply: u64,
+    max_supply: Option<u64>,
+    emission: Option<Emission>,
+    bridge: Option<BridgeGenesis>,
+    // withdrawals on the source chain already minted here
+    minted: HashSet<TransactionId>,
+    // nonce of each sender's last committed transaction
+    nonces: HashMap<Address, u64>,
+}
+
+impl Ledger {
+    /// Start the ledger from the balances allocated at genesis, minting
+    /// block rewards as the genesis' emission schedule says.
+    pub fn with_genesis(genesis: &Genesis) -> Self {
+        let mut ledger = Ledger {
+            max_supply: genesis.max_supply,
+            emission: genesis.emission.clone(),
+            bridge: genesis.bridge.clone(),
+            stakes: Stakes::new(genesis.unbonding_period.unwrap_or(DEFAULT_UNBONDING_PERIOD)),
+            ..Ledger::default()
+        };
+        for (address, amount) in &genesis.allocations {
+            ledger.credit(*address, *amount);
+            ledger.supply = ledger.supply.saturating_add(*amount);
+        }
+        ledger
+    }
+
+    pub fn supply(&self) -> u64 {
+        self.supply
+    }
+
+    pub fn stakes(&self) -> &Stakes {
+        &self.stakes
+    }
+
+    pub fn balance(&self, address: &Address) -> u64 {
+        self.balances.get(address).copied().unwrap_or_default()
+    }
+
+    /// Nonce of the last transaction committed from `address`, 0 if none was.
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.nonces.get(address).copied().unwrap_or_default()
+    }
+
+    /// Nonce for the next transaction from `address`, one above the last
+    /// committed or waiting in the mempool.
+    pub fn next_nonce<'a>(
+        &self,
+        address: &Address,
+        mempool: impl IntoIterator<Item = &'a Transaction>,
+    ) -> u64 {
+        mempool
+            .into_iter()
+            .filter(|transaction| Address::from(&transaction.public_key) == *address)
+            .map(|transaction| transaction.nonce)
+            .fold(self.nonce(address), u64::max)
+            + 1
+    }
+
+    /// Whether `transaction`'s nonce isn't above its sender's last committed one.
+    pub fn is_stale(&self, transaction: &Transaction) -> bool {
+        transaction.nonce <= self.nonce(&Address::from(&transaction.public_key))
+    }
+
+    /// Release the stake whose unbonding period is over, apply the
+    /// transfers, locks, claims, stake changes and bridge transactions of the
+    /// block committed at `height` in order, then mint its reward, returning
+    /// the addresses whose balance changed. Transactions whose nonce doesn't
+    /// follow their sender's last committed one are skipped, the others use
+    /// their nonce up even if they are skipped for what follows. Fees are
+    /// burnt, and transactions whose sender can't pay theirs are skipped.
+    /// Transfers, locks, bonds and withdrawals that would overdraw the sender
+    /// are skipped too, and so are claims that don't meet their lock's
+    /// script, unbonds of more than is staked and mints [`crate::bridge`]
+    /// doesn't accept.
+    pub fn apply<'a>(
+        &mut self,
+        height: u32,
+        transactions: impl IntoIterator<Item = &'a Transaction>,
+    ) -> BTreeSet<Address> {
+        self.height = height;
+        let mut changed = BTreeSet::new();
+        for (address, amount) in self.stakes.release(height) {
+            self.credit(address, amount);
+            changed.insert(address);
+        }
+        for transaction in transactions {
+            self.apply_transaction(transaction, &mut changed);
+        }
+
+        if let Some(emission) = &self.emission {
+            let left = self
+                .max_supply
+                .unwrap_or(u64::MAX)
+                .saturating_sub(self.supply);
+            let reward = emission.reward(height).min(left);
+            if reward > 0 {
+                let recipient = emission.recipient;
+                self.credit(recipient, reward);
+                self.supply += reward;
+                changed.insert(recipient);
+            }
+        }
+        changed
+    }
+
+    /// Move the balance and stake of `from` over to `to`, e.g. when a
+    /// validator rotates its key, see [`crate::validators`].
+    pub fn move_account(&mut self, from: &Address, to: Address) {
+        if let Some(balance) = self.balances.remove(from) {
+            self.credit(to, balance);
+        }
+        self.stakes.move_stake(from, to);
+    }
+
+    pub fn lock(&self, lock_id: &TransactionId) -> Option<&Lock> {
+        self.locks.get(lock_id)
+    }
+
+    /// Balance of `address` if every transaction currently in the mempool were committed.
+    pub fn pending_balance<'a>(
+        &self,
+        address: &Address,
+        mempool: impl IntoIterator<Item = &'a Transaction>,
+    ) -> u64 {
+        let mut pending = self.clone();
+        pending.apply(self.height + 1, mempool);
+        pending.balance(address)
+    }
+
+    /// Those of `transactions` that would overdraw their sender, if they
+    /// were committed in order on top of the current balances.
+    pub fn overdrafts<'a>(
+        &self,
+        transactions: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Vec<TransactionId> {
+        let mut pending = self.clone();
+        pending.height += 1;
+        let mut overdrawn = Vec::new();
+        for transaction in transactions {
+            let sender = Address::from(&transaction.public_key);
+            if spent(transaction) > pending.balance(&sender) {
+                overdrawn.push(transaction.id());
+            } else {
+                pending.apply_transaction(transaction, &mut BTreeSet::new());
+            }
+        }
+        overdrawn
+    }
+
+    /// Those of `transactions` whose nonce doesn't follow their sender's
+    /// last one, committed or earlier in `transactions`.
+    pub fn bad_nonces<'a>(
+        &self,
+        transactions: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Vec<TransactionId> {
+        let mut nonces = self.nonces.clone();
+        let mut bad = Vec::new();
+        for transaction in transactions {
+            let last = nonces
+                .entry(Address::from(&transaction.public_key))
+                .or_default();
+            if transaction.nonce == *last + 1 {
+                *last = transaction.nonce;
+            } else {
+                bad.push(transaction.id());
+            }
+        }
+        bad
+    }
+
+    fn apply_transaction(&mut self, transaction: &Transaction, changed: &mut BTreeSet<Address>) {
+        let sender = Address::from(&transaction.public_key);
+        let last = self.nonces.entry(sender).or_default();
+        if transaction.nonce != *last + 1 {
+            return;
+        }
+        *last = transaction.nonce;
+
+        if transaction.fee > 0 {
+            if !self.debit(&sender, transaction.fee) {
+                return;
+            }
+            self.supply -= transaction.fee;
+            changed.insert(sender);
+        }
+
+        if let Some(transfer) = Transfer::parse(&transaction.data) {
+            if self.debit(&sender, transfer.amount) {
+                self.credit(transfer.recipient, transfer.amount);
+                changed.extend([sender, transfer.recipient]);
+            }
+        } else if let Some(lock) = Lock::parse(&transaction.data) {
+            if self.debit(&sender, lock.amount) {
+                self.locks.insert(transaction.id(), lock);
+                changed.insert(sender);
+            }
+        } else if let Some((lock_id, witness)) = parse_claim(&transaction.data) {
+            let met = self
+                .locks
+                .get(&lock_id)
+                .is_some_and(|lock| lock.script.check(self.height, &lock_id, &witness));
+            if let Some(lock) = met.then(|| self.locks.remove(&lock_id)).flatten() {
+                self.credit(lock.recipient, lock.amount);
+                changed.insert(lock.recipient);
+            }
+        } else if let Some(change) = StakeChange::parse(&transaction.data) {
+            match change {
+                StakeChange::Bond(amount) => {
+                    if self.debit(&sender, amount) {
+                        self.stakes.bond(sender, amount);
+                        changed.insert(sender);
+                    }
+                }
+                // the balance only changes once the coins are released
+                StakeChange::Unbond(amount) => {
+                    self.stakes.unbond(sender, amount, self.height);
+                }
+            }
+        } else if let Some(withdrawal) = Withdrawal::parse(&transaction.data) {
+            // the coins leave this chain, to be minted on the destination
+            if self.debit(&sender, withdrawal.amount) {
+                self.supply -= withdrawal.amount;
+                changed.insert(sender);
+            }
+        } else if let Some(source) = bridge::parse_mint(&transaction.data) {
+            let Some(bridge) = &self.bridge else {
+                return;
+            };
+            let left = self
+                .max_supply
+                .unwrap_or(u64::MAX)
+                .saturating_sub(self.supply);
+            let withdrawal = Withdrawal::parse(&source.data);
+            // a mint past the max supply is refused, it may go through once coins are burnt
+            if let Some(withdrawal) = withdrawal.filter(|withdrawal| {
+                bridge.relayers.contains(&sender)
+                    && withdrawal.amount <= left
+                    && source.verify(&bridge.source_chain)
+                    && self.minted.insert(source.id())
+            }) {
+                self.credit(withdrawal.recipient, withdrawal.amount);
+                self.supply += withdrawal.amount;
+                changed.insert(withdrawal.recipient);
+            }
+        }
+    }
+
+    fn debit(&mut self, address: &Address, amount: u64) -> bool {
+        let balance = self.balance(address);
+        if balance < amount {
+            return false;
+        }
+        self.balances.insert(*address, balance - amount);
+        true
+    }
+
+    fn credit(&mut self, address: Address, amount: u64) {
+        let balance = self.balances.entry(address).or_default();
+        *balance = balance.saturating_add(amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+    use sha2::{Digest, Sha256};
+
+    fn sign(keypair: &Keypair, nonce: u64, data: &str) -> Transaction {
+        block_on(Transaction::sign(keypair, "test", data.as_bytes(), nonce)).unwrap()
+    }
+
+    #[test]
+    fn locked_coins_go_to_the_recipient_once_claimed() {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (from, to) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let mut ledger = Ledger::with_genesis(&Genesis::with_allocations(&[(from, 100)]));
+
+        let digest = hex::encode(Sha256::digest(b"secret"));
+        let lock = sign(
+            &alice,
+            1,
+            &format!("lock {to} 30 after 3 and hash {digest}"),
+        );
+        let lock_id = lock.id();
+        assert_eq!(ledger.apply(1, &[lock]), BTreeSet::from([from]));
+        assert_eq!(ledger.balance(&from), 70);
+        assert!(ledger.lock(&lock_id).is_some());
+
+        let claim = format!("claim {lock_id} preimage {}", hex::encode("secret"));
+        assert!(
+            ledger.apply(2, &[sign(&bob, 1, &claim)]).is_empty(),
+            "time locked until 3"
+        );
+        assert!(ledger
+            .apply(3, &[sign(&bob, 2, &format!("claim {lock_id} preimage 00"))])
+            .is_empty());
+        assert_eq!(
+            ledger.apply(3, &[sign(&bob, 3, &claim)]),
+            BTreeSet::from([to])
+        );
+        assert_eq!(ledger.balance(&to), 30);
+        assert!(ledger.lock(&lock_id).is_none());
+
+        // claimed once only
+        assert!(ledger.apply(4, &[sign(&bob, 4, &claim)]).is_empty());
+        assert_eq!(ledger.balance(&to), 30);
+    }
+
+    #[test]
+    fn fees_are_burnt() {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (from, to) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let mut ledger = Ledger::with_genesis(&Genesis::with_allocations(&[(from, 100)]));
+        let send = |nonce, amount: u64, fee| {
+            let data = format!("send {to} {amount}");
+            block_on(Transaction::sign_with_fee(
+                &alice,
+                "test",
+                data.as_bytes(),
+                nonce,
+                fee,
+            ))
+            .unwrap()
+        };
+
+        let (paid, overdrawn) = (send(1, 60, 5), send(2, 30, 6));
+        assert_eq!(
+            ledger.overdrafts([&paid, &overdrawn]),
+            vec![overdrawn.id()],
+            "the fee counts towards what is spent"
+        );
+        ledger.apply(1, &[paid]);
+        assert_eq!((ledger.balance(&from), ledger.balance(&to)), (35, 60));
+        assert_eq!(ledger.supply(), 95);
+
+        ledger.apply(2, &[send(2, 0, 36)]);
+        assert_eq!(ledger.balance(&from), 35, "a fee the sender can't pay");
+    }
+
+    #[test]
+    fn overdrafts_count_earlier_spending() {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (from, to) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let ledger = Ledger::with_genesis(&Genesis::with_allocations(&[(from, 100)]));
+
+        let first = sign(&alice, 1, &format!("send {to} 60"));
+        let second = sign(&alice, 2, &format!("send {to} 50"));
+        let forwarded = sign(&bob, 1, &format!("send {from} 60"));
+        assert!(ledger.overdrafts([&first]).is_empty());
+        assert_eq!(
+            ledger.overdrafts([&first, &second, &forwarded]),
+            vec![second.id()]
+        );
+        assert_eq!(
+            ledger.overdrafts([&forwarded, &first]),
+            vec![forwarded.id()],
+            "bob has nothing before alice pays"
+        );
+        assert_eq!(ledger.balance(&from), 100, "nothing was committed");
+    }
+
+    #[test]
+    fn replayed_transactions_are_skipped() {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (from, to) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let mut ledger = Ledger::with_genesis(&Genesis::with_allocations(&[(from, 100)]));
+
+        let transfer = sign(&alice, 1, &format!("send {to} 30"));
+        let (overdraft, next) = (
+            sign(&alice, 2, &format!("send {to} 500")),
+            sign(&alice, 3, &format!("send {to} 10")),
+        );
+        assert_eq!(ledger.next_nonce(&from, std::slice::from_ref(&transfer)), 2);
+        assert_eq!(
+            ledger.bad_nonces([&transfer, &transfer, &next]),
+            vec![transfer.id(), next.id()],
+            "replayed within the block, and a gap"
+        );
+        assert!(ledger.apply(1, &[next]).is_empty(), "waits for the gap");
+        assert_eq!(ledger.nonce(&from), 0);
+
+        ledger.apply(2, std::slice::from_ref(&transfer));
+        assert_eq!(ledger.nonce(&from), 1);
+        assert!(ledger.is_stale(&transfer) && !ledger.is_stale(&overdraft));
+        assert!(
+            ledger.apply(3, &[transfer]).is_empty(),
+            "replayed in a later block"
+        );
+        assert_eq!((ledger.balance(&from), ledger.balance(&to)), (70, 30));
+
+        let next = sign(&alice, 3, &format!("send {to} 10"));
+        ledger.apply(4, &[overdraft, next]);
+        assert_eq!(
+            (ledger.balance(&from), ledger.nonce(&from)),
+            (60, 3),
+            "an overdraft uses its nonce up"
+        );
+    }
+
+    #[test]
+    fn unbonded_stake_is_released_after_the_unbonding_period() {
+        let validator = Keypair::generate();
+        let address = Address::from(&validator.public());
+        let genesis = Genesis {
+            unbonding_period: Some(3),
+            ..Genesis::with_allocations(&[(address, 100)])
+        };
+        let mut ledger = Ledger::with_genesis(&genesis);
+
+        ledger.apply(
+            1,
+            &[
+                sign(&validator, 1, "bond 60"),
+                sign(&validator, 2, "bond 50"),
+            ],
+        );
+        assert_eq!(
+            ledger.stakes().stake(&address),
+            60,
+            "can't bond more than held"
+        );
+        assert_eq!(ledger.balance(&address), 40);
+
+        ledger.apply(2, &[sign(&validator, 3, "unbond 70")]);
+        assert_eq!(ledger.stakes().stake(&address), 60);
+        assert!(ledger
+            .apply(2, &[sign(&validator, 4, "unbond 60")])
+            .is_empty());
+        assert_eq!(ledger.stakes().stake(&address), 0);
+        assert_eq!(ledger.stakes().bonded().count(), 0);
+        assert_eq!(
+            ledger.balance(&address),
+            40,
+            "unbonding coins can't be spent"
+        );
+
+        ledger.apply(4, &[]);
+        assert_eq!(ledger.balance(&address), 40);
+        assert_eq!(ledger.apply(5, &[]), BTreeSet::from([address]));
+        assert_eq!(ledger.balance(&address), 100);
+        assert!(ledger.stakes().unbonding(&address).is_empty());
+    }
+
+    #[test]
+    fn bridged_coins_are_minted_once_per_withdrawal() {
+        let (user, relayer, impostor) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+        let recipient = Address::from(&Keypair::generate().public());
+        let user_address = Address::from(&user.public());
+        let mut source = Ledger::with_genesis(&Genesis::with_allocations(&[(user_address, 50)]));
+        let withdrawal = sign(&user, 1, &format!("bridge-out test-b {recipient} 20"));
+        source.apply(1, std::slice::from_ref(&withdrawal));
+        assert_eq!(source.balance(&user_address), 30);
+        assert_eq!(source.supply(), 30);
+
+        let mut destination = Ledger::with_genesis(&Genesis {
+            bridge: Some(BridgeGenesis {
+                source_chain: "test".to_string(),
+                relayers: vec![Address::from(&relayer.public())],
+            }),
+            ..Genesis::default()
+        });
+        let mint = format!("mint {}", hex::encode(withdrawal.to_bytes()));
+        assert!(destination
+            .apply(1, &[sign(&impostor, 1, &mint)])
+            .is_empty());
+        assert_eq!(
+            destination.apply(2, &[sign(&relayer, 1, &mint), sign(&relayer, 2, &mint)]),
+            BTreeSet::from([recipient])
+        );
+        assert_eq!(destination.balance(&recipient), 20, "minted once");
+        assert_eq!(destination.supply(), 20);
+    }
+
+    #[test]
+    fn bridged_coins_are_not_minted_past_the_max_supply() {
+        let (user, relayer) = (Keypair::generate(), Keypair::generate());
+        let recipient = Address::from(&Keypair::generate().public());
+        let withdrawal = sign(&user, 1, &format!("bridge-out test-b {recipient} 20"));
+        let mut destination = Ledger::with_genesis(&Genesis {
+            max_supply: Some(30),
+            allocations: vec![(recipient, 15)],
+            emission: Some(Emission {
+                block_reward: 10,
+                halving_interval: None,
+                recipient,
+            }),
+            bridge: Some(BridgeGenesis {
+                source_chain: "test".to_string(),
+                relayers: vec![Address::from(&relayer.public())],
+            }),
+            ..Genesis::default()
+        });
+
+        let mint = format!("mint {}", hex::encode(withdrawal.to_bytes()));
+        destination.apply(1, &[sign(&relayer, 1, &mint)]);
+        assert_eq!(destination.supply(), 25, "only the block reward");
+        // the reward can't take the supply past the cap, nor panic once it is reached
+        destination.apply(2, &[sign(&relayer, 2, &mint)]);
+        destination.apply(3, &[]);
+        assert_eq!(destination.supply(), 30);
+        assert_eq!(destination.balance(&recipient), 30);
+    }
+
+    #[test]
+    fn block_rewards_halve_until_the_supply_runs_out() {
+        let miner = Address::from(&Keypair::generate().public());
+        let genesis = Genesis {
+            allocations: vec![(miner, 10)],
+            max_supply: Some(45),
+            emission: Some(Emission {
+                block_reward: 16,
+                halving_interval: Some(2),
+                recipient: miner,
+            }),
+            ..Genesis::default()
+        };
+        let mut ledger = Ledger::with_genesis(&genesis);
+
+        // 16, 16, then 8 of which only 3 are left under the cap
+        let balances: Vec<_> = (1..=4)
+            .map(|height| {
+                ledger.apply(height, &[]);
+                ledger.balance(&miner)
+            })
+            .collect();
+        assert_eq!(balances, [26, 42, 45, 45]);
+        assert_eq!(ledger.supply(), 45);
+    }
+}
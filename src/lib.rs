@@ -0,0 +1,72 @@
+//! An educational blockchain node: accounts and transactions gossiped over
+//! libp2p, blocks proposed and voted on by every connected node, and the
+//! state derived from them.
+//!
+//! The `bloackchain_workshop` binary runs one node with a terminal attached.
+//! Other binaries and tests embed nodes with [`node::Node`], see
+//! [`node::Environment`] for what a node takes from its surroundings.
+
+// the types shared with other clients, under the paths they had before moving to their own crate
+pub use educoin_core::{cid, merkle, proto, signer, transaction};
+
+pub mod address;
+pub mod alerts;
+pub mod assembler;
+pub mod audit;
+pub mod batch;
+pub mod block;
+pub mod bridge;
+pub mod byzantine;
+pub mod cli;
+pub mod consensus;
+pub mod dashboard;
+pub mod error;
+pub mod explorer;
+pub mod export;
+pub mod faucet;
+pub mod faults;
+pub mod genesis;
+#[cfg(test)]
+mod golden;
+pub mod governance;
+pub mod history;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod hooks;
+pub mod ledger;
+pub mod lifecycle;
+pub mod light;
+pub mod logfile;
+pub mod message;
+pub mod metrics;
+pub mod names;
+pub mod nats;
+pub mod network;
+pub mod node;
+pub mod penalties;
+pub mod pow;
+pub mod proposal;
+pub mod recording;
+pub mod relay;
+#[cfg(feature = "rlp")]
+pub mod rlp;
+pub mod rpc;
+pub mod script;
+pub mod seen;
+pub mod signals;
+pub mod simulation;
+pub mod snapshot;
+pub mod sql_mirror;
+pub mod staking;
+pub mod state;
+pub mod storage;
+pub mod sync;
+pub mod telemetry;
+#[cfg(test)]
+mod testing;
+pub mod topology;
+pub mod validators;
+pub mod verifier;
+pub mod vote;
+pub mod wallet;
+pub mod watchdog;
+pub mod webhooks;
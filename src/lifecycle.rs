@@ -0,0 +1,71 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::transaction::TransactionId;
+
+// Oldest traces are forgotten past this many transactions.
+const MAX_TRACES: usize = 10_000;
+
+/// Steps a transaction goes through on its way into a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Milestone {
+    /// Submitted locally or received from a peer.
+    Received,
+    /// Published on the transaction topic.
+    Gossiped,
+    Admitted,
+    /// Part of a batch this node validated and voted on.
+    Proposed,
+    Committed,
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Milestone::Received => "received",
+            Milestone::Gossiped => "gossiped",
+            Milestone::Admitted => "admitted to mempool",
+            Milestone::Proposed => "included in proposal",
+            Milestone::Committed => "committed",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Milestones reached by recent transactions, keyed by transaction id, which
+/// doubles as the trace id in the logs.
+#[derive(Default)]
+pub struct Lifecycle {
+    traces: HashMap<TransactionId, Vec<(Milestone, u128)>>,
+    order: VecDeque<TransactionId>,
+}
+
+impl Lifecycle {
+    pub fn record(&mut self, id: TransactionId, milestone: Milestone) {
+        info!(trace_id = %id, %milestone, "transaction milestone");
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let trace = self.traces.entry(id).or_insert_with(|| {
+            self.order.push_back(id);
+            Vec::new()
+        });
+        trace.push((milestone, timestamp_ms));
+
+        if self.order.len() > MAX_TRACES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.traces.remove(&oldest);
+            }
+        }
+    }
+
+    /// Milestones reached so far by `id` with their unix timestamps in
+    /// milliseconds, oldest first.
+    pub fn trace(&self, id: &TransactionId) -> Option<&[(Milestone, u128)]> {
+        self.traces.get(id).map(Vec::as_slice)
+    }
+}
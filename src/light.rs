@@ -0,0 +1,272 @@
+//! Following the chain by its headers alone, as a light client does: every
+//! block's height, timestamp, previous hash and Merkle root, with the votes
+//! or proof-of-work it was committed with, but none of its transactions.
+//!
+//! A header is taken once it follows the last one and was agreed on, the
+//! same way a fetched block is, see [`Agreement`]. A transaction is then
+//! shown to be in a block by an [`InclusionProof`] of its id against the
+//! header's Merkle root, which any full node can put together with
+//! [`prove`].
+
+use libp2p::PeerId;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::block::{self, Block, Tip};
+use crate::consensus::{self, Quorum};
+use crate::merkle::{Hash, InclusionProof};
+use crate::pow;
+use crate::transaction::TransactionId;
+use crate::vote;
+
+/// A block without its transactions.
+pub struct Header {
+    pub height: u32,
+    pub timestamp: u64,
+    pub prev_hash: Hash,
+    pub merkle_root: Hash,
+    pub nonce: u64,
+    pub votes: Vec<Vec<u8>>,
+}
+
+impl Header {
+    /// Hash of the block, recomputed from the header.
+    pub fn hash(&self) -> Hash {
+        block::hash(
+            self.height,
+            self.timestamp,
+            &self.prev_hash,
+            &self.merkle_root,
+        )
+    }
+}
+
+impl From<&Block> for Header {
+    fn from(block: &Block) -> Self {
+        Header {
+            height: block.height,
+            timestamp: block.timestamp,
+            prev_hash: block.prev_hash,
+            merkle_root: block.merkle_root,
+            nonce: block.nonce,
+            votes: block.votes.clone(),
+        }
+    }
+}
+
+/// How a block counts as agreed on: voted for by a `quorum` of `validators`
+/// on `chain_id`, or mined with enough work for `difficulty`.
+pub enum Agreement<'a> {
+    Votes {
+        chain_id: &'a str,
+        quorum: Quorum,
+        validators: &'a BTreeSet<PeerId>,
+    },
+    Work {
+        difficulty: u32,
+    },
+}
+
+impl Agreement<'_> {
+    pub fn agreed_on(&self, header: &Header) -> bool {
+        match *self {
+            Agreement::Votes {
+                chain_id,
+                quorum,
+                validators,
+            } => consensus::certifies(
+                quorum,
+                validators,
+                header.height,
+                &header.hash(),
+                header
+                    .votes
+                    .iter()
+                    .filter_map(|vote| vote::decode_relayed(chain_id, vote)),
+            ),
+            Agreement::Work { difficulty } => {
+                pow::meets(&pow::header_hash(&header.hash(), header.nonce), difficulty)
+            }
+        }
+    }
+}
+
+/// The chain as a light client knows it, every header from the genesis
+/// block's on.
+pub struct HeaderChain {
+    headers: Vec<Header>,
+}
+
+impl HeaderChain {
+    pub fn new() -> Self {
+        HeaderChain {
+            headers: vec![Header::from(&Block::genesis())],
+        }
+    }
+
+    pub fn tip(&self) -> Tip {
+        let last = self
+            .headers
+            .last()
+            .expect("the genesis header is never removed");
+        Tip {
+            height: last.height,
+            hash: last.hash(),
+            timestamp: last.timestamp,
+        }
+    }
+
+    pub fn header(&self, height: u32) -> Option<&Header> {
+        self.headers.get(height as usize)
+    }
+
+    /// Take `header` as the next one, if it follows the tip, is timed after
+    /// it, and was agreed on as `agreement` has it.
+    pub fn append(&mut self, header: Header, agreement: &Agreement) -> Result<(), InvalidHeader> {
+        let tip = self.tip();
+        if header.height != tip.height + 1 || header.prev_hash != tip.hash {
+            return Err(InvalidHeader::NotOnTip);
+        }
+        if header.timestamp <= tip.timestamp {
+            return Err(InvalidHeader::BadTimestamp(header.timestamp));
+        }
+        if !agreement.agreed_on(&header) {
+            return Err(InvalidHeader::NotAgreed);
+        }
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Whether `proof` shows the transaction `id` in the block at `height`.
+    pub fn includes(&self, height: u32, id: &TransactionId, proof: &InclusionProof) -> bool {
+        self.header(height)
+            .is_some_and(|header| proof.verify(id.as_bytes(), &header.merkle_root))
+    }
+}
+
+impl Default for HeaderChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Proof that the transaction `id` is in `block`, for a light client that
+/// only has its header.
+pub fn prove(block: &Block, id: &TransactionId) -> Option<InclusionProof> {
+    let leaves: Vec<_> = block.transaction_ids().map(|id| *id.as_bytes()).collect();
+    let index = leaves.iter().position(|leaf| leaf == id.as_bytes())?;
+    InclusionProof::new(&leaves, index)
+}
+
+#[derive(Debug)]
+pub enum InvalidHeader {
+    NotOnTip,
+    BadTimestamp(u64),
+    NotAgreed,
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidHeader::NotOnTip => write!(f, "the header doesn't follow our tip"),
+            InvalidHeader::BadTimestamp(timestamp) => {
+                write!(f, "timestamp {timestamp} isn't after the tip's")
+            }
+            InvalidHeader::NotAgreed => write!(f, "the block wasn't agreed on"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+    use futures::executor::block_on;
+    use libp2p::identity::{self, ed25519::Keypair};
+
+    fn voted(mut block: Block, voters: &[Keypair]) -> Block {
+        block.votes = voters
+            .iter()
+            .map(|voter| vote::encode("test", block.height, 0, &block.hash, voter))
+            .collect();
+        block
+    }
+
+    #[test]
+    fn takes_headers_that_follow_the_tip_and_were_agreed_on() {
+        let keys = [Keypair::generate(), Keypair::generate()];
+        let validators: BTreeSet<_> = keys
+            .iter()
+            .map(|key| PeerId::from(identity::PublicKey::from(key.public())))
+            .collect();
+        let agreement = Agreement::Votes {
+            chain_id: "test",
+            quorum: Quorum::ALL,
+            validators: &validators,
+        };
+        let mut chain = HeaderChain::new();
+        let first = voted(Block::new(1, 60, chain.tip().hash, Vec::new()), &keys);
+
+        let unvoted = Block::new(1, 60, chain.tip().hash, Vec::new());
+        assert!(matches!(
+            chain.append(Header::from(&unvoted), &agreement),
+            Err(InvalidHeader::NotAgreed)
+        ));
+        let short = voted(Block::new(1, 60, chain.tip().hash, Vec::new()), &keys[..1]);
+        assert!(matches!(
+            chain.append(Header::from(&short), &agreement),
+            Err(InvalidHeader::NotAgreed)
+        ));
+        let mut moved = Header::from(&first);
+        moved.merkle_root = [1; 32];
+        assert!(
+            matches!(
+                chain.append(moved, &agreement),
+                Err(InvalidHeader::NotAgreed)
+            ),
+            "the votes are for the hash recomputed from the header"
+        );
+        chain.append(Header::from(&first), &agreement).unwrap();
+        assert_eq!(chain.tip().hash, first.hash);
+
+        assert!(matches!(
+            chain.append(Header::from(&first), &agreement),
+            Err(InvalidHeader::NotOnTip)
+        ));
+        let early = voted(Block::new(2, 60, first.hash, Vec::new()), &keys);
+        assert!(matches!(
+            chain.append(Header::from(&early), &agreement),
+            Err(InvalidHeader::BadTimestamp(60))
+        ));
+        let mined = Block::new(2, 61, first.hash, Vec::new());
+        assert!(chain
+            .append(Header::from(&mined), &Agreement::Work { difficulty: 0 })
+            .is_ok());
+    }
+
+    #[test]
+    fn proves_transactions_against_the_header() {
+        let keypair = Keypair::generate();
+        let transactions: Vec<_> = (1..=3)
+            .map(|nonce| block_on(Transaction::sign(&keypair, "test", b"hello", nonce)).unwrap())
+            .collect();
+        let ids: Vec<_> = transactions.iter().map(Transaction::id).collect();
+        let block = Block::new(1, 60, Block::genesis().hash, transactions);
+        let mut chain = HeaderChain::new();
+        chain
+            .append(Header::from(&block), &Agreement::Work { difficulty: 0 })
+            .unwrap();
+
+        for id in &ids {
+            let proof = prove(&block, id).unwrap();
+            assert!(chain.includes(1, id, &proof));
+            assert!(!chain.includes(0, id, &proof));
+            assert!(!chain.includes(2, id, &proof), "no header to check against");
+        }
+        let proof = prove(&block, &ids[0]).unwrap();
+        assert!(!chain.includes(1, &ids[1], &proof));
+        assert!(prove(&Block::genesis(), &ids[0]).is_none());
+    }
+}
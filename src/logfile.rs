@@ -0,0 +1,154 @@
+use clap::ValueEnum;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::error::lock;
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "node.log";
+// Rotated files kept next to the current one, as node.log.1 (newest) up to node.log.5.
+const KEEP_ROTATED: usize = 5;
+
+/// How often the log file is started afresh, regardless of its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl Rotation {
+    fn period(self) -> Option<Duration> {
+        match self {
+            Rotation::Hourly => Some(Duration::from_secs(60 * 60)),
+            Rotation::Daily => Some(Duration::from_secs(24 * 60 * 60)),
+            Rotation::Never => None,
+        }
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    opened_at: Instant,
+    max_bytes: u64,
+    rotation: Rotation,
+}
+
+impl RotatingFile {
+    fn needs_rotation(&self) -> bool {
+        self.written >= self.max_bytes
+            || self
+                .rotation
+                .period()
+                .is_some_and(|period| self.opened_at.elapsed() >= period)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..KEEP_ROTATED).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.path, index + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{index}"));
+    PathBuf::from(rotated)
+}
+
+/// Log output to `logs/node.log` in the data dir, rotated once it grows past
+/// a size limit or gets older than its [`Rotation`] period.
+#[derive(Clone)]
+pub struct LogFile(Arc<Mutex<RotatingFile>>);
+
+impl LogFile {
+    pub fn open(data_dir: &Path, max_bytes: u64, rotation: Rotation) -> io::Result<Self> {
+        let dir = data_dir.join(LOG_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(LOG_FILE);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(LogFile(Arc::new(Mutex::new(RotatingFile {
+            path,
+            file,
+            written,
+            opened_at: Instant::now(),
+            max_bytes,
+            rotation,
+        }))))
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file = lock(&self.0);
+        if file.needs_rotation() {
+            file.rotate()?;
+        }
+
+        let written = file.file.write(buf)?;
+        file.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        lock(&self.0).file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = LogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_once_past_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = LogFile::open(dir.path(), 10, Rotation::Never).unwrap();
+        let path = dir.path().join(LOG_DIR).join(LOG_FILE);
+
+        log.write_all(b"0123456789").unwrap();
+        assert!(!rotated_path(&path, 1).exists());
+        log.write_all(b"next").unwrap();
+        assert_eq!(fs::read(rotated_path(&path, 1)).unwrap(), b"0123456789");
+        assert_eq!(fs::read(&path).unwrap(), b"next");
+    }
+
+    #[test]
+    fn keeps_a_limited_number_of_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = LogFile::open(dir.path(), 1, Rotation::Never).unwrap();
+        let path = dir.path().join(LOG_DIR).join(LOG_FILE);
+
+        for line in 0..=KEEP_ROTATED + 2 {
+            log.write_all(line.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read(rotated_path(&path, 1)).unwrap(), b"6");
+        assert_eq!(fs::read(rotated_path(&path, KEEP_ROTATED)).unwrap(), b"2");
+        assert!(!rotated_path(&path, KEEP_ROTATED + 1).exists());
+    }
+}
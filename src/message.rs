@@ -0,0 +1,50 @@
+use libp2p::identity::ed25519::PublicKey;
+
+use crate::signer::{Signer, SignerError};
+
+/// Prefix of every signed message. Transaction signatures cover a prefix of
+/// their own, so a message signature can never be replayed as a transaction.
+pub const MESSAGE_CONTEXT: &[u8] = b"educoin/message/v1\n";
+
+/// Sign an arbitrary message, e.g. to prove control of an address to the instructor.
+pub async fn sign(signer: &dyn Signer, message: &[u8]) -> Result<Vec<u8>, SignerError> {
+    signer.sign(&signing_payload(message)).await
+}
+
+pub fn verify(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+    public_key.verify(&signing_payload(message), signature)
+}
+
+fn signing_payload(message: &[u8]) -> Vec<u8> {
+    [MESSAGE_CONTEXT, message].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+    use bytes::Bytes;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn message_and_transaction_signatures_dont_cross() {
+        let keypair = Keypair::generate();
+        let data = b"send edu1recipient 5";
+
+        let signature = block_on(sign(&keypair, data)).unwrap();
+        assert!(verify(&keypair.public(), data, &signature));
+        let as_transaction = Transaction {
+            public_key: keypair.public(),
+            signature: Bytes::from(signature),
+            data: Bytes::from_static(data),
+            fee: 0,
+            nonce: 0,
+        };
+        assert!(!as_transaction.verify("test"));
+
+        let transaction = block_on(Transaction::sign(&keypair, "test", data, 1)).unwrap();
+        assert!(transaction.verify("test"));
+        assert!(!verify(&keypair.public(), data, &transaction.signature));
+    }
+}
@@ -0,0 +1,133 @@
+use libp2p::gossipsub::TopicHash;
+use libp2p::metrics::{Metrics as Libp2pMetrics, Recorder};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TopicLabels {
+    topic: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RejectionLabels {
+    topic: String,
+    reason: String,
+}
+
+/// Node metrics, combining the libp2p swarm/gossipsub metrics with our own
+/// counters in a single Prometheus registry.
+pub struct Metrics {
+    registry: Registry,
+    libp2p: Libp2pMetrics,
+    messages_received: Family<TopicLabels, Counter>,
+    messages_rejected: Family<RejectionLabels, Counter>,
+    messages_dropped: Family<TopicLabels, Counter>,
+    publish_failures: Family<TopicLabels, Counter>,
+    pending_blocks: Gauge,
+}
+
+impl Metrics {
+    /// Take over `registry`, which may already hold metrics registered by
+    /// behaviours (e.g. gossipsub mesh peers per topic), and add ours to it.
+    pub fn new(mut registry: Registry) -> Self {
+        let libp2p = Libp2pMetrics::new(&mut registry);
+
+        let educoin = registry.sub_registry_with_prefix("educoin");
+        let messages_received = Family::default();
+        educoin.register(
+            "messages_received",
+            "Gossip messages received per topic",
+            messages_received.clone(),
+        );
+        let messages_rejected = Family::default();
+        educoin.register(
+            "messages_rejected",
+            "Gossip messages rejected per topic and reason",
+            messages_rejected.clone(),
+        );
+        let messages_dropped = Family::default();
+        educoin.register(
+            "messages_dropped",
+            "Gossip messages dropped per topic because the node couldn't keep up",
+            messages_dropped.clone(),
+        );
+        let publish_failures = Family::default();
+        educoin.register(
+            "publish_failures",
+            "Failed attempts to publish to a topic",
+            publish_failures.clone(),
+        );
+        let pending_blocks = Gauge::default();
+        educoin.register(
+            "storage_pending_blocks",
+            "Committed blocks waiting to be written to disk",
+            pending_blocks.clone(),
+        );
+
+        Metrics {
+            registry,
+            libp2p,
+            messages_received,
+            messages_rejected,
+            messages_dropped,
+            publish_failures,
+            pending_blocks,
+        }
+    }
+
+    /// Feed a swarm or protocol event to the libp2p metrics.
+    pub fn record<E>(&self, event: &E)
+    where
+        Libp2pMetrics: Recorder<E>,
+    {
+        self.libp2p.record(event);
+    }
+
+    pub fn message_received(&self, topic: &TopicHash) {
+        self.messages_received
+            .get_or_create(&TopicLabels {
+                topic: topic.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn message_rejected(&self, topic: &TopicHash, reason: &str) {
+        self.messages_rejected
+            .get_or_create(&RejectionLabels {
+                topic: topic.to_string(),
+                reason: reason.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn message_dropped(&self, topic: &TopicHash) {
+        self.messages_dropped
+            .get_or_create(&TopicLabels {
+                topic: topic.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn publish_failed(&self, topic: &TopicHash) {
+        self.publish_failures
+            .get_or_create(&TopicLabels {
+                topic: topic.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn set_pending_blocks(&self, pending: usize) {
+        self.pending_blocks.set(pending as i64);
+    }
+
+    /// Render every metric in the OpenMetrics text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("writing to a String never fails");
+        buffer
+    }
+}
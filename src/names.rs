@@ -0,0 +1,120 @@
+//! A name registry, the example of an application built on the chain: names
+//! are registered and handed on by transactions, and every node derives who
+//! owns what from the committed blocks, the same way it derives balances.
+//!
+//! - `register <name>` claims a name nobody owns yet for the sender
+//! - `transfer-name <name> <address>` hands a name the sender owns to another address
+//!
+//! Names are 3 to 32 lowercase letters, digits and dashes. In transaction data
+//! typed into the node, `@<name>` stands for the address owning the name.
+
+use std::collections::BTreeMap;
+
+use crate::address::Address;
+use crate::transaction::Transaction;
+
+const NAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+
+#[derive(Debug, PartialEq, Eq)]
+enum NameChange<'a> {
+    Register(&'a str),
+    Transfer(&'a str, Address),
+}
+
+impl<'a> NameChange<'a> {
+    fn parse(data: &'a [u8]) -> Option<NameChange<'a>> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.split_whitespace();
+
+        let change = match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("register"), Some(name), None, None) => NameChange::Register(name),
+            (Some("transfer-name"), Some(name), Some(owner), None) => {
+                NameChange::Transfer(name, owner.parse().ok()?)
+            }
+            _ => return None,
+        };
+        let (NameChange::Register(name) | NameChange::Transfer(name, _)) = change;
+        is_valid(name).then_some(change)
+    }
+}
+
+pub fn is_valid(name: &str) -> bool {
+    NAME_LENGTH.contains(&name.len())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+}
+
+/// Owner of every registered name.
+#[derive(Default)]
+pub struct NameRegistry {
+    owners: BTreeMap<String, Address>,
+}
+
+impl NameRegistry {
+    pub fn owner(&self, name: &str) -> Option<Address> {
+        self.owners.get(name).copied()
+    }
+
+    pub fn names_of<'a>(&'a self, owner: &'a Address) -> impl Iterator<Item = &'a str> {
+        self.owners
+            .iter()
+            .filter(move |(_, address)| *address == owner)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Apply the registrations and transfers in a committed block, in order.
+    /// Registrations of taken names and transfers by anyone but the owner are
+    /// ignored.
+    pub fn apply(&mut self, transactions: &[Transaction]) {
+        for transaction in transactions {
+            let sender = Address::from(&transaction.public_key);
+
+            match NameChange::parse(&transaction.data) {
+                Some(NameChange::Register(name)) => {
+                    self.owners.entry(name.to_string()).or_insert(sender);
+                }
+                Some(NameChange::Transfer(name, owner)) => {
+                    if let Some(current) = self.owners.get_mut(name) {
+                        if *current == sender {
+                            *current = owner;
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    fn sign(keypair: &Keypair, data: &str) -> Transaction {
+        block_on(Transaction::sign(keypair, "test", data.as_bytes(), 1)).unwrap()
+    }
+
+    #[test]
+    fn names_go_to_the_first_to_register_them() {
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let (first, second) = (Address::from(&alice.public()), Address::from(&bob.public()));
+        let mut names = NameRegistry::default();
+
+        names.apply(&[
+            sign(&alice, "register workshop"),
+            sign(&bob, "register workshop"),
+            sign(&bob, "register No_Caps"),
+            sign(&bob, &format!("transfer-name workshop {second}")),
+        ]);
+        assert_eq!(names.owner("workshop"), Some(first));
+        assert_eq!(names.owner("No_Caps"), None);
+
+        names.apply(&[sign(&alice, &format!("transfer-name workshop {second}"))]);
+        assert_eq!(names.owner("workshop"), Some(second));
+        assert_eq!(names.names_of(&second).collect::<Vec<_>>(), ["workshop"]);
+        assert_eq!(names.names_of(&first).count(), 0);
+    }
+}
@@ -0,0 +1,228 @@
+//! A bridge to a NATS server, for streaming-pipeline exercises: committed
+//! blocks are published on `<prefix>.blocks` and accepted transactions on
+//! `<prefix>.transactions`, both as the JSON `/export --format json` uses,
+//! and pre-signed transactions published on `<prefix>.submit` are submitted
+//! as if pasted with `/submit`. A relayer also follows the blocks of another
+//! chain on the same server, see [`crate::bridge`].
+//!
+//! Only the handful of verbs of the core NATS text protocol the bridge needs
+//! are spoken, over a plain TCP connection without TLS or auth.
+
+use futures::channel::mpsc::UnboundedSender;
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use tracing::{info, warn};
+
+use crate::bridge;
+use crate::export;
+use crate::transaction::Transaction;
+
+// Subscription ids of `<prefix>.submit` and of the relayed chain's blocks.
+const SUBMIT_SID: &str = "1";
+const RELAY_SID: &str = "2";
+
+/// Withdrawals to relay from the chain whose node bridges to NATS under
+/// `source_prefix` to this chain, `chain_id`.
+pub struct Relay {
+    pub source_prefix: String,
+    pub chain_id: String,
+}
+
+pub struct Bridge {
+    prefix: String,
+    // frames for the writer thread, already in wire format
+    frames: std_mpsc::Sender<Vec<u8>>,
+}
+
+impl Bridge {
+    /// Connect to the NATS server at `address` (`host:port`), sending the
+    /// `/submit` line of every transaction ingested, and the `mint` line of
+    /// every withdrawal relayed, to `submitted`.
+    pub fn connect(
+        address: &str,
+        prefix: &str,
+        relay: Option<Relay>,
+        submitted: UnboundedSender<String>,
+    ) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        // the server introduces itself before anything else
+        let mut info = String::new();
+        reader.read_line(&mut info)?;
+        if !info.starts_with("INFO ") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{address} is not a NATS server"),
+            ));
+        }
+
+        let (frames, queue) = std_mpsc::channel::<Vec<u8>>();
+        let mut connect = format!(
+            "CONNECT {{\"verbose\":false,\"pedantic\":false,\"name\":\"educoin\"}}\r\nSUB {prefix}.submit {SUBMIT_SID}\r\n"
+        );
+        if let Some(relay) = &relay {
+            connect += &format!("SUB {}.blocks {RELAY_SID}\r\n", relay.source_prefix);
+        }
+        let _ = frames.send(connect.into_bytes());
+
+        let mut writer = stream;
+        thread::Builder::new()
+            .name("nats-writer".to_string())
+            .spawn(move || {
+                for frame in queue {
+                    if let Err(e) = writer.write_all(&frame) {
+                        warn!(error = %e, "lost the connection to NATS");
+                        return;
+                    }
+                }
+            })?;
+        let pongs = frames.clone();
+        thread::Builder::new()
+            .name("nats-reader".to_string())
+            .spawn(move || {
+                if let Err(e) = read(reader, &pongs, relay.as_ref(), &submitted) {
+                    warn!(error = %e, "lost the connection to NATS");
+                }
+            })?;
+
+        info!(%address, %prefix, "bridging to NATS");
+        Ok(Bridge {
+            prefix: prefix.to_string(),
+            frames,
+        })
+    }
+
+    pub fn block_committed(&self, height: u32, transactions: &[Transaction]) {
+        self.publish("blocks", &export::Block::new(height, transactions));
+    }
+
+    pub fn transaction_accepted(&self, transaction: &Transaction) {
+        self.publish("transactions", &export::Transaction::new(transaction));
+    }
+
+    fn publish(&self, subject: &str, payload: &impl Serialize) {
+        let payload = match serde_json::to_vec(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!(error = %e, "failed to encode NATS message");
+                return;
+            }
+        };
+        let mut frame = format!("PUB {}.{subject} {}\r\n", self.prefix, payload.len()).into_bytes();
+        frame.extend(payload);
+        frame.extend(b"\r\n");
+        // a gone writer has already logged why
+        let _ = self.frames.send(frame);
+    }
+}
+
+fn read(
+    mut reader: impl BufRead,
+    frames: &std_mpsc::Sender<Vec<u8>>,
+    relay: Option<&Relay>,
+    submitted: &UnboundedSender<String>,
+) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut words = line.split_whitespace();
+        match words.next() {
+            // MSG <subject> <sid> [reply-to] <length>
+            Some("MSG") => {
+                let sid = words.nth(1).map(str::to_string);
+                let length: usize = words
+                    .last()
+                    .and_then(|length| length.parse().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, line.clone()))?;
+                let mut payload = vec![0; length + 2];
+                reader.read_exact(&mut payload)?;
+                payload.truncate(length);
+
+                if let Some(relay) = relay.filter(|_| sid.as_deref() == Some(RELAY_SID)) {
+                    match serde_json::from_slice::<export::Block>(&payload) {
+                        Ok(block) => {
+                            for mint in bridge::mints(&block, &relay.chain_id) {
+                                info!(height = block.height, "relaying a withdrawal");
+                                let _ = submitted.unbounded_send(mint);
+                            }
+                        }
+                        Err(e) => warn!(error = %e, "ignoring relayed block that isn't one"),
+                    }
+                    continue;
+                }
+                match submit_line(&payload) {
+                    Some(line) => {
+                        let _ = submitted.unbounded_send(line);
+                    }
+                    None => warn!("ignoring NATS message that isn't a pre-signed transaction"),
+                }
+            }
+            Some("PING") => {
+                let _ = frames.send(b"PONG\r\n".to_vec());
+            }
+            Some("-ERR") => warn!(error = line.trim(), "NATS server reported an error"),
+            _ => {}
+        }
+    }
+}
+
+// A submitted message holds a transaction blob and optionally its delegation, in hex as
+// `sign-tx` prints them, and nothing else that could pass for a command.
+fn submit_line(payload: &[u8]) -> Option<String> {
+    let payload = std::str::from_utf8(payload).ok()?;
+    let parts: Vec<_> = payload.split_whitespace().collect();
+    let is_hex = |part: &&str| part.bytes().all(|byte| byte.is_ascii_hexdigit());
+    (matches!(parts.len(), 1 | 2) && parts.iter().all(is_hex))
+        .then(|| format!("/submit {}", parts.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use std::net::TcpListener;
+
+    #[test]
+    fn publishes_and_ingests_over_nats() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (submitted, mut ingested) = mpsc::unbounded();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"INFO {}\r\n").unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut read_line = || {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                line
+            };
+            assert!(read_line().starts_with("CONNECT "));
+            assert_eq!(read_line(), "SUB educoin.submit 1\r\n");
+
+            stream
+                .write_all(b"PING\r\nMSG educoin.submit 1 9\r\n0a01 ff02\r\nMSG educoin.submit 1 7\r\n/faucet\r\n")
+                .unwrap();
+            assert_eq!(read_line(), "PONG\r\n");
+            let publish = read_line();
+            let payload = read_line();
+            (publish, payload)
+        });
+
+        let bridge = Bridge::connect(&address, "educoin", None, submitted).unwrap();
+        let line = futures::executor::block_on(ingested.next()).unwrap();
+        assert_eq!(line, "/submit 0a01 ff02", "commands are not ingested");
+
+        bridge.block_committed(7, &[]);
+        let (publish, payload) = server.join().unwrap();
+        let payload = payload.trim_end();
+        assert_eq!(publish, format!("PUB educoin.blocks {}\r\n", payload.len()));
+        let block: serde_json::Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(block["height"], 7);
+    }
+}
@@ -0,0 +1,151 @@
+//! The libp2p side of a node: gossipsub for every topic, block sync with
+//! single peers, and optionally mDNS to find peers on the local network.
+
+use futures::prelude::*;
+use libp2p::{
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{Boxed, ListenerId},
+    },
+    gossipsub::{self, MessageAcceptance},
+    identity, mdns,
+    multiaddr::Protocol,
+    swarm::behaviour::toggle::Toggle,
+    swarm::NetworkBehaviour,
+    swarm::SwarmBuilder,
+    Multiaddr, PeerId, Swarm,
+};
+use prometheus_client::registry::Registry;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::sync;
+
+// We create a custom network behaviour that combines Gossipsub, block sync and Mdns.
+#[derive(NetworkBehaviour)]
+pub struct EduCoinBehaviour {
+    pub gossipsub: gossipsub::Behaviour,
+    pub block_sync: sync::Behaviour,
+    pub mdns: Toggle<mdns::async_io::Behaviour>,
+}
+
+/// The gossipsub topic `name`, under `prefix` unless that is empty.
+pub fn topic(prefix: &str, name: &str) -> gossipsub::IdentTopic {
+    if prefix.is_empty() {
+        gossipsub::IdentTopic::new(name)
+    } else {
+        gossipsub::IdentTopic::new(format!("{prefix}/{name}"))
+    }
+}
+
+/// The peer `address` dials, when it ends in `/p2p/<peer id>`.
+pub fn peer_id(address: &Multiaddr) -> Option<PeerId> {
+    match address.iter().last()? {
+        Protocol::P2p(multihash) => PeerId::from_multihash(multihash).ok(),
+        _ => None,
+    }
+}
+
+/// Gossipsub signing with `id_keys`, recording mesh and topic metrics into
+/// `registry`. Messages are only relayed once the node [`validate`]s them.
+pub fn gossipsub(
+    id_keys: identity::Keypair,
+    registry: &mut Registry,
+) -> Result<gossipsub::Behaviour, Box<dyn Error>> {
+    // Message ids are a hash of the topic and the payload only, so every node derives the same
+    // id for a message and gossipsub's duplicate cache works across the network. Transactions
+    // carry their own key and signature in the payload, everything else is signed by gossipsub.
+    let message_id_fn = |message: &gossipsub::Message| {
+        let hash = Sha256::new()
+            .chain_update(message.topic.as_str())
+            .chain_update([0])
+            .chain_update(&message.data)
+            .finalize();
+        gossipsub::MessageId::from(hash.to_vec())
+    };
+
+    // Set a custom gossipsub configuration
+    let gossipsub_config = gossipsub::ConfigBuilder::default()
+        .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
+        .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
+        .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
+        .validate_messages() // only relay messages once we've decoded them, see `validate`
+        .build()?;
+
+    Ok(gossipsub::Behaviour::new_with_metrics(
+        gossipsub::MessageAuthenticity::Signed(id_keys),
+        gossipsub_config,
+        registry.sub_registry_with_prefix("gossipsub"),
+        gossipsub::MetricsConfig::default(),
+    )?)
+}
+
+/// A swarm running `gossipsub` and `block_sync` over `transport`, discovering
+/// peers with mDNS when `mdns` is set.
+pub fn swarm(
+    transport: Boxed<(PeerId, StreamMuxerBox)>,
+    gossipsub: gossipsub::Behaviour,
+    block_sync: sync::Behaviour,
+    mdns: bool,
+    local_peer_id: PeerId,
+) -> Result<Swarm<EduCoinBehaviour>, Box<dyn Error>> {
+    let mdns = mdns
+        .then(|| mdns::async_io::Behaviour::new(mdns::Config::default(), local_peer_id))
+        .transpose()?
+        .into();
+    let behaviour = EduCoinBehaviour {
+        gossipsub,
+        block_sync,
+        mdns,
+    };
+    Ok(SwarmBuilder::with_async_std_executor(transport, behaviour, local_peer_id).build())
+}
+
+/// Unsubscribe from `topics`, stop listening and hang up on every peer.
+// Unsubscribing lets peers know we're gone instead of finding out when gossip to us fails.
+// The swarm has to run for that to go out, so give it `grace` before hanging up.
+pub async fn leave(
+    swarm: &mut Swarm<EduCoinBehaviour>,
+    topics: &[&gossipsub::IdentTopic],
+    listener: ListenerId,
+    grace: Duration,
+) {
+    for topic in topics {
+        if let Err(e) = swarm.behaviour_mut().gossipsub.unsubscribe(topic) {
+            warn!(%topic, error = ?e, "failed to unsubscribe");
+        }
+    }
+    swarm.remove_listener(listener);
+
+    let drive = async {
+        loop {
+            swarm.select_next_some().await;
+        }
+    };
+    let _ = async_std::future::timeout(grace, drive).await;
+
+    let peers: Vec<PeerId> = swarm.connected_peers().copied().collect();
+    for peer_id in peers {
+        let _ = swarm.disconnect_peer_id(peer_id);
+    }
+}
+
+/// Tell gossipsub whether to relay a message. With `validate_messages` nothing is
+/// forwarded to other peers before this.
+pub fn validate(
+    swarm: &mut Swarm<EduCoinBehaviour>,
+    id: &gossipsub::MessageId,
+    propagation_source: &PeerId,
+    acceptance: MessageAcceptance,
+) {
+    // replayed and delayed messages may have left gossipsub's cache by now, that's fine
+    if let Err(e) = swarm
+        .behaviour_mut()
+        .gossipsub
+        .report_message_validation_result(id, propagation_source, acceptance)
+    {
+        debug!(%id, error = ?e, "failed to report message validation");
+    }
+}
@@ -0,0 +1,959 @@
+use async_std::task::JoinHandle;
+use crossterm::event::EventStream;
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, FuturesUnordered};
+use futures::{prelude::*, select, stream};
+use libp2p::{
+    core::{muxing::StreamMuxerBox, transport::Boxed},
+    gossipsub::{self, MessageAcceptance},
+    identity, mdns,
+    swarm::{SwarmEvent, THandlerErr},
+    Multiaddr, PeerId, Swarm,
+};
+use prometheus_client::registry::Registry;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Span};
+
+use crate::address::{Address, AddressBook};
+use crate::alerts::{Alert, AlertKind, Alerts};
+use crate::assembler::{check_sealed, BlockAssembler, BlockRules};
+use crate::audit::{AuditEvent, AuditLog};
+use crate::block::{Block, Chain};
+use crate::cli::Cli;
+use crate::consensus::{Consensus, Mode};
+use crate::dashboard::{self, BlockSummary, Dashboard, DashboardState, LogBuffer};
+use crate::faucet::{Faucet, FAUCET_ACCOUNT};
+use crate::faults::{Faults, Verdict};
+use crate::genesis::Genesis;
+#[cfg(any(test, feature = "test-utils"))]
+use crate::hooks::Hook;
+use crate::lifecycle::Lifecycle;
+#[cfg(any(test, feature = "test-utils"))]
+use crate::lifecycle::Milestone;
+use crate::metrics::Metrics;
+use crate::nats;
+use crate::network::{self, EduCoinBehaviour, EduCoinBehaviourEvent};
+use crate::penalties::Penalties;
+use crate::pow::{Miner, Retarget};
+use crate::recording::{self, Recorder};
+use crate::rpc::{self, Call};
+use crate::seen::SeenCache;
+use crate::snapshot;
+use crate::sql_mirror::SqlMirror;
+use crate::staking::ValidatorSet;
+use crate::state::ChainState;
+use crate::storage::BlockStore;
+use crate::sync;
+use crate::transaction::{Transaction, TransactionId};
+use crate::verifier::VerifierPool;
+use crate::wallet::{self, Wallet};
+use crate::watchdog::Watchdog;
+use crate::webhooks::Webhooks;
+
+mod catch_up;
+mod commands;
+mod mempool;
+mod voting;
+
+// How often the dashboard redraws when enabled.
+const DASHBOARD_REFRESH: Duration = Duration::from_millis(250);
+// Gossip message ids remembered across restarts, see `SeenCache`.
+const SEEN_CAPACITY: usize = 10_000;
+// How often the stall watchdog looks for progress.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+// How often the current round of voting is checked for having run out of time.
+const ROUND_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// How long shutting down waits for peers to hear about it, and again for blocks to be written.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+// Stored blocks read back and checked at a time when the node starts.
+const REPLAY_BATCH: usize = 1024;
+
+/// Things a node reports to whoever started it, see [`Node::events`].
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    BlockCommitted {
+        height: u32,
+        transactions: Vec<TransactionId>,
+    },
+    PeerSubscribed {
+        peer_id: PeerId,
+        topic: String,
+    },
+    Stalled {
+        idle: Duration,
+    },
+    PeerBlacklisted {
+        peer_id: PeerId,
+    },
+}
+
+/// Everything a node takes from the outside world besides its configuration,
+/// so the same node can run over TCP with a terminal attached or in-process
+/// over the memory transport.
+pub struct Environment {
+    pub id_keys: identity::Keypair,
+    pub transport: Boxed<(PeerId, StreamMuxerBox)>,
+    pub listen_on: Multiaddr,
+    /// Discover peers on the local network. Nodes that can't be discovered
+    /// this way (e.g. in-process ones) dial each other instead.
+    pub mdns: bool,
+    pub dial: Vec<Multiaddr>,
+    /// Lines entered by the user, transactions or `/` commands.
+    pub input: BoxStream<'static, io::Result<String>>,
+    /// Yields when the node should shut down, e.g. on SIGINT or SIGTERM.
+    pub shutdown: BoxStream<'static, ()>,
+    pub dashboard_logs: Option<LogBuffer>,
+    pub events: Option<UnboundedSender<NodeEvent>>,
+    pub faults: Option<Faults>,
+    #[cfg(any(test, feature = "test-utils"))]
+    pub hooks: Option<mpsc::UnboundedReceiver<Hook>>,
+}
+
+/// A node running on a task of its own, for embedding nodes in tests and in
+/// other binaries.
+///
+/// The node is driven like one with a terminal attached: lines handed to
+/// [`Node::submit`] are handled as if typed in, alongside whatever
+/// `input` it was started with.
+pub struct Node {
+    peer_id: PeerId,
+    input: UnboundedSender<io::Result<String>>,
+    shutdown: UnboundedSender<()>,
+    events: UnboundedReceiver<NodeEvent>,
+    task: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Node {
+    /// Start a node configured by `cli` in `env`. Its events go to
+    /// [`Node::events`], in place of any `env.events`.
+    pub fn start(cli: Cli, mut env: Environment) -> Node {
+        let peer_id = PeerId::from(env.id_keys.public());
+        let (input, submitted) = mpsc::unbounded();
+        let (shutdown, stop) = mpsc::unbounded();
+        let (events_tx, events) = mpsc::unbounded();
+        env.input = stream::select(env.input, submitted).boxed();
+        env.shutdown = stream::select(env.shutdown, stop).boxed();
+        env.events = Some(events_tx);
+
+        let task =
+            async_std::task::spawn(async move { run(&cli, env).await.map_err(|e| e.to_string()) });
+        Node {
+            peer_id,
+            input,
+            shutdown,
+            events,
+            task: Some(task),
+        }
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// Whether the node is still running, i.e. it wasn't shut down or killed.
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Hand the node a line as if it was typed in: a transaction to sign or a
+    /// `/` command.
+    pub fn submit(&self, line: &str) {
+        let _ = self.input.unbounded_send(Ok(line.to_string()));
+    }
+
+    /// Submit `transaction`, signed elsewhere, like `/submit` does.
+    pub fn submit_transaction(&self, transaction: &Transaction) {
+        self.submit(&format!("/submit {}", hex::encode(transaction.to_bytes())));
+    }
+
+    /// Everything the node reports, until it stops.
+    pub fn events(&mut self) -> &mut UnboundedReceiver<NodeEvent> {
+        &mut self.events
+    }
+
+    /// Ask the node to shut down, as on SIGTERM, and wait until it has.
+    pub async fn shut_down(&mut self) -> Result<(), String> {
+        let _ = self.shutdown.unbounded_send(());
+        match self.task.take() {
+            Some(task) => task.await,
+            None => Ok(()),
+        }
+    }
+
+    /// Stop the node right away, as if its process was killed.
+    pub async fn kill(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.cancel().await;
+        }
+    }
+}
+/// Run a node until the dashboard is quit or it is told to shut down.
+///
+/// Either way it saves the round and mempool for the next start, see
+/// [`snapshot`], leaves the network and waits a moment for blocks still
+/// being written.
+pub async fn run(cli: &Cli, env: Environment) -> Result<(), Box<dyn Error>> {
+    let Environment {
+        id_keys,
+        transport,
+        listen_on,
+        mdns,
+        dial,
+        input,
+        shutdown,
+        dashboard_logs,
+        events,
+        faults,
+        #[cfg(any(test, feature = "test-utils"))]
+        hooks,
+    } = env;
+    // the wallet owns the identity key from here on, every node starts with a single account
+    // backed by it, more can be derived from stdin
+    let mut wallet = Wallet::from_keypair(id_keys.try_into_ed25519()?);
+    let local_peer_id = PeerId::from(identity::PublicKey::from(wallet.identity().public()));
+    info!(peer_id = %local_peer_id, "local peer id");
+    if let Some(strategy) = cli.byzantine {
+        warn!(?strategy, "running as a byzantine node");
+    }
+
+    // build a gossipsub network behaviour, recording mesh and topic metrics into the registry.
+    // It signs with the only other copy of the identity key, libp2p takes the keypair by value
+    let mut registry = Registry::default();
+    let identity_copy = identity::Keypair::from(wallet.identity().clone());
+    let mut gossipsub = network::gossipsub(identity_copy, &mut registry)?;
+    let topics = Topics {
+        // Create a Gossipsub topic over which we will send transactions
+        transactions: network::topic(&cli.topic_prefix, "transaction"),
+        // Create a topic over which the proposer publishes the next block
+        blocks: network::topic(&cli.topic_prefix, "blocks"),
+        // Create a topic over which validators vote for the proposed block
+        votes: network::topic(&cli.topic_prefix, "vote"),
+        // Create a topic over which participants ask the faucet for coins
+        faucet: network::topic(&cli.topic_prefix, "faucet"),
+    };
+    for topic in topics.all() {
+        gossipsub.subscribe(topic)?;
+    }
+
+    // Fetch blocks from peers when we fall behind, and serve theirs
+    let block_sync = sync::Behaviour::new(&cli.chain_id);
+
+    // Create a Swarm to manage peers and events
+    let mut swarm = network::swarm(transport, gossipsub, block_sync, mdns, local_peer_id)?;
+    let metrics = Metrics::new(registry);
+
+    // lines the node feeds itself and handles as if they were typed in: transfers of faucet
+    // coins and the transactions of a `/submit --file` batch
+    let (queued_lines, queued) = mpsc::unbounded();
+    let mut stdin = stream::select(input, queued.map(Ok)).fuse();
+    // transactions ingested from NATS, and mints of relayed withdrawals, are queued like pasted ones
+    let relay = cli.relay_from.clone().map(|source_prefix| nats::Relay {
+        source_prefix,
+        chain_id: cli.chain_id.clone(),
+    });
+    let bridge = cli
+        .nats
+        .as_deref()
+        .map(|address| {
+            nats::Bridge::connect(address, &cli.nats_prefix, relay, queued_lines.clone())
+        })
+        .transpose()?;
+    #[cfg(any(test, feature = "test-utils"))]
+    let mut hooks = hooks
+        .map_or_else(|| stream::pending().boxed(), |hooks| hooks.boxed())
+        .fuse();
+    #[cfg(not(any(test, feature = "test-utils")))]
+    let mut hooks = stream::pending::<std::convert::Infallible>().fuse();
+    // without `--rpc-listen` the sender is dropped, and no call ever comes
+    let (rpc_sender, rpc_calls) = mpsc::unbounded::<Call>();
+    let mut rpc_calls = rpc_calls.fuse();
+    if let Some(address) = cli.rpc_listen {
+        rpc::listen(address, rpc_sender)?;
+    }
+    let (verifier, verified) = VerifierPool::start(
+        thread::available_parallelism().map_or(1, usize::from),
+        cli.inbound_queue,
+        &cli.chain_id,
+    )?;
+    let mut verified = verified.fuse();
+    let (synced_blocks, synced) = mpsc::unbounded::<Block>();
+    let mut synced = synced.fuse();
+    let (mined_blocks, mined) = mpsc::unbounded::<Block>();
+    let mut mined = mined.fuse();
+    let delayed_messages = FuturesUnordered::new();
+    let recorder = cli.record.as_deref().map(Recorder::create).transpose()?;
+    let replayed = cli
+        .replay
+        .as_deref()
+        .map(recording::load)
+        .transpose()?
+        .unwrap_or_default();
+    if !replayed.is_empty() {
+        info!(messages = replayed.len(), "replaying recorded gossip");
+    }
+    for recorded in replayed {
+        delayed_messages.push(
+            async move {
+                async_std::task::sleep(recorded.offset).await;
+                (
+                    recorded.propagation_source,
+                    recorded.message_id,
+                    recorded.message,
+                )
+            }
+            .boxed(),
+        );
+    }
+
+    let listener = swarm.listen_on(listen_on)?;
+    for address in dial {
+        swarm.dial(address)?;
+    }
+    // bootstrap peers get our gossip whether or not they made it into the mesh
+    for address in &cli.bootstrap_peers {
+        if let Some(peer_id) = network::peer_id(address) {
+            swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+        }
+        if let Err(e) = swarm.dial(address.clone()) {
+            warn!(%address, error = %e, "failed to dial bootstrap peer");
+        }
+    }
+
+    let mut dashboard = dashboard_logs.map(Dashboard::start).transpose()?;
+    let (redraws, terminal_events) = if dashboard.is_some() {
+        (
+            async_std::stream::interval(DASHBOARD_REFRESH).boxed(),
+            EventStream::new().boxed(),
+        )
+    } else {
+        println!(
+            "Enter messages via STDIN and they will be sent to connected peers using Gossipsub"
+        );
+        (stream::pending().boxed(), stream::pending().boxed())
+    };
+    let (mut redraws, mut terminal_events) = (redraws.fuse(), terminal_events.fuse());
+
+    fs::create_dir_all(&cli.data_dir)?;
+    // sized for a full verifier queue, so a burst of gossip doesn't keep reallocating the mempool
+    let mut assembler = BlockAssembler::with_capacity(cli.inbound_queue);
+    let (block_store, block_acks) = BlockStore::open(&cli.data_dir)?;
+    let mut block_acks = block_acks.fuse();
+    let chain = Chain::resume(block_store.tip());
+    let mut retarget = Retarget::new(cli.difficulty, cli.retarget_interval, cli.target_block_time);
+    let mut consensus = match snapshot::take(&cli.data_dir)? {
+        Some(snapshot) => {
+            info!(
+                height = snapshot.consensus.height(),
+                mempool = snapshot.mempool.len(),
+                "resuming where the last shutdown left off"
+            );
+            for transaction in snapshot.mempool {
+                assembler.admit(transaction);
+            }
+            snapshot.consensus
+        }
+        None => Consensus::default(),
+    };
+    // the round always follows the stored chain, blocks still unwritten at shutdown were lost
+    if consensus.height() != chain.tip().height + 1 {
+        warn!(
+            height = consensus.height(),
+            stored = chain.tip().height,
+            "the round doesn't follow the stored chain, starting after its tip"
+        );
+        consensus = Consensus::resume(chain.tip().height + 1, 0, []);
+    }
+    let round_span = consensus_round_span(consensus.height(), consensus.round());
+    let mut round_checks = async_std::stream::interval(ROUND_CHECK_INTERVAL).fuse();
+    let mut shutdown = shutdown.fuse();
+    let address_book = AddressBook::open(&cli.data_dir)?;
+    let genesis = Genesis::load(cli.genesis.as_deref(), &cli.genesis_balances)?;
+    let sql_mirror = cli
+        .sql_mirror
+        .then(|| SqlMirror::create(&cli.data_dir, &genesis))
+        .transpose()?;
+    let mut state = ChainState::new(&genesis, cli.epoch_length, sql_mirror);
+    // applied the way they were when committed, nothing derived from the chain is stored, and
+    // checked again on the way in batches: hashes and signatures side by side on every core,
+    // the rest in order, against what the blocks before left
+    let (no_blacklist, no_validators) = (HashSet::new(), BTreeSet::new());
+    let mut replayed = Chain::new();
+    let heights: Vec<_> = (1..=chain.tip().height).collect();
+    for batch in heights.chunks(REPLAY_BATCH) {
+        let blocks = batch
+            .iter()
+            .filter_map(|&height| block_store.read_block(height).transpose())
+            .collect::<io::Result<Vec<_>>>()?;
+        if let Some((height, e)) = blocks.par_iter().find_map_first(|block| {
+            check_sealed(&cli.chain_id, block, &block.hash)
+                .err()
+                .map(|e| (block.height, e))
+        }) {
+            return Err(format!("stored block {height} doesn't check out, {e}").into());
+        }
+        for block in blocks {
+            // the blacklist and the clock are for new blocks, these were fine when committed
+            BlockRules {
+                chain_id: &cli.chain_id,
+                tip: replayed.tip(),
+                latest: u64::MAX,
+                block_size: assembler.block_size(),
+                blacklist: &no_blacklist,
+                validator_keys: &state.validator_keys,
+                ledger: &state.ledger,
+                validators: &no_validators,
+            }
+            .check_on_tip(&block, None)
+            .map_err(|e| format!("stored block {} doesn't check out, {e}", block.height))?;
+            replayed.append(&block)?;
+            // the difficulty of mined blocks follows the stored chain's timestamps
+            if cli.consensus == Mode::Pow {
+                retarget.record(block.height, block.timestamp);
+            }
+            state.apply(block, &mut assembler);
+        }
+    }
+    if chain.tip().height > 0 {
+        info!(blocks = chain.tip().height, "replayed the stored chain");
+    }
+    let mut watchdog_checks = async_std::stream::interval(WATCHDOG_INTERVAL).fuse();
+
+    let faucet = match &cli.faucet_key {
+        Some(key_file) => {
+            let keypair = wallet::load_key_file(key_file)?;
+            let address = Address::from(&keypair.public());
+            wallet.add_account(FAUCET_ACCOUNT, Box::new(keypair))?;
+            info!(%address, amount = cli.faucet_amount, "running a faucet");
+            Some(Faucet::new(address, cli.faucet_amount))
+        }
+        None => None,
+    };
+
+    let mut node = EventLoop {
+        cli,
+        local_peer_id,
+        swarm,
+        topics,
+        metrics,
+        events,
+        faults,
+        recorder,
+        delayed_messages,
+        seen: SeenCache::open(&cli.data_dir, SEEN_CAPACITY)?,
+        penalties: Penalties::new(cli.malformed_limit),
+        connected_peers: HashSet::new(),
+        wallet,
+        address_book,
+        delegations: HashMap::new(),
+        faucet,
+        queued_lines,
+        bridge,
+        verifier,
+        assembler,
+        lifecycle: Lifecycle::default(),
+        genesis,
+        state,
+        chain,
+        block_store,
+        recent_blocks: VecDeque::with_capacity(dashboard::RECENT_BLOCKS),
+        consensus,
+        proposals: BTreeMap::new(),
+        signed_votes: HashMap::new(),
+        checked_proposal: None,
+        round_span,
+        round_started: None,
+        force_block: false,
+        synced_blocks,
+        synced_block: None,
+        retarget,
+        mined_blocks,
+        mined_block: None,
+        miner: None,
+        alerts: Alerts::new(cli.alert_webhook.clone(), cli.alert_command.clone()),
+        webhooks: Webhooks::new(cli.block_webhooks.clone(), cli.webhook_transactions),
+        audit_log: AuditLog::open(&cli.data_dir)?,
+        watchdog: Watchdog::new(Duration::from_secs(cli.stall_timeout)),
+        blacklist: cli.blacklisted_addresses.iter().copied().collect(),
+    };
+
+    // Kick it off
+    let mut previous_number_of_peers = 0;
+    loop {
+        let number_of_peers = node.connected_peers.len();
+        if previous_number_of_peers > 0 && number_of_peers == 0 {
+            node.alerts.fire(Alert {
+                kind: AlertKind::QuorumLost,
+                height: node.consensus.height(),
+                detail: format!("all {previous_number_of_peers} peers are gone"),
+            });
+        }
+        previous_number_of_peers = number_of_peers;
+
+        let validators = node.validators();
+        node.commit(&validators);
+        node.propose(&validators);
+        node.vote(&validators);
+
+        select! {
+            _ = redraws.select_next_some() => {
+                if let Some(dashboard) = dashboard.as_mut() {
+                    let state = DashboardState {
+                        local_peer_id: node.local_peer_id,
+                        height: node.consensus.height(),
+                        recent_blocks: &node.recent_blocks,
+                        mempool: node.assembler
+                            .mempool()
+                            .map(|transaction| format!("{} {}", transaction.id(), String::from_utf8_lossy(&transaction.data)))
+                            .collect(),
+                        peers: node.connected_peers.iter().copied().collect(),
+                        votes: node.consensus.votes(),
+                    };
+                    if let Err(e) = dashboard.draw(&state) {
+                        warn!(error = %e, "failed to draw dashboard");
+                    }
+                }
+            },
+            block = mined.select_next_some() => node.on_mined(block),
+            block = synced.select_next_some() => node.on_synced(block, &validators),
+            ack = block_acks.select_next_some() => {
+                info_span!("storage").in_scope(|| node.block_store.acknowledge(ack));
+                node.metrics.set_pending_blocks(node.block_store.pending());
+            },
+            _ = watchdog_checks.select_next_some() => node.check_stall(),
+            _ = round_checks.select_next_some() => node.on_round_check(),
+            hook = hooks.select_next_some() => {
+                #[cfg(any(test, feature = "test-utils"))]
+                match hook {
+                    Hook::ProduceBlock => node.force_block = true,
+                    Hook::InjectTransaction(transaction) => {
+                        let tx_id = transaction.id();
+                        node.lifecycle.record(tx_id, Milestone::Received);
+                        node.lifecycle.record(tx_id, Milestone::Admitted);
+                        node.assembler.admit(transaction);
+                    }
+                    Hook::InjectVote { height, voter } => match node.proposals.get(&height) {
+                        Some((_, proposed)) => {
+                            node.consensus.record_vote(height, node.consensus.round(), voter, proposed.hash);
+                        }
+                        None => warn!(height, "no block proposed to inject a vote for"),
+                    },
+                    Hook::AdvanceClock(by) => node.watchdog.advance(by),
+                    Hook::PublishRaw { topic, data } => {
+                        if let Err(e) = node.swarm.behaviour_mut().gossipsub.publish(gossipsub::IdentTopic::new(topic), data) {
+                            warn!(error = ?e, "failed to publish raw message");
+                        }
+                    }
+                    Hook::Balance { address, reply } => {
+                        let _ = reply.send(node.state.ledger.balance(&address));
+                    }
+                }
+                #[cfg(not(any(test, feature = "test-utils")))]
+                match hook {}
+            },
+            call = rpc_calls.select_next_some() => node.on_rpc(call),
+            verified = verified.select_next_some() => node.on_verified(verified),
+            event = terminal_events.select_next_some() => {
+                if matches!(event, Ok(ref event) if dashboard::is_quit(event)) {
+                    break;
+                }
+            },
+            _ = shutdown.select_next_some() => {
+                info!("asked to shut down");
+                break;
+            },
+            line = stdin.select_next_some() => match line {
+                Ok(line) => node.on_line(line).await,
+                Err(e) => warn!(error = %e, "failed to read from stdin"),
+            },
+            event = async {
+                stream::select(
+                    (&mut node.swarm).map(|event| (event, true)),
+                    (&mut node.delayed_messages).map(|(propagation_source, message_id, message)| {
+                        let event = gossipsub::Event::Message { propagation_source, message_id, message };
+                        (SwarmEvent::Behaviour(EduCoinBehaviourEvent::Gossipsub(event)), false)
+                    }),
+                ).select_next_some().await
+            }.fuse() => {
+                let (event, fresh) = event;
+                node.on_swarm_event(event, fresh);
+            }
+        }
+    }
+
+    info!("shutting down");
+    let EventLoop {
+        mut swarm,
+        topics,
+        consensus,
+        assembler,
+        mut block_store,
+        ..
+    } = node;
+    if let Err(e) = snapshot::save(&cli.data_dir, &consensus, assembler.mempool()) {
+        error!(error = %e, "failed to save the round and mempool, they are lost");
+    }
+    network::leave(
+        &mut swarm,
+        &topics.all().collect::<Vec<_>>(),
+        listener,
+        SHUTDOWN_GRACE,
+    )
+    .await;
+
+    let flushed = async {
+        while block_store.pending() > 0 {
+            let Some(ack) = block_acks.next().await else {
+                break;
+            };
+            info_span!("storage").in_scope(|| block_store.acknowledge(ack));
+        }
+    };
+    if async_std::future::timeout(SHUTDOWN_GRACE, flushed)
+        .await
+        .is_err()
+    {
+        error!(
+            pending = block_store.pending(),
+            "stopping with blocks that could not be written"
+        );
+    }
+
+    // restore the terminal before exiting
+    drop(dashboard);
+    Ok(())
+}
+
+// The topics the node gossips over.
+struct Topics {
+    transactions: gossipsub::IdentTopic,
+    blocks: gossipsub::IdentTopic,
+    votes: gossipsub::IdentTopic,
+    faucet: gossipsub::IdentTopic,
+}
+
+impl Topics {
+    fn all(&self) -> impl Iterator<Item = &gossipsub::IdentTopic> {
+        [&self.transactions, &self.blocks, &self.votes, &self.faucet].into_iter()
+    }
+}
+
+// Everything the event loop of `run` keeps from one turn to the next. What it does with each
+// event lives with the rest of its kind: taking in transactions in `mempool`, proposing, voting
+// on and committing blocks in `voting`, fetching and serving blocks in `catch_up` and what's
+// typed in or asked over RPC in `commands`.
+struct EventLoop<'a> {
+    cli: &'a Cli,
+    local_peer_id: PeerId,
+    swarm: Swarm<EduCoinBehaviour>,
+    topics: Topics,
+    metrics: Metrics,
+    events: Option<UnboundedSender<NodeEvent>>,
+    faults: Option<Faults>,
+    recorder: Option<Recorder>,
+    // gossip held back by fault injection, or waiting to be replayed
+    delayed_messages: FuturesUnordered<BoxFuture<'static, DelayedMessage>>,
+    seen: SeenCache,
+    penalties: Penalties,
+    // kept up to date from connection events rather than recounted on every iteration
+    connected_peers: HashSet<PeerId>,
+    wallet: Wallet,
+    address_book: AddressBook,
+    // delegations already signed by the wallet's other accounts, see `relay`
+    delegations: HashMap<Address, Vec<u8>>,
+    faucet: Option<Faucet>,
+    // lines the node feeds itself and handles as if they were typed in
+    queued_lines: UnboundedSender<String>,
+    bridge: Option<nats::Bridge>,
+    verifier: VerifierPool,
+    assembler: BlockAssembler,
+    lifecycle: Lifecycle,
+    genesis: Genesis,
+    state: ChainState,
+    chain: Chain,
+    block_store: BlockStore,
+    recent_blocks: VecDeque<BlockSummary>,
+    consensus: Consensus,
+    // the first block proposed for each height from the current one on, and its proposer
+    proposals: BTreeMap<u32, (PeerId, Block)>,
+    // the signed votes counted so far, by height, round and voter, for the certificate of the block
+    // they commit
+    signed_votes: HashMap<(u32, u32, PeerId), Vec<u8>>,
+    // height whose proposal was last checked for a vote
+    checked_proposal: Option<u32>,
+    // one span per consensus round, closed when its block gets committed or the round times out
+    round_span: Span,
+    // when the current round got its proposal, rounds without one never time out
+    round_started: Option<Instant>,
+    // set by a test hook to commit without waiting for votes
+    force_block: bool,
+    // blocks fetched from a peer to catch up with, committed one per turn like agreed ones
+    synced_blocks: UnboundedSender<Block>,
+    // the fetched block that follows the tip
+    synced_block: Option<Block>,
+    retarget: Retarget,
+    // blocks found by our own miner, and the one mined by whoever was first, with `--consensus pow`
+    mined_blocks: UnboundedSender<Block>,
+    mined_block: Option<Block>,
+    // mining the block at the current height, stops when dropped
+    miner: Option<Miner>,
+    alerts: Alerts,
+    webhooks: Webhooks,
+    audit_log: AuditLog,
+    watchdog: Watchdog,
+    blacklist: HashSet<Address>,
+}
+
+// A gossip message as delivered: who relayed it, its id and the message itself.
+type DelayedMessage = (PeerId, gossipsub::MessageId, gossipsub::Message);
+
+impl EventLoop<'_> {
+    // Who votes on the next block, and takes turns proposing it.
+    fn validators(&self) -> BTreeSet<PeerId> {
+        validators(
+            self.state.validator_set.as_ref(),
+            &self.connected_peers,
+            self.local_peer_id,
+        )
+    }
+
+    fn on_swarm_event(
+        &mut self,
+        event: SwarmEvent<EduCoinBehaviourEvent, THandlerErr<EduCoinBehaviour>>,
+        fresh: bool,
+    ) {
+        let _network = info_span!("network").entered();
+        // delayed messages are replayed as if they just arrived, but were counted when they did
+        if fresh {
+            self.metrics.record(&event);
+            if let SwarmEvent::Behaviour(EduCoinBehaviourEvent::Gossipsub(gossipsub_event)) = &event
+            {
+                self.metrics.record(gossipsub_event);
+            }
+        }
+        match event {
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
+                for (peer_id, _multiaddr) in list {
+                    info!(%peer_id, "mDNS discovered a new peer");
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .add_explicit_peer(&peer_id);
+                }
+            }
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
+                for (peer_id, _multiaddr) in list {
+                    info!(%peer_id, "mDNS discovered peer has expired");
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .remove_explicit_peer(&peer_id);
+                }
+            }
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::Gossipsub(
+                gossipsub::Event::Message {
+                    propagation_source,
+                    message_id,
+                    message,
+                },
+            )) => self.on_message(propagation_source, message_id, message, fresh),
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::Gossipsub(
+                gossipsub::Event::Subscribed { peer_id, topic },
+            )) => {
+                debug!(%peer_id, %topic, "peer subscribed");
+                if let Some(events) = &self.events {
+                    let _ = events.unbounded_send(NodeEvent::PeerSubscribed {
+                        peer_id,
+                        topic: topic.to_string(),
+                    });
+                }
+            }
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::BlockSync(sync::Event::GetBlocks {
+                peer,
+                from,
+                to,
+            })) => self.serve_blocks(peer, from, to),
+            SwarmEvent::Behaviour(EduCoinBehaviourEvent::BlockSync(sync::Event::Blocks {
+                peer,
+                blocks,
+            })) => self.on_fetched_blocks(peer, blocks),
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                self.connected_peers.insert(peer_id);
+                // peers may have committed blocks we haven't, e.g. when we just joined
+                self.catch_up(peer_id);
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                self.connected_peers.remove(&peer_id);
+            }
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!(%address, "local node is listening");
+            }
+            _ => {}
+        }
+    }
+
+    // A gossip message relayed by `peer_id`, handed to the handler of its topic unless fault
+    // injection holds it back or it was seen before.
+    fn on_message(
+        &mut self,
+        peer_id: PeerId,
+        id: gossipsub::MessageId,
+        message: gossipsub::Message,
+        fresh: bool,
+    ) {
+        // record what the network delivered, before any fault injection
+        if let Some(recorder) = self.recorder.as_mut().filter(|_| fresh) {
+            if let Err(e) = recorder.record(&peer_id, &id, &message) {
+                warn!(error = %e, "failed to record message");
+            }
+        }
+        if let Some(faults) = self.faults.as_ref().filter(|_| fresh) {
+            match faults.inbound(&peer_id) {
+                Verdict::Deliver => {}
+                Verdict::Drop => {
+                    debug!(%peer_id, "fault injection dropped a message");
+                    network::validate(&mut self.swarm, &id, &peer_id, MessageAcceptance::Ignore);
+                    return;
+                }
+                Verdict::Delay(delay) => {
+                    self.delayed_messages.push(
+                        async move {
+                            async_std::task::sleep(delay).await;
+                            (peer_id, id, message)
+                        }
+                        .boxed(),
+                    );
+                    return;
+                }
+            }
+        }
+        // gossipsub forgets what it saw on restart, a message delivered again afterwards was already processed
+        match self.seen.insert(&id.0) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.metrics.message_rejected(&message.topic, "seen_before");
+                debug!(%peer_id, %id, "dropping a message seen before");
+                network::validate(&mut self.swarm, &id, &peer_id, MessageAcceptance::Ignore);
+                return;
+            }
+            Err(e) => warn!(error = %e, "failed to remember a seen message"),
+        }
+        debug!(%peer_id, topic = %message.topic, "got a new message, processing");
+        self.metrics.message_received(&message.topic);
+        self.watchdog.touch();
+        if message.topic == self.topics.votes.hash() {
+            self.on_vote(peer_id, &id, message);
+        } else if message.topic == self.topics.blocks.hash() {
+            self.on_proposal(peer_id, &id, message);
+        } else if message.topic == self.topics.transactions.hash() {
+            self.on_transaction(peer_id, &id, message);
+        } else if message.topic == self.topics.faucet.hash() {
+            self.on_faucet_request(peer_id, &id, message);
+        }
+    }
+
+    // Log what the node was up to when nothing has happened for a while, and start over with its
+    // peers if asked to.
+    fn check_stall(&mut self) {
+        let Some(idle) = self.watchdog.check() else {
+            return;
+        };
+        let connected: Vec<PeerId> = self.swarm.connected_peers().copied().collect();
+        error!(
+            alert = "stall",
+            idle_secs = idle.as_secs(),
+            height = self.consensus.height(),
+            peers = self.connected_peers.len(),
+            connected = connected.len(),
+            mempool = self.assembler.mempool().len(),
+            votes = self.consensus.votes(),
+            "node looks stalled, nothing processed for a while"
+        );
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(NodeEvent::Stalled { idle });
+        }
+
+        if self.cli.restart_on_stall {
+            warn!("dropping every connection and redialing peers");
+            for peer_id in connected {
+                let _ = self.swarm.disconnect_peer_id(peer_id);
+                if let Err(e) = self.swarm.dial(peer_id) {
+                    warn!(%peer_id, error = %e, "failed to redial peer");
+                }
+            }
+        }
+    }
+
+    // Reject a malformed message, and cut the peer that sent it off once it keeps doing so.
+    fn penalize(&mut self, id: &gossipsub::MessageId, peer_id: PeerId) {
+        network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Reject);
+        if self.penalties.strike(peer_id) {
+            warn!(%peer_id, "peer keeps sending malformed messages, blacklisting it");
+            self.swarm
+                .behaviour_mut()
+                .gossipsub
+                .blacklist_peer(&peer_id);
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+            record_audit(
+                &mut self.audit_log,
+                AuditEvent::PeerBlacklisted {
+                    peer_id: peer_id.to_string(),
+                    reason: "malformed messages".to_string(),
+                },
+            );
+            if let Some(events) = &self.events {
+                let _ = events.unbounded_send(NodeEvent::PeerBlacklisted { peer_id });
+            }
+        }
+    }
+}
+
+// Tell the webhooks and the NATS bridge about a transaction that made it into the mempool.
+fn announce_accepted(
+    webhooks: &Webhooks,
+    bridge: Option<&nats::Bridge>,
+    transaction: &Transaction,
+) {
+    webhooks.transaction_accepted(transaction);
+    if let Some(bridge) = bridge {
+        bridge.transaction_accepted(transaction);
+    }
+}
+
+fn consensus_round_span(height: u32, round: u32) -> Span {
+    info_span!(parent: None, "consensus", height, round)
+}
+
+// Failing to audit is never worth stopping the node for.
+fn record_audit(audit_log: &mut AuditLog, event: AuditEvent) {
+    if let Err(e) = audit_log.record(&event) {
+        warn!(error = %e, ?event, "failed to write audit log");
+    }
+}
+
+// Every connected peer and this node are validators, or, with staking epochs, the validators
+// registered by their stake once there are any.
+fn validators(
+    validator_set: Option<&ValidatorSet>,
+    connected_peers: &HashSet<PeerId>,
+    local_peer_id: PeerId,
+) -> BTreeSet<PeerId> {
+    match validator_set.filter(|set| !set.validators().is_empty()) {
+        Some(set) => set.validators().clone(),
+        None => connected_peers
+            .iter()
+            .copied()
+            .chain([local_peer_id])
+            .collect(),
+    }
+}
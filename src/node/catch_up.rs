@@ -0,0 +1,94 @@
+//! Fetching the blocks a node missed from its peers, and serving its own
+//! to peers that fell behind.
+
+use libp2p::PeerId;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use super::EventLoop;
+use crate::block::Block;
+use crate::consensus::Mode;
+use crate::light::{Agreement, Header};
+use crate::sync;
+
+impl EventLoop<'_> {
+    // Ask `peer_id` for the blocks after our tip.
+    pub(super) fn catch_up(&mut self, peer_id: PeerId) {
+        let from = self.chain.tip().height + 1;
+        self.swarm
+            .behaviour_mut()
+            .block_sync
+            .catch_up(peer_id, from);
+    }
+
+    // Answer a peer asking for the stored blocks `from` to `to`, as many as fit an answer.
+    pub(super) fn serve_blocks(&mut self, peer: PeerId, from: u32, to: u32) {
+        let mut blocks = Vec::new();
+        for height in from.max(1)..=to.min(from.saturating_add(sync::MAX_BLOCKS - 1)) {
+            match self.block_store.block(height) {
+                Ok(Some(block)) => blocks.push(block),
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(height, error = %e, "failed to read a block for a peer");
+                    break;
+                }
+            }
+        }
+        debug!(%peer, from, blocks = blocks.len(), "sending blocks to sync");
+        self.swarm
+            .behaviour_mut()
+            .block_sync
+            .send_blocks(peer, blocks.iter().map(Arc::as_ref));
+    }
+
+    // Blocks `peer` sent to catch up with, committed one per turn once they check out, see
+    // `on_synced`.
+    pub(super) fn on_fetched_blocks(&mut self, peer: PeerId, blocks: Vec<Block>) {
+        let Some(last) = blocks.last() else {
+            debug!(%peer, "nothing to catch up with");
+            return;
+        };
+        info!(%peer, from = blocks[0].height, to = last.height, "fetched blocks to catch up with");
+        // a full answer means there may be more
+        if blocks.len() == sync::MAX_BLOCKS as usize {
+            let next = last.height + 1;
+            self.swarm.behaviour_mut().block_sync.catch_up(peer, next);
+        }
+        for block in blocks {
+            let _ = self.synced_blocks.unbounded_send(block);
+        }
+    }
+
+    // Take a fetched block to commit next, if it follows the tip and `validators` agreed on it.
+    pub(super) fn on_synced(&mut self, block: Block, validators: &BTreeSet<PeerId>) {
+        let cli = self.cli;
+        let tip = self.chain.tip();
+        if block.height != tip.height + 1 || block.prev_hash != tip.hash {
+            debug!(
+                height = block.height,
+                "skipping a fetched block that doesn't follow the tip"
+            );
+            return;
+        }
+        // a block counts as agreed on with the votes it was committed with or, mined, its work
+        let agreement = match cli.consensus {
+            Mode::Vote => Agreement::Votes {
+                chain_id: &cli.chain_id,
+                quorum: cli.quorum,
+                validators,
+            },
+            Mode::Pow => Agreement::Work {
+                difficulty: self.retarget.difficulty(),
+            },
+        };
+        if agreement.agreed_on(&Header::from(&block)) {
+            self.synced_block = Some(block);
+        } else {
+            warn!(
+                height = block.height,
+                "skipping a fetched block that wasn't agreed on"
+            );
+        }
+    }
+}
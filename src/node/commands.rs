@@ -0,0 +1,450 @@
+//! What's typed in, or queued as if it was: `/` commands and transactions
+//! to sign, and the calls answered over RPC, see [`crate::rpc`].
+
+use std::path::Path;
+
+use super::EventLoop;
+use crate::address::{self, Address, AddressBook};
+use crate::batch;
+use crate::export;
+use crate::history::{self, TransactionIndex};
+use crate::ledger::Ledger;
+use crate::names;
+use crate::rpc::Call;
+use crate::topology::Topology;
+use crate::transaction::{Transaction, TransactionId};
+use crate::validators::ValidatorKeys;
+use crate::wallet::{self, Wallet};
+
+impl EventLoop<'_> {
+    // A line typed in: a `/` command, or a transaction to sign otherwise.
+    pub(super) async fn on_line(&mut self, line: String) {
+        if let Some(path) = line.trim().strip_prefix("/submit --file ") {
+            match batch::load(Path::new(path.trim())) {
+                Ok(lines) => {
+                    println!("submitting {} transactions from {path}", lines.len());
+                    for line in lines {
+                        let _ = self.queued_lines.unbounded_send(line);
+                    }
+                }
+                Err(e) => println!("Failed to read batch {path}: {e}"),
+            }
+            return;
+        }
+
+        if let Some(args) = line.strip_prefix("/submit ") {
+            self.submit(args);
+            return;
+        }
+
+        if let Some(recipient) = line.trim().strip_prefix("/faucet") {
+            let recipient = Some(recipient.trim()).filter(|recipient| !recipient.is_empty());
+            let recipient = match resolve_or_default(&self.wallet, &self.address_book, recipient) {
+                Ok(recipient) => recipient,
+                Err(e) => {
+                    println!("Address book error: {e}");
+                    return;
+                }
+            };
+
+            match self.request_coins(recipient) {
+                Ok(requested) | Err(requested) => println!("{requested}"),
+            }
+            return;
+        }
+
+        if line.trim() == "/metrics" {
+            print!("{}", self.metrics.encode());
+            return;
+        }
+
+        if let Some(format) = line.trim().strip_prefix("/topology") {
+            let topology = Topology::collect(
+                &self.local_peer_id,
+                &self.swarm.behaviour().gossipsub,
+                self.swarm.connected_peers(),
+            );
+            match format.trim() {
+                "" | "dot" => print!("{}", topology.to_dot()),
+                "json" => match topology.to_json() {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => println!("Failed to encode topology: {e}"),
+                },
+                other => println!("Unknown topology format `{other}`, use `dot` or `json`"),
+            }
+            return;
+        }
+
+        if let Some(tx_id) = line.trim().strip_prefix("/trace ") {
+            match tx_id.trim().parse::<TransactionId>() {
+                Ok(tx_id) => match self.lifecycle.trace(&tx_id) {
+                    Some(trace) => {
+                        println!("transaction {tx_id}:");
+                        for (milestone, timestamp_ms) in trace {
+                            println!("  {timestamp_ms} {milestone}");
+                        }
+                    }
+                    None => println!("Transaction {tx_id} hasn't been seen by this node"),
+                },
+                Err(e) => println!("Invalid transaction id: {e}"),
+            }
+            return;
+        }
+
+        if line.trim() == "/proposals" {
+            let stakes = self.state.ledger.stakes();
+            let total = stakes.total();
+            for (id, proposal) in self.state.governance.proposals() {
+                let (yes, no) = proposal.tally(stakes);
+                println!(
+                    "proposal {id}: {} at height {}, {yes} for and {no} against of {total} staked",
+                    proposal.change, proposal.activation_height
+                );
+            }
+            return;
+        }
+
+        if line.trim() == "/names" {
+            let owner = Address::from(&self.wallet.default_account().public_key());
+            for name in self.state.names.names_of(&owner) {
+                println!("{name}");
+            }
+            return;
+        }
+
+        if let Some(name) = line.trim().strip_prefix("/name ") {
+            match self.state.names.owner(name) {
+                Some(owner) => println!("{name} is owned by {owner}"),
+                None if names::is_valid(name) => {
+                    println!("{name} is not registered, `register {name}` to claim it")
+                }
+                None => println!("`{name}` can't be registered, names are 3 to 32 lowercase letters, digits and dashes"),
+            }
+            return;
+        }
+
+        if line.trim() == "/supply" {
+            let token = &self.genesis.token;
+            let max_supply = self
+                .genesis
+                .max_supply
+                .map_or("unlimited".to_string(), |max| max.to_string());
+            println!(
+                "{}: {} {} in existence, max supply {max_supply}",
+                token.name,
+                self.state.ledger.supply(),
+                token.symbol
+            );
+            return;
+        }
+
+        if let Some(block) = line.trim().strip_prefix("/block ") {
+            let height = block.parse().ok().or_else(|| {
+                let hash = hex::decode(block).ok()?.try_into().ok()?;
+                self.block_store.height_of(&hash)
+            });
+            match height.map(|height| self.block_store.block(height)) {
+                Some(Ok(Some(block))) => {
+                    println!(
+                        "block {} at height {}, timestamp {}",
+                        hex::encode(block.hash),
+                        block.height,
+                        block.timestamp
+                    );
+                    println!("  follows {}", hex::encode(block.prev_hash));
+                    for id in block.transaction_ids() {
+                        println!("  {id}");
+                    }
+                }
+                Some(Ok(None)) | None => println!("No block `{block}` stored on this node"),
+                Some(Err(e)) => println!("Failed to read the block: {e}"),
+            }
+            return;
+        }
+
+        if line.trim() == "/health" {
+            if self.block_store.is_healthy() {
+                println!("storage: ok");
+            } else {
+                println!(
+                    "storage: failing, {} block(s) queued in memory",
+                    self.block_store.pending()
+                );
+            }
+            return;
+        }
+
+        if let Some(command) = line.strip_prefix('/') {
+            handle_command(
+                &mut self.wallet,
+                &mut self.address_book,
+                &self.state.ledger,
+                &self.state.transaction_index,
+                &self.state.validator_keys,
+                self.assembler.mempool(),
+                command,
+            );
+            return;
+        }
+
+        self.sign_and_publish(&line).await;
+    }
+
+    // Answer an RPC call, see [`crate::rpc`].
+    pub(super) fn on_rpc(&mut self, call: Call) {
+        let answer = match (call.method.as_str(), call.params.as_slice()) {
+            ("faucet", [] | [_]) => resolve_or_default(
+                &self.wallet,
+                &self.address_book,
+                call.params.first().map(String::as_str),
+            )
+            .map_err(|e| format!("address book error: {e}"))
+            .and_then(|recipient| self.request_coins(recipient))
+            .map(serde_json::Value::from),
+            ("faucet", _) => Err("usage: faucet [address or label]".to_string()),
+            _ => answer_rpc(
+                &self.wallet,
+                &mut self.address_book,
+                &self.state.ledger,
+                self.assembler.mempool(),
+                &call.method,
+                &call.params,
+            ),
+        };
+        let _ = call.reply.send(answer);
+    }
+}
+
+// Commands are entered on stdin prefixed with a slash, e.g. `/account new alice`.
+fn handle_command<'a>(
+    wallet: &mut Wallet,
+    address_book: &mut AddressBook,
+    ledger: &Ledger,
+    transaction_index: &TransactionIndex,
+    validator_keys: &ValidatorKeys,
+    mempool: impl IntoIterator<Item = &'a Transaction>,
+    command: &str,
+) {
+    let mut words = command.split_whitespace();
+
+    match (words.next(), words.next(), words.next()) {
+        (Some("account"), Some("new"), Some(label)) => match wallet.derive_account(label) {
+            Ok(account) => println!("derived {}", describe_account(account)),
+            Err(e) => println!("Wallet error: {e}"),
+        },
+        (Some("account"), Some("import"), Some(label)) => {
+            let Some(key_file) = words.next() else {
+                println!("Usage: /account import <label> <key-file>");
+                return;
+            };
+
+            match wallet::load_key_file(Path::new(key_file)) {
+                Ok(keypair) => match wallet.add_account(label, Box::new(keypair)) {
+                    Ok(account) => println!("imported {}", describe_account(account)),
+                    Err(e) => println!("Wallet error: {e}"),
+                },
+                Err(e) => println!("Failed to read key file: {e}"),
+            }
+        }
+        (Some("alias"), Some(label), Some(address_or_label)) => {
+            let result = address_book
+                .resolve(address_or_label)
+                .and_then(|address| address_book.insert(label, address).map(|_| address));
+
+            match result {
+                Ok(address) => println!("`{label}` now refers to {address}"),
+                Err(e) => println!("Address book error: {e}"),
+            }
+        }
+        (Some("aliases"), None, None) => {
+            for (label, address) in address_book.entries() {
+                println!("{label}: {address}");
+            }
+        }
+        (Some("balance"), address_or_label, None) => {
+            match resolve_or_default(wallet, address_book, address_or_label) {
+                Ok(address) => println!(
+                    "balance of {address}: {} confirmed, {} pending",
+                    ledger.balance(&address),
+                    ledger.pending_balance(&address, mempool)
+                ),
+                Err(e) => println!("Address book error: {e}"),
+            }
+        }
+        (Some("nonce"), address_or_label, None) => {
+            match resolve_or_default(wallet, address_book, address_or_label) {
+                Ok(address) => println!(
+                    "nonce of {address}: {} committed, sign the next transaction with {}",
+                    ledger.nonce(&address),
+                    ledger.next_nonce(&address, mempool)
+                ),
+                Err(e) => println!("Address book error: {e}"),
+            }
+        }
+        (Some("history"), address_or_label, page) => {
+            let address = match resolve_or_default(wallet, address_book, address_or_label) {
+                Ok(address) => address,
+                Err(e) => {
+                    println!("Address book error: {e}");
+                    return;
+                }
+            };
+            // pages are numbered from 1 for humans
+            let Ok(page) = page.map_or(Ok(1), str::parse::<usize>) else {
+                println!("Usage: /history [address] [page]");
+                return;
+            };
+
+            let total = transaction_index.history_len(&address);
+            let pages = total.div_ceil(history::PAGE_SIZE).max(1);
+            println!("history of {address}: {total} transactions, page {page}/{pages}");
+            for entry in transaction_index.history(&address, page.saturating_sub(1)) {
+                println!(
+                    "height {}: from {} {:?}",
+                    entry.height,
+                    Address::from(&entry.transaction.public_key),
+                    String::from_utf8_lossy(&entry.transaction.data)
+                );
+            }
+        }
+        (Some("lock"), Some(lock_id), None) => match lock_id.parse::<TransactionId>() {
+            Ok(lock_id) => match ledger.lock(&lock_id) {
+                Some(lock) => println!(
+                    "lock {lock_id}: {} for {}, released by a claim meeting `{}`",
+                    lock.amount, lock.recipient, lock.script
+                ),
+                None => {
+                    println!("No coins are locked under {lock_id}, or they were claimed already")
+                }
+            },
+            Err(e) => println!("Invalid lock id: {e}"),
+        },
+        (Some("stakes"), None, None) => {
+            for (address, stake) in ledger.stakes().bonded() {
+                println!("{address}: {stake} bonded");
+            }
+        }
+        (Some("stake"), address_or_label, None) => {
+            match resolve_or_default(wallet, address_book, address_or_label) {
+                Ok(address) => {
+                    let stakes = ledger.stakes();
+                    println!(
+                        "stake of {address}: {} bonded of {} in total",
+                        stakes.stake(&address),
+                        stakes.total()
+                    );
+                    for unbonding in stakes.unbonding(&address) {
+                        println!(
+                            "{} unbonding, released at height {}",
+                            unbonding.amount, unbonding.release_height
+                        );
+                    }
+                }
+                Err(e) => println!("Address book error: {e}"),
+            }
+        }
+        (Some("validator"), address_or_label, None) => {
+            match resolve_or_default(wallet, address_book, address_or_label) {
+                Ok(key) => {
+                    let identity = validator_keys.identity(&key);
+                    let status = if validator_keys.is_retired(&key) {
+                        "retired"
+                    } else {
+                        "active"
+                    };
+                    println!(
+                        "key {key} is {status}, identity {identity} currently signs with {}",
+                        validator_keys.current_key(&identity)
+                    );
+                }
+                Err(e) => println!("Address book error: {e}"),
+            }
+        }
+        (Some("export"), Some("--format"), Some(format)) => {
+            let format = match format.parse::<export::Format>() {
+                Ok(format) => format,
+                Err(e) => {
+                    println!("{e}");
+                    return;
+                }
+            };
+            let (Some(path), None) = (words.next(), words.next()) else {
+                println!("Usage: /export --format json|csv <file>");
+                return;
+            };
+            match export::export(Path::new(path), format, transaction_index) {
+                Ok(blocks) => println!("exported {blocks} blocks to {path} as {format}"),
+                Err(e) => println!("Failed to export blocks: {e}"),
+            }
+        }
+        (Some("accounts"), None, None) => {
+            for account in wallet.accounts() {
+                println!("{}", describe_account(account));
+            }
+        }
+        _ => println!("Unknown command: /{command}"),
+    }
+}
+
+// The answer to the RPC call of `method` with `params`, see [`crate::rpc`].
+fn answer_rpc<'a>(
+    wallet: &Wallet,
+    address_book: &mut AddressBook,
+    ledger: &Ledger,
+    mempool: impl IntoIterator<Item = &'a Transaction>,
+    method: &str,
+    params: &[String],
+) -> Result<serde_json::Value, String> {
+    match (method, params) {
+        ("balance", [] | [_]) => {
+            let address =
+                resolve_or_default(wallet, address_book, params.first().map(String::as_str))
+                    .map_err(|e| format!("address book error: {e}"))?;
+            Ok(serde_json::json!({
+                "address": address.to_string(),
+                "confirmed": ledger.balance(&address),
+                "pending": ledger.pending_balance(&address, mempool),
+            }))
+        }
+        ("balance", _) => Err("usage: balance [address or label]".to_string()),
+        ("alias", [label, address_or_label]) => {
+            let address = address_book
+                .resolve(address_or_label)
+                .and_then(|address| address_book.insert(label, address).map(|_| address))
+                .map_err(|e| format!("address book error: {e}"))?;
+            Ok(serde_json::json!({ "label": label, "address": address.to_string() }))
+        }
+        ("alias", _) => Err("usage: alias <label> <address or label>".to_string()),
+        ("aliases", []) => Ok(address_book
+            .entries()
+            .map(|(label, address)| (label.clone(), address.to_string().into()))
+            .collect::<serde_json::Map<_, _>>()
+            .into()),
+        (method, _) => Err(format!("unknown method `{method}`")),
+    }
+}
+
+// Queries default to the node's own account when no address is given.
+fn resolve_or_default(
+    wallet: &Wallet,
+    address_book: &AddressBook,
+    address_or_label: Option<&str>,
+) -> Result<Address, address::AddressError> {
+    match address_or_label {
+        Some(address_or_label) => address_book.resolve(address_or_label),
+        None => Ok(Address::from(&wallet.default_account().public_key())),
+    }
+}
+
+fn describe_account(account: &wallet::Account) -> String {
+    let origin = match account.index {
+        Some(index) => format!("index {index}"),
+        None => "external signer".to_string(),
+    };
+
+    format!(
+        "account `{}` ({origin}): {}",
+        account.label,
+        Address::from(&account.public_key())
+    )
+}
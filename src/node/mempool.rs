@@ -0,0 +1,495 @@
+//! Taking transactions into the mempool: signed by the wallet, submitted
+//! signed elsewhere or gossiped by peers, and faucet coins asked for.
+
+use libp2p::gossipsub::{self, MessageAcceptance};
+use libp2p::PeerId;
+use std::path::Path;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use super::{announce_accepted, record_audit, EventLoop};
+use crate::address::{self, Address, AddressBook};
+use crate::audit::AuditEvent;
+use crate::error;
+use crate::faucet;
+use crate::lifecycle::Milestone;
+use crate::names::NameRegistry;
+use crate::network;
+use crate::relay;
+use crate::transaction::{self, Transaction};
+use crate::validators::KeyRotation;
+use crate::verifier::Verified;
+use crate::wallet::{self, Wallet, WalletError};
+
+impl EventLoop<'_> {
+    // Sign what was typed in with the wallet's account it names, and publish it.
+    pub(super) async fn sign_and_publish(&mut self, line: &str) {
+        let cli = self.cli;
+        let mempool_span = info_span!("mempool");
+        let (account, data) = match select_account(&self.wallet, line) {
+            Ok(selected) => selected,
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        };
+        let (fee, data) = match wallet::parse_fee(data) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        };
+        let address = Address::from(&account.public_key());
+        if self.state.validator_keys.is_retired(&address) {
+            println!(
+                "Account `{}` has a retired key and can no longer sign transactions",
+                account.label
+            );
+            return;
+        }
+        if self.blacklist.contains(&address) {
+            println!("Account `{}` is blacklisted on this node", account.label);
+            return;
+        }
+        let data =
+            match prepare_transaction_data(data, account, &self.address_book, &self.state.names)
+                .await
+            {
+                Ok(data) => data,
+                Err(e) => {
+                    println!("{e}");
+                    return;
+                }
+            };
+
+        let nonce = self
+            .state
+            .ledger
+            .next_nonce(&address, self.assembler.mempool());
+        let signed = Transaction::sign_with_fee(
+            account.signer.as_ref(),
+            &cli.chain_id,
+            data.as_bytes(),
+            nonce,
+            fee,
+        )
+        .instrument(info_span!(parent: &mempool_span, "sign", account = %account.label))
+        .await;
+        let _mempool = mempool_span.enter();
+        let mut transaction = match signed {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                warn!(account = %account.label, error = %e, "failed to sign transaction");
+                return;
+            }
+        };
+        if self.assembler.holds(&transaction.id()) {
+            println!("That transaction is already waiting in the mempool");
+            return;
+        }
+        // what's waiting in the mempool gets spent first
+        if self
+            .state
+            .ledger
+            .overdrafts(self.assembler.mempool().chain([&transaction]))
+            .contains(&transaction.id())
+        {
+            println!(
+                "Account `{}` doesn't hold enough coins for that",
+                account.label
+            );
+            return;
+        }
+
+        // other accounts' transactions go out with the account's permission for this node to publish them
+        let delegation = if relay::is_authorized(&account.public_key(), &self.local_peer_id, None) {
+            None
+        } else if let Some(delegation) = self.delegations.get(&address) {
+            Some(delegation.clone())
+        } else {
+            match relay::delegate(account.signer.as_ref(), &self.local_peer_id).await {
+                Ok(delegation) => {
+                    self.delegations.insert(address, delegation.clone());
+                    Some(delegation)
+                }
+                Err(e) => {
+                    warn!(account = %account.label, error = %e, "failed to sign delegation");
+                    return;
+                }
+            }
+        };
+
+        if let Some(strategy) = cli.byzantine {
+            strategy.tamper(&mut transaction);
+        }
+        let tx_id = transaction.id();
+        self.lifecycle.record(tx_id, Milestone::Received);
+        if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(
+            self.topics.transactions.clone(),
+            relay::encode(&transaction, delegation.as_deref()),
+        ) {
+            self.metrics
+                .publish_failed(&self.topics.transactions.hash());
+            warn!(%tx_id, error = ?e, "failed to publish transaction");
+        } else {
+            self.lifecycle.record(tx_id, Milestone::Gossiped);
+            self.lifecycle.record(tx_id, Milestone::Admitted);
+            announce_accepted(&self.webhooks, self.bridge.as_ref(), &transaction);
+            self.assembler.admit(transaction);
+
+            info!(%tx_id, account = %account.label, "transaction stored and published");
+            debug!(mempool = ?self.assembler.mempool().collect::<Vec<_>>());
+        }
+    }
+
+    // `/submit <transaction> [delegation]`: a transaction signed elsewhere, published by this
+    // node with the key holder's delegation.
+    pub(super) fn submit(&mut self, args: &str) {
+        let _mempool = info_span!("mempool").entered();
+        let mut args = args.split_whitespace();
+        let blob = args.next().unwrap_or_default();
+        // badly encoded delegations fail verification like any other bad one
+        let delegation = args
+            .next()
+            .map(|delegation| hex::decode(delegation).unwrap_or_default());
+        let local_peer_id = self.local_peer_id;
+        let rejected =
+            |transaction: Option<&Transaction>, reason: &str| AuditEvent::TransactionRejected {
+                tx_id: transaction.map(|transaction| transaction.id().to_string()),
+                peer_id: None,
+                reason: reason.to_string(),
+            };
+        match transaction::from_hex(blob) {
+            Ok(transaction)
+                if self
+                    .state
+                    .validator_keys
+                    .is_retired(&Address::from(&transaction.public_key)) =>
+            {
+                warn!(tx_id = %transaction.id(), "rejected pre-signed transaction: signed with a retired key");
+                record_audit(
+                    &mut self.audit_log,
+                    rejected(Some(&transaction), "retired key"),
+                );
+            }
+            Ok(transaction)
+                if self
+                    .blacklist
+                    .contains(&Address::from(&transaction.public_key)) =>
+            {
+                warn!(tx_id = %transaction.id(), "rejected pre-signed transaction: signed by a blacklisted address");
+                record_audit(
+                    &mut self.audit_log,
+                    rejected(Some(&transaction), "blacklisted address"),
+                );
+            }
+            Ok(transaction)
+                if !relay::is_authorized(
+                    &transaction.public_key,
+                    &local_peer_id,
+                    delegation.as_deref(),
+                ) =>
+            {
+                println!("Transactions signed by other keys need a delegation to this node, see `sign-tx --relay {local_peer_id}`");
+                record_audit(
+                    &mut self.audit_log,
+                    rejected(Some(&transaction), "not delegated to this node"),
+                );
+            }
+            Ok(transaction) if self.assembler.holds(&transaction.id()) => {
+                info!(tx_id = %transaction.id(), "pre-signed transaction already in the mempool");
+            }
+            Ok(transaction)
+                if self.state.ledger.is_stale(&transaction)
+                    || self.assembler.holds_nonce(&transaction) =>
+            {
+                warn!(tx_id = %transaction.id(), nonce = transaction.nonce, "rejected pre-signed transaction: nonce already used");
+                record_audit(
+                    &mut self.audit_log,
+                    rejected(Some(&transaction), "stale nonce"),
+                );
+            }
+            Ok(transaction) if transaction.verify(&self.cli.chain_id) => {
+                self.lifecycle.record(transaction.id(), Milestone::Received);
+                if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(
+                    self.topics.transactions.clone(),
+                    relay::encode(&transaction, delegation.as_deref()),
+                ) {
+                    self.metrics
+                        .publish_failed(&self.topics.transactions.hash());
+                    warn!(tx_id = %transaction.id(), error = ?e, "failed to publish transaction");
+                } else {
+                    info!(tx_id = %transaction.id(), "pre-signed transaction stored and published");
+                    self.lifecycle.record(transaction.id(), Milestone::Gossiped);
+                    self.lifecycle.record(transaction.id(), Milestone::Admitted);
+                    announce_accepted(&self.webhooks, self.bridge.as_ref(), &transaction);
+                    self.assembler.admit(transaction);
+                }
+            }
+            Ok(transaction) => {
+                warn!(tx_id = %transaction.id(), "rejected pre-signed transaction: invalid signature");
+                record_audit(
+                    &mut self.audit_log,
+                    rejected(Some(&transaction), "invalid signature"),
+                );
+            }
+            Err(e) => {
+                warn!(error = %e, "rejected pre-signed transaction");
+                record_audit(&mut self.audit_log, rejected(None, &e.to_string()));
+            }
+        }
+    }
+
+    // A transaction gossiped by `peer_id`, handed to the verifiers once it passes the cheap checks.
+    pub(super) fn on_transaction(
+        &mut self,
+        peer_id: PeerId,
+        id: &gossipsub::MessageId,
+        mut message: gossipsub::Message,
+    ) {
+        let _mempool = info_span!("mempool").entered();
+        let rejected = |tx_id: Option<String>, reason: &str| AuditEvent::TransactionRejected {
+            tx_id,
+            peer_id: Some(peer_id.to_string()),
+            reason: reason.to_string(),
+        };
+        // transactions carry their own public key and signature, decode them and push into mempool
+        // the payload isn't needed anymore, the transaction takes it over rather than copying it
+        let relayed = match relay::decode(std::mem::take(&mut message.data).into()) {
+            Ok(relayed) => relayed,
+            Err(e) => {
+                self.metrics.message_rejected(&message.topic, "malformed");
+                warn!(%peer_id, error = %e, "dropping malformed transaction");
+                record_audit(&mut self.audit_log, rejected(None, &e.to_string()));
+                self.penalize(id, peer_id);
+                return;
+            }
+        };
+        // strict validation makes sure `source` is the node that published the message
+        if !message
+            .source
+            .is_some_and(|source| relayed.is_authorized_for(&source))
+        {
+            self.metrics
+                .message_rejected(&message.topic, "not_delegated");
+            warn!(%peer_id, publisher = ?message.source, tx_id = %relayed.transaction.id(), "dropping transaction published without its key holder's delegation");
+            record_audit(
+                &mut self.audit_log,
+                rejected(
+                    Some(relayed.transaction.id().to_string()),
+                    "not delegated to its publisher",
+                ),
+            );
+            self.penalize(id, peer_id);
+            return;
+        }
+        let transaction = relayed.transaction;
+        let tx_id = transaction.id();
+        // a transaction relayed again, or ours coming back, isn't verified twice
+        if self.assembler.holds(&tx_id) {
+            self.metrics.message_rejected(&message.topic, "duplicate");
+            debug!(%peer_id, %tx_id, "dropping a transaction already in the mempool");
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        self.lifecycle.record(tx_id, Milestone::Received);
+        let signer = Address::from(&transaction.public_key);
+        if self.state.validator_keys.is_retired(&signer) {
+            self.metrics.message_rejected(&message.topic, "retired_key");
+            warn!(%peer_id, %tx_id, "dropping transaction signed with a retired key");
+            record_audit(
+                &mut self.audit_log,
+                rejected(Some(tx_id.to_string()), "retired key"),
+            );
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        if self.blacklist.contains(&signer) {
+            self.metrics
+                .message_rejected(&message.topic, "blacklisted_address");
+            warn!(%peer_id, %tx_id, "dropping transaction signed by a blacklisted address");
+            record_audit(
+                &mut self.audit_log,
+                rejected(Some(tx_id.to_string()), "blacklisted address"),
+            );
+            // other nodes may not blacklist it, so don't hold relaying it against the peer
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Accept);
+        // signatures are checked off the event loop, see `on_verified`
+        if !self.verifier.verify(peer_id, transaction) {
+            self.metrics.message_dropped(&message.topic);
+            debug!(%peer_id, %tx_id, "verifier queue is full, dropping transaction");
+        }
+    }
+
+    // A gossiped transaction back from the verifiers, admitted into the mempool if its signature
+    // checks out and its nonce is still unused.
+    pub(super) fn on_verified(&mut self, verified: Verified) {
+        let _mempool = info_span!("mempool").entered();
+        let Verified {
+            peer_id,
+            transaction,
+            valid,
+        } = verified;
+        let tx_id = transaction.id();
+        let topic = self.topics.transactions.hash();
+        let rejected = |reason: &str| AuditEvent::TransactionRejected {
+            tx_id: Some(tx_id.to_string()),
+            peer_id: Some(peer_id.to_string()),
+            reason: reason.to_string(),
+        };
+        if !valid {
+            self.metrics.message_rejected(&topic, "invalid_signature");
+            warn!(%peer_id, %tx_id, "dropping transaction with an invalid signature");
+            record_audit(&mut self.audit_log, rejected("invalid signature"));
+            return;
+        }
+        // two peers may have relayed it before either copy was verified
+        if self.assembler.holds(&tx_id) {
+            debug!(%peer_id, %tx_id, "transaction already in the mempool");
+            return;
+        }
+        if self.state.ledger.is_stale(&transaction) {
+            self.metrics.message_rejected(&topic, "stale_nonce");
+            warn!(%peer_id, %tx_id, nonce = transaction.nonce, "dropping transaction whose nonce was already used");
+            record_audit(&mut self.audit_log, rejected("stale nonce"));
+            return;
+        }
+        if self.assembler.holds_nonce(&transaction) {
+            debug!(%peer_id, %tx_id, "another transaction with its nonce is already in the mempool");
+            return;
+        }
+        self.lifecycle.record(tx_id, Milestone::Admitted);
+        announce_accepted(&self.webhooks, self.bridge.as_ref(), &transaction);
+        self.assembler.admit(transaction);
+
+        let mempool_len = self.assembler.mempool().len();
+        info!(%peer_id, %tx_id, mempool_len, "got a new transaction, stored into mempool");
+
+        debug!(mempool = ?self.assembler.mempool().collect::<Vec<_>>());
+    }
+
+    // A peer asking the faucet for coins, sent when this node runs it.
+    pub(super) fn on_faucet_request(
+        &mut self,
+        peer_id: PeerId,
+        id: &gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) {
+        let Some(recipient) = faucet::decode_request(&message.data) else {
+            self.metrics.message_rejected(&message.topic, "malformed");
+            warn!(%peer_id, "dropping malformed faucet request");
+            self.penalize(id, peer_id);
+            return;
+        };
+        network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Accept);
+        let Some(faucet) = self.faucet.as_mut() else {
+            return;
+        };
+        match faucet.dispense(recipient, &self.state.ledger, self.assembler.mempool()) {
+            Ok(line) => {
+                info!(%peer_id, %recipient, "dispensing faucet coins");
+                let _ = self.queued_lines.unbounded_send(line);
+            }
+            Err(e) => info!(%peer_id, error = %e, "refusing faucet request"),
+        }
+    }
+
+    // Coins for `recipient`, sent by this node when it runs the faucet and asked of the faucet over
+    // gossip otherwise. Says what was done, or why it wasn't.
+    pub(super) fn request_coins(&mut self, recipient: Address) -> Result<String, String> {
+        match self.faucet.as_mut() {
+            Some(faucet) => {
+                let line = faucet
+                    .dispense(recipient, &self.state.ledger, self.assembler.mempool())
+                    .map_err(|e| format!("Faucet error: {e}"))?;
+                let _ = self.queued_lines.unbounded_send(line);
+                Ok(format!(
+                    "faucet {} is sending coins to {recipient}",
+                    faucet.address()
+                ))
+            }
+            None => match self.swarm.behaviour_mut().gossipsub.publish(
+                self.topics.faucet.clone(),
+                faucet::encode_request(&recipient),
+            ) {
+                Ok(_) => Ok(format!("asked the faucet for coins for {recipient}")),
+                Err(e) => {
+                    self.metrics.publish_failed(&self.topics.faucet.hash());
+                    Err(format!("Failed to reach the faucet: {e:?}"))
+                }
+            },
+        }
+    }
+}
+
+// Pick the account named by a leading `--from <label>`, or the default one.
+fn select_account<'a, 'l>(
+    wallet: &'a Wallet,
+    line: &'l str,
+) -> Result<(&'a wallet::Account, &'l str), error::Error> {
+    match wallet::parse_from_selector(line)? {
+        (Some(label), data) => wallet
+            .account(label)
+            .map(|account| (account, data))
+            .ok_or_else(|| WalletError::UnknownAccount(label.to_string()).into()),
+        (None, data) => Ok((wallet.default_account(), data)),
+    }
+}
+
+// `@<name>` for the owner of a registered name, a label or an address otherwise
+fn resolve_recipient(
+    address_book: &AddressBook,
+    names: &NameRegistry,
+    recipient: &str,
+) -> Result<Address, address::AddressError> {
+    match recipient.strip_prefix('@') {
+        Some(name) => names
+            .owner(name)
+            .ok_or_else(|| address::AddressError::UnregisteredName(name.to_string())),
+        None => address_book.resolve(recipient),
+    }
+}
+
+// Expand the shorthand accepted on stdin into the data that actually gets signed:
+// `send <recipient> <amount>` and `lock <recipient> <amount> <script>` may name the recipient
+// by its address book label, since labels only exist on this node, and `rotate-key <key-file>`
+// becomes a rotation proven by the new key.
+async fn prepare_transaction_data(
+    data: &str,
+    account: &wallet::Account,
+    address_book: &AddressBook,
+    names: &NameRegistry,
+) -> Result<String, error::Error> {
+    if let ["lock", recipient, amount, script] = data
+        .trim()
+        .splitn(4, char::is_whitespace)
+        .collect::<Vec<_>>()[..]
+    {
+        let recipient = resolve_recipient(address_book, names, recipient)?;
+        return Ok(format!("lock {recipient} {amount} {script}"));
+    }
+    let mut words = data.split_whitespace();
+
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("send"), Some(recipient), Some(amount), None) => {
+            let recipient = resolve_recipient(address_book, names, recipient)?;
+            Ok(format!("send {recipient} {amount}"))
+        }
+        (Some("bridge-out"), Some(destination), Some(recipient), Some(amount)) => {
+            let recipient = resolve_recipient(address_book, names, recipient)?;
+            Ok(format!("bridge-out {destination} {recipient} {amount}"))
+        }
+        (Some("transfer-name"), Some(name), Some(owner), None) => {
+            let owner = resolve_recipient(address_book, names, owner)?;
+            Ok(format!("transfer-name {name} {owner}"))
+        }
+        (Some("rotate-key"), Some(key_file), None, None) => {
+            let new_keypair = wallet::load_key_file(Path::new(key_file))?;
+            Ok(KeyRotation::data(&account.public_key(), &new_keypair).await?)
+        }
+        _ => Ok(data.to_string()),
+    }
+}
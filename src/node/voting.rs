@@ -0,0 +1,686 @@
+//! Proposing or mining the next block, voting on the blocks peers propose
+//! and committing the ones agreed on.
+
+use libp2p::gossipsub::{self, MessageAcceptance};
+use libp2p::PeerId;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info, info_span, warn};
+
+use super::{consensus_round_span, record_audit, EventLoop, NodeEvent};
+use crate::alerts::{Alert, AlertKind};
+use crate::assembler::{BlockAssembler, BlockRules};
+use crate::audit::AuditEvent;
+use crate::block::{self, Block};
+use crate::byzantine;
+use crate::cid;
+use crate::consensus::{Mode, Recorded};
+use crate::dashboard::{self, BlockSummary};
+use crate::lifecycle::Milestone;
+use crate::merkle::Hash;
+use crate::network;
+use crate::pow::{self, Miner};
+use crate::proposal;
+use crate::transaction::Transaction;
+use crate::vote::{self, Vote};
+
+impl EventLoop<'_> {
+    // Commit the next block once there is one: fetched, mined, forced by a test hook or agreed on
+    // by `validators`.
+    pub(super) fn commit(&mut self, validators: &BTreeSet<PeerId>) {
+        let Some(block) = self.next_block(validators) else {
+            return;
+        };
+        self.force_block = false;
+        let _consensus = self.round_span.clone().entered();
+        let block_height = self.consensus.height();
+        info!(
+            votes = self.consensus.votes_for(&block.hash),
+            validators = validators.len(),
+            "collected a quorum of votes, executing consensus"
+        );
+        let merkle_root = cid::encode(&block.merkle_root);
+        debug!(
+            transactions = block.transactions.len(),
+            %merkle_root,
+            hash = %hex::encode(block.hash),
+            "took transactions from mempool"
+        );
+
+        info_span!("storage").in_scope(|| {
+            self.block_store.store(&block);
+            self.metrics.set_pending_blocks(self.block_store.pending());
+        });
+        if self.recent_blocks.len() == dashboard::RECENT_BLOCKS {
+            self.recent_blocks.pop_front();
+        }
+        self.recent_blocks.push_back(BlockSummary {
+            height: block_height,
+            transactions: block.transactions.len(),
+        });
+        record_audit(
+            &mut self.audit_log,
+            AuditEvent::BlockCommitted {
+                height: block_height,
+                merkle_root,
+                transactions: block
+                    .transactions
+                    .iter()
+                    .map(|transaction| transaction.id().to_string())
+                    .collect(),
+            },
+        );
+        for transaction in &block.transactions {
+            self.lifecycle
+                .record(transaction.id(), Milestone::Committed);
+        }
+        self.webhooks
+            .block_committed(block_height, &block.transactions);
+        if let Some(bridge) = &self.bridge {
+            bridge.block_committed(block_height, &block.transactions);
+        }
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(NodeEvent::BlockCommitted {
+                height: block_height,
+                transactions: block.transactions.iter().map(Transaction::id).collect(),
+            });
+        }
+        if self.cli.consensus == Mode::Pow {
+            if let Some(difficulty) = self.retarget.record(block_height, block.timestamp) {
+                info!(difficulty, "adjusted the mining difficulty");
+            }
+        }
+        self.state.apply(block, &mut self.assembler);
+
+        self.watchdog.touch();
+
+        debug!("clearing votes collected for the current block");
+        self.consensus.commit();
+        let height = self.consensus.height();
+        self.proposals.retain(|&proposed, _| proposed >= height);
+        self.signed_votes
+            .retain(|&(voted, _, _), _| voted >= height);
+        self.round_span = consensus_round_span(height, self.consensus.round());
+        self.round_started = None;
+        self.miner = None;
+    }
+
+    // The block to commit, put together again from the mempool, if there is one and it checks out.
+    fn next_block(&mut self, validators: &BTreeSet<PeerId>) -> Option<Block> {
+        let cli = self.cli;
+        let height = self.consensus.height();
+        // consensus was reached on the proposed block, commit exactly what was proposed
+        let agreed = self.proposals.get(&height).is_some_and(|(_, proposed)| {
+            self.consensus
+                .has_quorum_of(cli.quorum, validators, &proposed.hash)
+        });
+        let tip = self.chain.tip();
+        // the block, the hash agreed on for it and who proposed it, for blocks that were voted on
+        let block = if let Some(unvoted) = self.synced_block.take().or_else(|| {
+            self.mined_block
+                .take()
+                .filter(|block| block.prev_hash == tip.hash)
+        }) {
+            let hash = unvoted.hash;
+            Some((reassemble(&mut self.assembler, unvoted), hash, None))
+        } else if self.force_block {
+            self.assembler
+                .take(true, &self.state.ledger)
+                .map(|transactions| {
+                    let timestamp = tip.next_timestamp(SystemTime::now());
+                    let block = Block::new(height, timestamp, tip.hash, transactions);
+                    let hash = block.hash;
+                    (block, hash, None)
+                })
+        } else if agreed {
+            self.proposals.remove(&height).map(|(proposer, proposed)| {
+                let hash = proposed.hash;
+                let mut block = reassemble(&mut self.assembler, proposed);
+                // the votes that committed it, for peers syncing the block later on
+                block.votes = self
+                    .consensus
+                    .voters_for(&hash)
+                    .filter(|voter| validators.contains(voter))
+                    .filter_map(|voter| {
+                        self.signed_votes
+                            .get(&(height, self.consensus.round(), voter))
+                            .cloned()
+                    })
+                    .collect();
+                (block, hash, Some(proposer))
+            })
+        } else {
+            None
+        };
+        let (block, agreed_hash, proposer) = block?;
+        // never persist a block on the strength of its earlier checks, a forced one had none
+        let checked = self
+            .rules(validators)
+            .check(&block, &agreed_hash, proposer.as_ref())
+            .map_err(|e| e.to_string());
+        match checked.and_then(|()| self.chain.append(&block).map_err(|e| e.to_string())) {
+            Ok(()) => Some(block),
+            Err(e) => {
+                self.force_block = false;
+                self.alerts.fire(Alert {
+                    kind: AlertKind::RejectedBlock,
+                    height,
+                    detail: format!("dropping the block, {e}"),
+                });
+                None
+            }
+        }
+    }
+
+    // What a block at the current height has to meet, with `validators` voting on it.
+    fn rules<'r>(&'r self, validators: &'r BTreeSet<PeerId>) -> BlockRules<'r> {
+        BlockRules {
+            chain_id: &self.cli.chain_id,
+            tip: self.chain.tip(),
+            latest: block::unix_time(SystemTime::now()) + self.cli.max_clock_drift,
+            block_size: self.assembler.block_size(),
+            blacklist: &self.blacklist,
+            validator_keys: &self.state.validator_keys,
+            ledger: &self.state.ledger,
+            validators,
+        }
+    }
+
+    // Mine the next block once a full one is waiting, or propose it when it's our turn among
+    // `validators`.
+    pub(super) fn propose(&mut self, validators: &BTreeSet<PeerId>) {
+        let cli = self.cli;
+        let block_height = self.consensus.height();
+        let tip = self.chain.tip();
+
+        // the proposal is only taken apart for its block
+        if cli.consensus == Mode::Pow {
+            if let Some(candidate) = self
+                .miner
+                .is_none()
+                .then(|| self.assembler.candidate(block_height, &self.state.ledger))
+                .flatten()
+            {
+                let _consensus = self.round_span.enter();
+                info!(
+                    transactions = candidate.len(),
+                    difficulty = self.retarget.difficulty(),
+                    "mining the next block"
+                );
+                let timestamp = tip.next_timestamp(SystemTime::now());
+                let data = proposal::encode(
+                    &cli.chain_id,
+                    &tip,
+                    0,
+                    timestamp,
+                    candidate,
+                    &self.local_peer_id,
+                );
+                if let Some((block, _)) =
+                    proposal::decode(&cli.chain_id, &data, &self.local_peer_id)
+                {
+                    let difficulty = self.retarget.difficulty();
+                    match Miner::start(block, difficulty, self.mined_blocks.clone()) {
+                        Ok(started) => self.miner = Some(started),
+                        // this node sits the height out, the other miners still mine it
+                        Err(e) => warn!(error = %e, "failed to start mining"),
+                    }
+                }
+            }
+        } else if proposal::proposer(block_height, self.consensus.round(), validators)
+            == Some(&self.local_peer_id)
+        {
+            if let Some(candidate) = self.assembler.candidate(block_height, &self.state.ledger) {
+                let _consensus = self.round_span.enter();
+                info!(transactions = candidate.len(), "proposing the next block");
+                let timestamp = tip.next_timestamp(SystemTime::now());
+                let conflicting = cli
+                    .byzantine
+                    .and_then(|strategy| strategy.conflicting_block(&candidate))
+                    .map(|transactions| {
+                        proposal::encode(
+                            &cli.chain_id,
+                            &tip,
+                            self.consensus.round(),
+                            timestamp,
+                            transactions,
+                            &self.local_peer_id,
+                        )
+                    });
+                let data = proposal::encode(
+                    &cli.chain_id,
+                    &tip,
+                    self.consensus.round(),
+                    timestamp,
+                    candidate,
+                    &self.local_peer_id,
+                );
+                // gossipsub doesn't deliver our own messages, take the proposal like a received one
+                if let Some((proposed, _)) =
+                    proposal::decode(&cli.chain_id, &data, &self.local_peer_id)
+                {
+                    self.proposals
+                        .entry(block_height)
+                        .or_insert((self.local_peer_id, proposed));
+                }
+                for data in [data].into_iter().chain(conflicting) {
+                    if let Err(e) = self
+                        .swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .publish(self.topics.blocks.clone(), data)
+                    {
+                        self.metrics.publish_failed(&self.topics.blocks.hash());
+                        warn!(error = ?e, "failed to publish block proposal");
+                    }
+                }
+            }
+        }
+    }
+
+    // Validate the proposed block once all of its transactions reached our mempool, and vote for
+    // it if they check out.
+    pub(super) fn vote(&mut self, validators: &BTreeSet<PeerId>) {
+        let cli = self.cli;
+        let block_height = self.consensus.height();
+        if self.round_started.is_none() && self.proposals.contains_key(&block_height) {
+            self.round_started = Some(Instant::now());
+        }
+
+        if self.checked_proposal == Some(block_height) {
+            return;
+        }
+        let Some((proposer, proposed)) = self
+            .proposals
+            .get(&block_height)
+            .filter(|(_, proposed)| self.assembler.holds_all(proposed.transaction_ids()))
+        else {
+            return;
+        };
+        let _consensus = self.round_span.enter();
+        self.checked_proposal = Some(block_height);
+        info!(
+            transactions = proposed.transactions.len(),
+            "got every proposed transaction, validating"
+        );
+        let checked = if proposed.transactions.is_empty() {
+            Err("it has no transactions".to_string())
+        } else {
+            self.rules(validators)
+                .check(proposed, &proposed.hash, Some(proposer))
+                .map_err(|e| e.to_string())
+        };
+
+        // if the block checks out cast our vote for its hash
+        let tip = self.chain.tip();
+        if proposed.prev_hash != tip.hash {
+            self.alerts.fire(Alert {
+                kind: AlertKind::Fork,
+                height: block_height,
+                detail: format!(
+                    "proposed block follows {}, not our tip {}, not voting",
+                    hex::encode(proposed.prev_hash),
+                    hex::encode(tip.hash)
+                ),
+            });
+            return;
+        }
+        if let Err(e) = checked {
+            self.alerts.fire(Alert {
+                kind: AlertKind::FailedRound,
+                height: block_height,
+                detail: format!("the proposed block is invalid, {e}, not voting"),
+            });
+            return;
+        }
+        info!("the proposed block is valid, sending vote");
+        for id in proposed.transaction_ids() {
+            self.lifecycle.record(id, Milestone::Proposed);
+        }
+
+        let round = self.consensus.round();
+        for hash in vote_hashes(cli.byzantine, proposed.hash) {
+            debug!(round, block = %hex::encode(hash), "publishing vote");
+            let data = vote::encode(
+                &cli.chain_id,
+                block_height,
+                round,
+                &hash,
+                self.wallet.identity(),
+            );
+            // gossipsub doesn't deliver our own messages, count the vote right away
+            if self
+                .consensus
+                .record_vote(block_height, round, self.local_peer_id, hash)
+                == Recorded::Counted
+            {
+                self.signed_votes
+                    .insert((block_height, round, self.local_peer_id), data.clone());
+            }
+            if let Err(e) = self
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(self.topics.votes.clone(), data)
+            {
+                self.metrics.publish_failed(&self.topics.votes.hash());
+                warn!(error = ?e, "failed to publish vote");
+            }
+        }
+    }
+
+    // Publish the block our own miner found, unless the chain moved on in the meantime.
+    pub(super) fn on_mined(&mut self, block: Block) {
+        if block.prev_hash != self.chain.tip().hash {
+            debug!(
+                height = block.height,
+                "dropping a block mined on an old tip"
+            );
+            return;
+        }
+        let _consensus = self.round_span.enter();
+        info!(nonce = block.nonce, hash = %hex::encode(block.hash), "mined a block, publishing it");
+        let data = proposal::encode_mined(&self.cli.chain_id, &block, &self.local_peer_id);
+        if let Err(e) = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(self.topics.blocks.clone(), data)
+        {
+            self.metrics.publish_failed(&self.topics.blocks.hash());
+            warn!(error = ?e, "failed to publish mined block");
+        }
+        self.mined_block = Some(block);
+    }
+
+    // Start the round over once it has run out of time.
+    pub(super) fn on_round_check(&mut self) {
+        let cli = self.cli;
+        let block_height = self.consensus.height();
+        let round_timeout = Duration::from_secs(cli.round_timeout);
+        if self
+            .round_started
+            .is_none_or(|started| started.elapsed() < round_timeout)
+        {
+            return;
+        }
+        self.alerts.fire(Alert {
+            kind: AlertKind::FailedRound,
+            height: block_height,
+            detail: format!(
+                "round {} timed out with {} votes, starting over",
+                self.consensus.round(),
+                self.consensus.votes()
+            ),
+        });
+        let round = self.consensus.next_round();
+        self.round_span = consensus_round_span(block_height, round);
+        let _consensus = self.round_span.clone().entered();
+        // vote again once the proposal checks out, the votes of the last round are gone
+        self.checked_proposal = None;
+
+        // the next validator in turn proposes the block again, so peers that missed it get
+        // another chance at it, while everyone else waits for it from the new proposer
+        let our_turn = proposal::proposer(block_height, round, &self.validators())
+            == Some(&self.local_peer_id);
+        if !our_turn {
+            self.proposals.remove(&block_height);
+        } else if !self.proposals.contains_key(&block_height) {
+            self.assembler.reoffer();
+        }
+        // the round runs out on the new proposer too, if it never shows up
+        self.round_started = Some(Instant::now());
+        if let Some((_, proposed)) = self.proposals.get(&block_height) {
+            info!(round, "proposing the block again");
+            let data = proposal::encode(
+                &cli.chain_id,
+                &self.chain.tip(),
+                round,
+                proposed.timestamp,
+                &proposed.transactions,
+                &self.local_peer_id,
+            );
+            if let Err(e) = self
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(self.topics.blocks.clone(), data)
+            {
+                self.metrics.publish_failed(&self.topics.blocks.hash());
+                warn!(error = ?e, "failed to publish block proposal");
+            }
+        }
+    }
+
+    // A vote gossiped by `peer_id`, counted for whoever signed it.
+    pub(super) fn on_vote(
+        &mut self,
+        peer_id: PeerId,
+        id: &gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) {
+        let _consensus = self.round_span.clone().entered();
+        let block_height = self.consensus.height();
+        if message
+            .source
+            .is_some_and(|source| self.state.validator_keys.is_retired_peer(&source))
+        {
+            self.metrics.message_rejected(&message.topic, "retired_key");
+            warn!(voter = %peer_id, "ignoring vote from a retired validator key");
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        // the vote has to be signed by the message's signed author, and counts for it,
+        // not for whoever relayed it to us
+        let Some(Vote {
+            height,
+            round,
+            block_hash: block,
+            voter,
+        }) = message
+            .source
+            .and_then(|source| vote::decode(&self.cli.chain_id, &message.data, &source))
+        else {
+            self.metrics.message_rejected(&message.topic, "malformed");
+            warn!(%peer_id, "dropping vote without a height, block and voter's signature");
+            self.penalize(id, peer_id);
+            return;
+        };
+        network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Accept);
+        if height != block_height {
+            self.alerts.fire(Alert {
+                kind: AlertKind::Fork,
+                height: block_height,
+                detail: format!("{voter} voted for height {height}"),
+            });
+        } else if self
+            .proposals
+            .get(&height)
+            .is_some_and(|(_, proposed)| proposed.hash != block)
+        {
+            self.alerts.fire(Alert {
+                kind: AlertKind::Fork,
+                height: block_height,
+                detail: format!(
+                    "{voter} voted for block {}, not the one proposed",
+                    hex::encode(block)
+                ),
+            });
+        }
+        info!(%voter, height, round, "got a vote, storing the voter");
+        match self.consensus.record_vote(height, round, voter, block) {
+            Recorded::Counted => {
+                self.signed_votes
+                    .insert((height, round, voter), message.data);
+                record_audit(
+                    &mut self.audit_log,
+                    AuditEvent::VoteReceived {
+                        height,
+                        voter: voter.to_string(),
+                    },
+                );
+            }
+            Recorded::Conflicting(equivocation) => {
+                self.alerts.fire(Alert {
+                    kind: AlertKind::Equivocation,
+                    height,
+                    detail: format!(
+                        "{voter} voted for blocks {} and {} in round {round}",
+                        hex::encode(equivocation.first),
+                        hex::encode(equivocation.second)
+                    ),
+                });
+                // the signed votes prove it to anyone, not just to us
+                let first = self
+                    .signed_votes
+                    .get(&(height, round, voter))
+                    .map(hex::encode)
+                    .unwrap_or_default();
+                record_audit(
+                    &mut self.audit_log,
+                    AuditEvent::Equivocation {
+                        height,
+                        round,
+                        voter: voter.to_string(),
+                        votes: [first, hex::encode(&message.data)],
+                    },
+                );
+            }
+            Recorded::Ignored => {}
+        }
+    }
+
+    // A block proposed, or mined with `--consensus pow`, gossiped by `peer_id`.
+    pub(super) fn on_proposal(
+        &mut self,
+        peer_id: PeerId,
+        id: &gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) {
+        let _consensus = self.round_span.clone().entered();
+        let cli = self.cli;
+        let block_height = self.consensus.height();
+        let Some((proposer, (proposal, round))) = message.source.and_then(|source| {
+            Some((
+                source,
+                proposal::decode(&cli.chain_id, &message.data, &source)?,
+            ))
+        }) else {
+            self.metrics.message_rejected(&message.topic, "malformed");
+            warn!(%peer_id, "dropping malformed block proposal");
+            self.penalize(id, peer_id);
+            return;
+        };
+        if proposal.height == block_height {
+            check_clock(&proposal, &proposer, cli.max_clock_drift);
+        }
+        if cli.consensus == Mode::Pow {
+            if !pow::is_mined(&proposal, self.retarget.difficulty()) {
+                self.metrics
+                    .message_rejected(&message.topic, "insufficient_work");
+                warn!(%proposer, height = proposal.height, "dropping a block without enough work");
+                self.penalize(id, peer_id);
+                return;
+            }
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Accept);
+            if proposal.height > block_height {
+                self.catch_up(peer_id);
+            } else if proposal.height == block_height && proposal.prev_hash == self.chain.tip().hash
+            {
+                info!(miner = %proposer, height = proposal.height, "got a mined block");
+                self.mined_block = Some(proposal);
+            }
+            return;
+        }
+        if proposal.height == block_height && round < self.consensus.round() {
+            debug!(%proposer, round, "ignoring a proposal from a round that timed out");
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        // without staking epochs the validators are whoever is connected, peers may briefly
+        // disagree on whose turn it is then
+        if proposal::proposer(proposal.height, round, &self.validators()) != Some(&proposer) {
+            self.metrics
+                .message_rejected(&message.topic, "not_proposer");
+            warn!(%proposer, height = proposal.height, "ignoring block proposed out of turn");
+            network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Ignore);
+            return;
+        }
+        network::validate(&mut self.swarm, id, &peer_id, MessageAcceptance::Accept);
+        if proposal.height > block_height {
+            // the network moved on without us, fetch what we missed to vote again
+            self.catch_up(peer_id);
+        }
+        if proposal.height < block_height {
+            debug!(%proposer, height = proposal.height, "ignoring proposal for a committed height");
+            return;
+        }
+        if proposal.height == block_height && proposal.prev_hash != self.chain.tip().hash {
+            warn!(%proposer, height = proposal.height, prev_hash = %hex::encode(proposal.prev_hash), "ignoring a proposal that doesn't follow our tip");
+            return;
+        }
+        match self.proposals.get(&proposal.height) {
+            // a proposer gets one block per turn, a second one is an equivocation
+            Some((first_proposer, first))
+                if *first_proposer == proposer && first.hash != proposal.hash =>
+            {
+                self.alerts.fire(Alert {
+                    kind: AlertKind::Equivocation,
+                    height: proposal.height,
+                    detail: format!(
+                        "{proposer} proposed blocks {} and {} in round {round}",
+                        hex::encode(first.hash),
+                        hex::encode(proposal.hash)
+                    ),
+                });
+            }
+            Some((_, first)) if first.hash != proposal.hash => {
+                warn!(%proposer, height = proposal.height, "ignoring a second, different proposal for the height")
+            }
+            Some(_) => {}
+            None => {
+                info!(%proposer, height = proposal.height, transactions = proposal.transactions.len(), "got a block proposal");
+                self.proposals.insert(proposal.height, (proposer, proposal));
+            }
+        }
+    }
+}
+
+// Warn when the block a peer just made is timed further from the local clock than the drift
+// allowed, the local clock is likely off then and blocks will keep being rejected.
+fn check_clock(block: &Block, proposer: &PeerId, max_drift: u64) {
+    let now = block::unix_time(SystemTime::now());
+    if block.timestamp.abs_diff(now) > max_drift {
+        error!(
+            alert = "clock",
+            %proposer,
+            height = block.height,
+            block_time = block.timestamp,
+            local_time = now,
+            "the block is timed far from the local clock, check that this machine's clock is right"
+        );
+    }
+}
+
+// `block` with its transactions taken out of the mempool, hashed again from what it holds.
+fn reassemble(assembler: &mut BlockAssembler, block: Block) -> Block {
+    let Block {
+        height,
+        timestamp,
+        prev_hash,
+        transactions,
+        nonce,
+        votes,
+        ..
+    } = block;
+    let transactions = assembler.take_proposed(transactions);
+    let mut block = Block::new(height, timestamp, prev_hash, transactions);
+    block.nonce = nonce;
+    block.votes = votes;
+    block
+}
+
+// Blocks to vote for once the proposed `block` checks out.
+fn vote_hashes(byzantine: Option<byzantine::Strategy>, block: Hash) -> Vec<Hash> {
+    byzantine.map_or_else(|| vec![block], |strategy| strategy.vote_hashes(block))
+}
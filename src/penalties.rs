@@ -0,0 +1,44 @@
+use libp2p::PeerId;
+use std::collections::HashMap;
+
+/// Counts malformed messages per peer, so a peer that keeps sending garbage
+/// gets cut off instead of being dropped message by message forever.
+///
+/// Messages are only relayed once they pass validation, so whoever delivered
+/// a malformed message is the one who made it up.
+pub struct Penalties {
+    limit: u32,
+    strikes: HashMap<PeerId, u32>,
+}
+
+impl Penalties {
+    pub fn new(limit: u32) -> Self {
+        Penalties {
+            limit,
+            strikes: HashMap::new(),
+        }
+    }
+
+    /// Count a malformed message from `peer_id`. Returns `true` once the peer
+    /// reaches the limit, only the first time.
+    pub fn strike(&mut self, peer_id: PeerId) -> bool {
+        let strikes = self.strikes.entry(peer_id).or_default();
+        *strikes += 1;
+        *strikes == self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bans_once_at_the_limit() {
+        let mut penalties = Penalties::new(3);
+        let peer_id = PeerId::random();
+
+        let banned: Vec<_> = (0..5).map(|_| penalties.strike(peer_id)).collect();
+        assert_eq!(banned, [false, false, true, false, false]);
+        assert!(!penalties.strike(PeerId::random()));
+    }
+}
@@ -0,0 +1,228 @@
+//! Proof-of-work, the `--consensus pow` alternative to voting on proposals.
+//!
+//! A block is mined once its header, the block hash and a nonce, hashes
+//! below the target of the chain's difficulty: `2^(256 - difficulty)`, so
+//! the header hash starts with at least `difficulty` zero bits. Whoever
+//! finds a nonce first gossips the block, and every node that checks the
+//! work commits it without a vote.
+//!
+//! The difficulty follows how fast blocks come in, see [`Retarget`], so the
+//! chain keeps its pace however many laptops are mining.
+
+use futures::channel::mpsc::UnboundedSender;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::{io, thread};
+
+use crate::block::Block;
+use crate::merkle::Hash;
+
+// Prefix of the header hash, so it can't be mistaken for any other hash.
+const HEADER_HASH_CONTEXT: &[u8] = b"educoin/pow/v1\n";
+// Nonces tried between looking whether the miner was cancelled.
+const NONCES_PER_CHECK: u64 = 4096;
+// Most difficulty bits a retarget adds or takes away, every bit doubles or halves the work.
+const MAX_RETARGET_BITS: u32 = 2;
+
+/// Hash of the header of the block with `block_hash`, mined with `nonce`.
+pub fn header_hash(block_hash: &Hash, nonce: u64) -> Hash {
+    Sha256::new()
+        .chain_update(HEADER_HASH_CONTEXT)
+        .chain_update(block_hash)
+        .chain_update(nonce.to_be_bytes())
+        .finalize()
+        .into()
+}
+
+/// Whether `hash` is below the target of `difficulty`.
+pub fn meets(hash: &Hash, difficulty: u32) -> bool {
+    leading_zeros(hash) >= difficulty
+}
+
+/// Whether the nonce of `block` makes its header meet `difficulty`.
+pub fn is_mined(block: &Block, difficulty: u32) -> bool {
+    meets(&header_hash(&block.hash, block.nonce), difficulty)
+}
+
+fn leading_zeros(hash: &Hash) -> u32 {
+    let mut zeros = 0;
+    for byte in hash {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+/// Difficulty of the chain, recalculated every `interval` blocks from the
+/// timestamps of the blocks in the window just finished.
+///
+/// A window that took half the `block_time` per block or less gets a bit
+/// harder, one that took twice as long or more a bit easier, up to two bits
+/// at once. Every node replays the same timestamps,
+/// so they all agree on the difficulty of the next block.
+pub struct Retarget {
+    difficulty: u32,
+    interval: u32,
+    block_time: u64,
+    // timestamp of the first block of the current window
+    window_start: Option<u64>,
+}
+
+impl Retarget {
+    pub fn new(difficulty: u32, interval: u32, block_time: u64) -> Self {
+        Retarget {
+            difficulty,
+            interval: interval.max(2),
+            block_time,
+            window_start: None,
+        }
+    }
+
+    /// Difficulty the next block has to meet.
+    pub fn difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    /// Account for the block at `height` committed at `timestamp`, in
+    /// height order. Returns the new difficulty when a window ends.
+    pub fn record(&mut self, height: u32, timestamp: u64) -> Option<u32> {
+        match height % self.interval {
+            1 => self.window_start = Some(timestamp),
+            0 => {
+                let start = self.window_start.take()?;
+                let elapsed = timestamp.saturating_sub(start);
+                let expected = u64::from(self.interval - 1) * self.block_time;
+                self.difficulty = retarget(self.difficulty, elapsed, expected);
+                return Some(self.difficulty);
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+// `difficulty` for blocks that took `elapsed` seconds instead of `expected`.
+fn retarget(difficulty: u32, elapsed: u64, expected: u64) -> u32 {
+    let mut harder = 0;
+    while harder < MAX_RETARGET_BITS && elapsed << (harder + 1) <= expected {
+        harder += 1;
+    }
+    let mut easier = 0;
+    while easier < MAX_RETARGET_BITS && elapsed >= expected << (easier + 1) {
+        easier += 1;
+    }
+    (difficulty + harder).saturating_sub(easier).min(255)
+}
+
+/// Mines a block on a thread of its own, until it finds a nonce or is
+/// dropped, e.g. because another node's block came first.
+pub struct Miner {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Miner {
+    /// Start looking for a nonce that makes `block` meet `difficulty`, and
+    /// send the mined block to `found`.
+    pub fn start(
+        mut block: Block,
+        difficulty: u32,
+        found: UnboundedSender<Block>,
+    ) -> io::Result<Self> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancelled);
+        thread::Builder::new()
+            .name(format!("miner-{}", block.height))
+            .spawn(move || {
+                if let Some(nonce) = mine(&block.hash, difficulty, &flag) {
+                    block.nonce = nonce;
+                    let _ = found.unbounded_send(block);
+                }
+            })?;
+        Ok(Miner { cancelled })
+    }
+}
+
+impl Drop for Miner {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+// The first nonce that meets `difficulty`, or `None` once `cancelled` is set.
+fn mine(block_hash: &Hash, difficulty: u32, cancelled: &AtomicBool) -> Option<u64> {
+    let mut nonce = 0u64;
+    loop {
+        for _ in 0..NONCES_PER_CHECK {
+            if meets(&header_hash(block_hash, nonce), difficulty) {
+                return Some(nonce);
+            }
+            nonce = nonce.wrapping_add(1);
+        }
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    #[test]
+    fn counts_zero_bits_across_bytes() {
+        let mut hash = [0xff; 32];
+        assert!(meets(&hash, 0));
+        assert!(!meets(&hash, 1));
+
+        hash[0] = 0;
+        hash[1] = 0b0001_0000;
+        assert!(meets(&hash, 11));
+        assert!(!meets(&hash, 12));
+    }
+
+    #[test]
+    fn mined_blocks_meet_the_difficulty() {
+        let (found, mut mined) = mpsc::unbounded();
+        let _miner = Miner::start(Block::new(1, 60, [0; 32], Vec::new()), 8, found).unwrap();
+
+        let block = block_on(mined.next()).unwrap();
+        assert!(is_mined(&block, 8));
+        assert_eq!(block.hash, Block::new(1, 60, [0; 32], Vec::new()).hash);
+    }
+
+    #[test]
+    fn retargets_towards_the_block_time() {
+        // nine intervals of ten seconds are expected per window
+        let window = |retarget: &mut Retarget, start: u32, seconds: u64| {
+            retarget.record(start, 1_000);
+            (start + 1..start + 10)
+                .map(|height| retarget.record(height, 1_000 + seconds))
+                .last()
+                .flatten()
+        };
+        let mut retarget = Retarget::new(16, 10, 10);
+
+        assert_eq!(window(&mut retarget, 1, 90), Some(16));
+        assert_eq!(window(&mut retarget, 11, 45), Some(17));
+        assert_eq!(
+            window(&mut retarget, 21, 0),
+            Some(19),
+            "at most two bits at once"
+        );
+        assert_eq!(window(&mut retarget, 31, 200), Some(18));
+        assert_eq!(window(&mut retarget, 41, 1_000), Some(16));
+        assert_eq!(retarget.record(52, 1_000), None, "not a window's end");
+    }
+
+    #[test]
+    fn cancelled_miners_give_up() {
+        let cancelled = AtomicBool::new(true);
+        assert_eq!(mine(&[0; 32], 256, &cancelled), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Block proposals are protobuf `BlockProposal`s: the block the proposer
+//! wants committed at a height, with its transactions in block order, and
+//! the round of voting it was published in. Votes name the hash of the block
+//! they are for, so every node commits the same block.
+//!
+//! With `--consensus pow` the same messages carry mined blocks instead, with
+//! their nonce and the miner as proposer, see [`encode_mined`].
+//!
+//! Validators take turns proposing, round-robin by height and round over
+//! their peer ids in sorted order, so every node agrees on whose turn it is
+//! without talking about it, and a round that timed out moves on to the next
+//! validator instead of waiting on the same one again.
+
+use libp2p::PeerId;
+use prost::Message;
+use std::collections::BTreeSet;
+
+use crate::block::{Block, Tip};
+use crate::proto;
+use crate::transaction::Transaction;
+
+/// Propose the block of `transactions` at `timestamp` on top of `tip`, in
+/// `round` of voting on it.
+pub fn encode<'a>(
+    chain_id: &str,
+    tip: &Tip,
+    round: u32,
+    timestamp: u64,
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+    proposer: &PeerId,
+) -> Vec<u8> {
+    proto::BlockProposal {
+        chain_id: chain_id.to_string(),
+        height: tip.height + 1,
+        transactions: transactions
+            .into_iter()
+            .map(proto::Transaction::from)
+            .collect(),
+        proposer: proposer.to_bytes(),
+        timestamp,
+        prev_hash: tip.hash.to_vec(),
+        round,
+        nonce: 0,
+    }
+    .encode_to_vec()
+}
+
+/// Publish `block`, mined by `miner`, along with its nonce.
+pub fn encode_mined(chain_id: &str, block: &Block, miner: &PeerId) -> Vec<u8> {
+    proto::BlockProposal {
+        chain_id: chain_id.to_string(),
+        height: block.height,
+        transactions: block
+            .transactions
+            .iter()
+            .map(proto::Transaction::from)
+            .collect(),
+        proposer: miner.to_bytes(),
+        timestamp: block.timestamp,
+        prev_hash: block.prev_hash.to_vec(),
+        round: 0,
+        nonce: block.nonce,
+    }
+    .encode_to_vec()
+}
+
+/// The block proposed in `data` and the round it was proposed in, if it is a
+/// well-formed proposal by `proposer` on the chain `chain_id`. Signatures of
+/// the transactions are left to check.
+pub fn decode(chain_id: &str, data: &[u8], proposer: &PeerId) -> Option<(Block, u32)> {
+    let proposal = proto::BlockProposal::decode(data).ok()?;
+    if proposal.chain_id != chain_id || proposal.proposer != proposer.to_bytes() {
+        return None;
+    }
+
+    let transactions = proposal
+        .transactions
+        .into_iter()
+        .map(Transaction::try_from)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let mut block = Block::new(
+        proposal.height,
+        proposal.timestamp,
+        proposal.prev_hash.try_into().ok()?,
+        transactions,
+    );
+    block.nonce = proposal.nonce;
+    Some((block, proposal.round))
+}
+
+/// Proposer of the block at `height` in `round` among the `validators`, a round that timed out
+/// passes the turn on to the next one.
+pub fn proposer(height: u32, round: u32, validators: &BTreeSet<PeerId>) -> Option<&PeerId> {
+    let turn = (height as usize + round as usize).checked_rem(validators.len())?;
+    validators.iter().nth(turn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn proposals_round_trip_for_their_proposer_only() {
+        let keypair = Keypair::generate();
+        let transactions: Vec<_> = (0..3)
+            .map(|i| block_on(Transaction::sign(&keypair, "test", &[i], u64::from(i) + 1)).unwrap())
+            .collect();
+        let proposer = PeerId::random();
+        let tip = Tip {
+            height: 3,
+            hash: [9; 32],
+            timestamp: 50,
+        };
+
+        let data = encode("test", &tip, 2, 60, &transactions, &proposer);
+        let (block, round) = decode("test", &data, &proposer).unwrap();
+        assert_eq!(
+            (block.height, round, block.timestamp, block.prev_hash),
+            (4, 2, 60, tip.hash)
+        );
+        assert!(block
+            .transaction_ids()
+            .eq(transactions.iter().map(Transaction::id)));
+        assert!(decode("test", &data, &PeerId::random()).is_none());
+        assert!(decode("another-chain", &data, &proposer).is_none());
+
+        // the hash covers the order of the transactions
+        let reordered = Block::new(4, 60, tip.hash, transactions.into_iter().rev().collect());
+        assert_ne!(reordered.hash, block.hash);
+    }
+
+    #[test]
+    fn validators_take_turns_by_height_and_round() {
+        let validators: BTreeSet<_> = (0..3).map(|_| PeerId::random()).collect();
+        let sorted: Vec<_> = validators.iter().collect();
+        let proposers: Vec<_> = (1..=6)
+            .map(|height| proposer(height, 0, &validators).unwrap())
+            .collect();
+        assert_eq!(
+            proposers,
+            (1..=6).map(|height| sorted[height % 3]).collect::<Vec<_>>()
+        );
+
+        // a timed-out round hands the height to the next validator
+        assert_eq!(proposer(4, 1, &validators), Some(sorted[2]));
+        assert_eq!(proposer(4, 3, &validators), Some(sorted[1]));
+        assert_eq!(proposer(4, 0, &BTreeSet::new()), None);
+    }
+}
@@ -0,0 +1,151 @@
+use libp2p::gossipsub::{Message, MessageId, TopicHash};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// One inbound gossip message as it reached the node, a line of a recording.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    timestamp_ms: u128,
+    /// Milliseconds since the node started, replayed messages keep this pace.
+    offset_ms: u64,
+    propagation_source: String,
+    message_id: String,
+    source: Option<String>,
+    sequence_number: Option<u64>,
+    topic: String,
+    data: String,
+}
+
+/// Writes every inbound gossip message to a JSONL file, see `--record`.
+pub struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Recorder {
+            file: File::create(path)?,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        propagation_source: &PeerId,
+        message_id: &MessageId,
+        message: &Message,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            propagation_source: propagation_source.to_string(),
+            message_id: hex::encode(&message_id.0),
+            source: message.source.map(|source| source.to_string()),
+            sequence_number: message.sequence_number,
+            topic: message.topic.to_string(),
+            data: hex::encode(&message.data),
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+}
+
+/// A recorded message, ready to be fed back into a node.
+pub struct Recorded {
+    /// When the message arrived, relative to the start of the recording.
+    pub offset: Duration,
+    pub propagation_source: PeerId,
+    pub message_id: MessageId,
+    pub message: Message,
+}
+
+/// Read a recording made with [`Recorder`].
+pub fn load(path: &Path) -> Result<Vec<Recorded>, RecordingError> {
+    let contents = fs::read_to_string(path).map_err(RecordingError::Io)?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| parse_entry(line).ok_or(RecordingError::Malformed(index + 1)))
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Option<Recorded> {
+    let entry: Entry = serde_json::from_str(line).ok()?;
+
+    Some(Recorded {
+        offset: Duration::from_millis(entry.offset_ms),
+        propagation_source: entry.propagation_source.parse().ok()?,
+        message_id: MessageId::new(&hex::decode(entry.message_id).ok()?),
+        message: Message {
+            source: entry.source.map(|source| source.parse()).transpose().ok()?,
+            data: hex::decode(entry.data).ok()?,
+            sequence_number: entry.sequence_number,
+            topic: TopicHash::from_raw(entry.topic),
+        },
+    })
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    /// The line with this (1-based) number isn't a recorded message.
+    Malformed(usize),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "failed to read recording: {e}"),
+            RecordingError::Malformed(line) => {
+                write!(f, "line {line} of the recording is malformed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity;
+
+    #[test]
+    fn recordings_load_back_what_was_recorded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("gossip.jsonl");
+        let relay = PeerId::random();
+        let author = PeerId::from(identity::Keypair::generate_ed25519().public());
+        let message = Message {
+            source: Some(author),
+            data: b"1 vote".to_vec(),
+            sequence_number: Some(42),
+            topic: TopicHash::from_raw("vote"),
+        };
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder
+            .record(&relay, &MessageId::new(b"id"), &message)
+            .unwrap();
+        drop(recorder);
+
+        let recorded = load(&path).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].propagation_source, relay);
+        assert_eq!(recorded[0].message_id, MessageId::new(b"id"));
+        assert_eq!(recorded[0].message, message);
+    }
+}
@@ -0,0 +1,153 @@
+//! The payload of the transaction topic, a protobuf `TransactionMessage`.
+//!
+//! Gossipsub authenticates the node that published a message, the transaction
+//! inside names the key that signed it. Either the two are the same, or the
+//! key holder signed a delegation allowing that node to publish for it, which
+//! travels along with the transaction.
+//!
+//! Without this any node could publish transactions signed by others, e.g.
+//! ones it picked up on the network, as if they were its own.
+
+use bytes::Bytes;
+use libp2p::identity::{self, ed25519::PublicKey};
+use libp2p::PeerId;
+use prost::Message;
+use std::fmt;
+
+use crate::signer::{Signer, SignerError};
+use crate::transaction::{DecodeError, Transaction};
+use crate::{message, proto};
+
+// Prefix of the statement a key signs to delegate publishing to a node.
+const DELEGATION_CONTEXT: &[u8] = b"educoin/delegate/v1\n";
+const DELEGATION_LEN: usize = 64;
+
+/// Sign a delegation allowing `relay` to publish transactions signed by `signer`.
+pub async fn delegate(signer: &dyn Signer, relay: &PeerId) -> Result<Vec<u8>, SignerError> {
+    message::sign(signer, &statement(relay)).await
+}
+
+fn statement(relay: &PeerId) -> Vec<u8> {
+    [DELEGATION_CONTEXT, &relay.to_bytes()].concat()
+}
+
+/// A transaction as received on the transaction topic.
+pub struct Relayed {
+    pub transaction: Transaction,
+    pub delegation: Option<Bytes>,
+}
+
+impl Relayed {
+    /// Whether `publisher`, the gossipsub-authenticated author of the message,
+    /// was allowed to publish this transaction.
+    pub fn is_authorized_for(&self, publisher: &PeerId) -> bool {
+        is_authorized(
+            &self.transaction.public_key,
+            publisher,
+            self.delegation.as_deref(),
+        )
+    }
+}
+
+/// Whether `publisher` may publish transactions signed by `key`, given the
+/// key holder's `delegation` if there is one.
+pub fn is_authorized(key: &PublicKey, publisher: &PeerId, delegation: Option<&[u8]>) -> bool {
+    match delegation {
+        None => PeerId::from(identity::PublicKey::from(key.clone())) == *publisher,
+        Some(delegation) => message::verify(key, &statement(publisher), delegation),
+    }
+}
+
+pub fn encode(transaction: &Transaction, delegation: Option<&[u8]>) -> Vec<u8> {
+    proto::TransactionMessage {
+        transaction: Some(transaction.into()),
+        delegation: delegation.map(Bytes::copy_from_slice),
+    }
+    .encode_to_vec()
+}
+
+/// Decode the payload, the transaction shares the buffer like
+/// [`Transaction::decode`] does.
+pub fn decode(bytes: Bytes) -> Result<Relayed, RelayError> {
+    let message = proto::TransactionMessage::decode(bytes).map_err(RelayError::Malformed)?;
+    let transaction = message.transaction.ok_or(RelayError::MissingTransaction)?;
+    if let Some(delegation) = &message.delegation {
+        if delegation.len() != DELEGATION_LEN {
+            return Err(RelayError::InvalidDelegationLength(delegation.len()));
+        }
+    }
+
+    Ok(Relayed {
+        transaction: transaction.try_into().map_err(RelayError::Transaction)?,
+        delegation: message.delegation,
+    })
+}
+
+#[derive(Debug)]
+pub enum RelayError {
+    Malformed(prost::DecodeError),
+    MissingTransaction,
+    InvalidDelegationLength(usize),
+    Transaction(DecodeError),
+}
+
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::Malformed(e) => write!(f, "transaction message is malformed: {e}"),
+            RelayError::MissingTransaction => write!(f, "transaction message is empty"),
+            RelayError::InvalidDelegationLength(len) => {
+                write!(f, "delegation is {len} bytes instead of {DELEGATION_LEN}")
+            }
+            RelayError::Transaction(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RelayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[async_std::test]
+    async fn only_the_key_holder_or_its_delegate_may_publish() {
+        let (key, node) = (Keypair::generate(), Keypair::generate());
+        let node_id = PeerId::from(identity::PublicKey::from(node.public()));
+        let key_id = PeerId::from(identity::PublicKey::from(key.public()));
+        let transaction = Transaction::sign(&key, "test", b"send 00 1", 1)
+            .await
+            .unwrap();
+
+        let own = decode(encode(&transaction, None).into()).unwrap();
+        assert!(own.is_authorized_for(&key_id));
+        assert!(!own.is_authorized_for(&node_id));
+
+        let delegation = delegate(&key, &node_id).await.unwrap();
+        let relayed = decode(encode(&transaction, Some(&delegation)).into()).unwrap();
+        assert_eq!(relayed.transaction.id(), transaction.id());
+        assert!(relayed.is_authorized_for(&node_id));
+        assert!(!relayed.is_authorized_for(&PeerId::random()));
+    }
+
+    #[async_std::test]
+    async fn rejects_incomplete_payloads() {
+        assert!(matches!(
+            decode(Bytes::new()),
+            Err(RelayError::MissingTransaction)
+        ));
+        assert!(matches!(
+            decode(Bytes::from_static(&[0x0a, 5, 1])),
+            Err(RelayError::Malformed(_))
+        ));
+
+        let transaction = Transaction::sign(&Keypair::generate(), "test", b"send 00 1", 1)
+            .await
+            .unwrap();
+        assert!(matches!(
+            decode(encode(&transaction, Some(&[1, 2])).into()),
+            Err(RelayError::InvalidDelegationLength(2))
+        ));
+    }
+}
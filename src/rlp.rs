@@ -0,0 +1,139 @@
+//! RLP, Ethereum's canonical encoding, for blocks and transactions, so the
+//! workshop can set ours next to a real chain's and check the encoder
+//! against Ethereum's own vectors. Only built with the `rlp` feature.
+//!
+//! A transaction is the list `[public key, signature, data]` and a block
+//! `[height, merkle root, [transaction, ...]]`.
+
+use crate::block;
+use crate::transaction::Transaction;
+
+/// Encoding of a block of `transactions` committed at `height`, with the
+/// same merkle root as [`block::Block::merkle_root`].
+pub fn encode_block<'a>(
+    height: u32,
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+) -> Vec<u8> {
+    let transactions: Vec<_> = transactions.into_iter().collect();
+    let root = block::merkle_root(transactions.iter().copied());
+    let encoded_transactions: Vec<u8> = transactions
+        .into_iter()
+        .flat_map(encode_transaction)
+        .collect();
+
+    let mut payload = Vec::new();
+    encode_uint(&mut payload, height.into());
+    encode_bytes(&mut payload, &root);
+    encode_list(&mut payload, &encoded_transactions);
+    let mut block = Vec::new();
+    encode_list(&mut block, &payload);
+    block
+}
+
+pub fn encode_transaction(transaction: &Transaction) -> Vec<u8> {
+    let mut payload = Vec::new();
+    encode_bytes(&mut payload, &transaction.public_key.to_bytes());
+    encode_bytes(&mut payload, &transaction.signature);
+    encode_bytes(&mut payload, &transaction.data);
+    let mut encoded = Vec::new();
+    encode_list(&mut encoded, &payload);
+    encoded
+}
+
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    // a single byte below 0x80 is its own encoding
+    if let [byte @ 0..=0x7f] = bytes {
+        out.push(*byte);
+        return;
+    }
+    encode_length(out, bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+}
+
+/// Append a list whose items are already encoded in `payload`.
+fn encode_list(out: &mut Vec<u8>, payload: &[u8]) {
+    encode_length(out, payload.len(), 0xc0);
+    out.extend_from_slice(payload);
+}
+
+// Integers are big endian without leading zeros, so zero is the empty string.
+fn encode_uint(out: &mut Vec<u8>, value: u64) {
+    let bytes = value.to_be_bytes();
+    let leading_zeros = (value.leading_zeros() / 8) as usize;
+    encode_bytes(out, &bytes[leading_zeros..]);
+}
+
+// Up to 55 bytes the length is added to `offset`, longer ones follow it as
+// a big endian integer whose own length is added to `offset + 55`.
+fn encode_length(out: &mut Vec<u8>, length: usize, offset: u8) {
+    if length <= 55 {
+        out.push(offset + length as u8);
+        return;
+    }
+    let bytes = (length as u64).to_be_bytes();
+    let length_bytes = &bytes[(length as u64).leading_zeros() as usize / 8..];
+    out.push(offset + 55 + length_bytes.len() as u8);
+    out.extend_from_slice(length_bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: &[u8]) -> String {
+        let mut out = Vec::new();
+        encode_bytes(&mut out, value);
+        hex::encode(out)
+    }
+
+    fn uint(value: u64) -> String {
+        let mut out = Vec::new();
+        encode_uint(&mut out, value);
+        hex::encode(out)
+    }
+
+    fn list(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_list(&mut out, &items.concat());
+        out
+    }
+
+    // from ethereum/tests, RLPTests/rlptest.json
+    #[test]
+    fn matches_the_ethereum_vectors() {
+        assert_eq!(bytes(b""), "80");
+        assert_eq!(bytes(b"\x00"), "00");
+        assert_eq!(bytes(b"\x7f"), "7f");
+        assert_eq!(bytes(b"\x80"), "8180");
+        assert_eq!(bytes(b"dog"), "83646f67");
+        assert_eq!(
+            bytes(b"Lorem ipsum dolor sit amet, consectetur adipisicing eli"),
+            "b74c6f72656d20697073756d20646f6c6f722073697420616d65742c20636f6e7365637465747572206164697069736963696e6720656c69"
+        );
+        assert_eq!(
+            bytes(b"Lorem ipsum dolor sit amet, consectetur adipisicing elit"),
+            "b8384c6f72656d20697073756d20646f6c6f722073697420616d65742c20636f6e7365637465747572206164697069736963696e6720656c6974"
+        );
+        assert_eq!(&bytes(&[b'a'; 1024])[..6], "b90400");
+
+        assert_eq!(uint(0), "80");
+        assert_eq!(uint(1), "01");
+        assert_eq!(uint(127), "7f");
+        assert_eq!(uint(128), "8180");
+        assert_eq!(uint(1000), "8203e8");
+        assert_eq!(uint(100000), "830186a0");
+
+        let encoded = |value: &[u8]| hex::decode(bytes(value)).unwrap();
+        assert_eq!(hex::encode(list(&[])), "c0");
+        assert_eq!(
+            hex::encode(list(&[encoded(b"dog"), encoded(b"god"), encoded(b"cat")])),
+            "cc83646f6783676f6483636174"
+        );
+        // [ [ [], [] ], [] ]
+        let empty = list(&[]);
+        assert_eq!(
+            hex::encode(list(&[list(&[empty.clone(), empty.clone()]), empty])),
+            "c4c2c0c0c0"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! A JSON-RPC endpoint for scripts and tools that can't type into the
+//! node's terminal, served with `--rpc-listen`. Each request is a line of
+//! JSON such as `{"id": 1, "method": "balance", "params": ["alice"]}`,
+//! answered by a line holding the same `id` and either a `result` or an
+//! `error`. The node answers the calls from its event loop, so they see the
+//! state the terminal commands see.
+//!
+//! Like the NATS bridge it speaks plain TCP, without TLS or auth, so it is
+//! meant to listen on localhost only.
+
+use futures::channel::mpsc::UnboundedSender;
+use futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use tracing::{debug, info, warn};
+
+/// A request for the node to answer on `reply`.
+#[derive(Debug)]
+pub struct Call {
+    pub method: String,
+    pub params: Vec<String>,
+    pub reply: oneshot::Sender<Result<Value, String>>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Response {
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Listen on `address`, sending every request to `calls`. Returns the
+/// address listened on, the port filled in when `address` left it to the
+/// system.
+pub fn listen(address: SocketAddr, calls: UnboundedSender<Call>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    thread::Builder::new()
+        .name("rpc-listener".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!(error = %e, "failed to accept an RPC connection");
+                        continue;
+                    }
+                };
+                let calls = calls.clone();
+                let spawned = thread::Builder::new()
+                    .name("rpc-connection".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve(stream, &calls) {
+                            debug!(error = %e, "RPC connection closed");
+                        }
+                    });
+                if let Err(e) = spawned {
+                    warn!(error = %e, "failed to serve an RPC connection");
+                }
+            }
+        })?;
+    info!(%address, "serving JSON-RPC");
+    Ok(address)
+}
+
+// Answer the requests of one connection until it closes, or the node stops.
+fn serve(stream: TcpStream, calls: &UnboundedSender<Call>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (reply, answer) = oneshot::channel();
+                let call = Call {
+                    method: request.method,
+                    params: request.params,
+                    reply,
+                };
+                if calls.unbounded_send(call).is_err() {
+                    return Ok(());
+                }
+                let Ok(answer) = futures::executor::block_on(answer) else {
+                    return Ok(());
+                };
+                let (result, error) = match answer {
+                    Ok(result) => (Some(result), None),
+                    Err(error) => (None, Some(error)),
+                };
+                Response {
+                    id: request.id,
+                    result,
+                    error,
+                }
+            }
+            Err(e) => Response {
+                id: Value::Null,
+                result: None,
+                error: Some(format!("invalid request: {e}")),
+            },
+        };
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+        writer.write_all(&response)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[test]
+    fn answers_each_request_line() {
+        let (calls, mut received) = mpsc::unbounded();
+        let address = listen("127.0.0.1:0".parse().unwrap(), calls).unwrap();
+        thread::spawn(move || {
+            futures::executor::block_on(async {
+                while let Some(call) = received.next().await {
+                    let answer = match call.method.as_str() {
+                        "echo" => Ok(json!(call.params)),
+                        method => Err(format!("unknown method `{method}`")),
+                    };
+                    let _ = call.reply.send(answer);
+                }
+            })
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut call = |request: &str| {
+            writer.write_all(format!("{request}\n").as_bytes()).unwrap();
+            serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            call(r#"{"id": 1, "method": "echo", "params": ["alice"]}"#),
+            json!({"id": 1, "result": ["alice"]})
+        );
+        assert_eq!(
+            call(r#"{"id": "two", "method": "mine"}"#),
+            json!({"id": "two", "error": "unknown method `mine`"})
+        );
+        let malformed = call("balance alice");
+        assert!(malformed["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+    }
+}
@@ -0,0 +1,242 @@
+//! Spending conditions on locked coins, a first taste of programmability.
+//!
+//! `lock <recipient> <amount> <script>` takes coins out of the sender's
+//! balance and holds them under the id of the locking transaction, until a
+//! `claim <lock id> [<witness>...]` transaction meets the script and the
+//! coins go to the recipient. Both are checked when the block holding them
+//! is executed, see [`crate::ledger::Ledger`].
+//!
+//! A script is one or more clauses joined by `and`:
+//!
+//! - `after <height>` — the claim only counts in a block at that height or later (a time lock)
+//! - `hash <sha256 hex>` — the claim reveals a preimage with `preimage <hex>` (a hash lock)
+//! - `multisig <m> <address>,<address>,...` — the claim carries `sig <address> <signature>`
+//!   witnesses from at least `m` of the addresses, each signing the message
+//!   `claim <lock id>` with `sign-message`
+
+use libp2p::identity::ed25519::PublicKey;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::address::Address;
+use crate::message;
+use crate::transaction::TransactionId;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script(Vec<Clause>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Clause {
+    After(u32),
+    Hash([u8; 32]),
+    Multisig {
+        required: usize,
+        signers: Vec<Address>,
+    },
+}
+
+/// What a claim offers to meet a script.
+#[derive(Debug, Default)]
+pub struct Witness {
+    preimage: Option<Vec<u8>>,
+    signatures: Vec<(Address, Vec<u8>)>,
+}
+
+impl Script {
+    /// Whether a claim of the lock `lock_id` with `witness`, executed at
+    /// `height`, meets every clause.
+    pub fn check(&self, height: u32, lock_id: &TransactionId, witness: &Witness) -> bool {
+        self.0.iter().all(|clause| match clause {
+            Clause::After(after) => height >= *after,
+            Clause::Hash(digest) => witness
+                .preimage
+                .as_ref()
+                .is_some_and(|preimage| Sha256::digest(preimage)[..] == digest[..]),
+            Clause::Multisig { required, signers } => {
+                let message = claim_message(lock_id);
+                let signed: HashSet<_> = witness
+                    .signatures
+                    .iter()
+                    .filter(|(signer, signature)| {
+                        signers.contains(signer)
+                            && PublicKey::try_from_bytes(signer.as_bytes()).is_ok_and(|key| {
+                                message::verify(&key, message.as_bytes(), signature)
+                            })
+                    })
+                    .map(|(signer, _)| signer)
+                    .collect();
+                signed.len() >= *required
+            }
+        })
+    }
+}
+
+/// The message `multisig` signers sign to release the lock `lock_id`.
+pub fn claim_message(lock_id: &TransactionId) -> String {
+    format!("claim {lock_id}")
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut clauses = Vec::new();
+        for clause in s.split(" and ") {
+            let words: Vec<_> = clause.split_whitespace().collect();
+            let clause = match words[..] {
+                ["after", height] => Clause::After(height.parse().map_err(|_| invalid(clause))?),
+                ["hash", digest] => Clause::Hash(
+                    hex::decode(digest)
+                        .ok()
+                        .and_then(|digest| digest.try_into().ok())
+                        .ok_or_else(|| invalid(clause))?,
+                ),
+                ["multisig", required, signers] => {
+                    let required: usize = required.parse().map_err(|_| invalid(clause))?;
+                    let signers = signers
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<Vec<Address>, _>>()
+                        .map_err(|_| invalid(clause))?;
+                    if required == 0 || required > signers.len() {
+                        return Err(invalid(clause));
+                    }
+                    Clause::Multisig { required, signers }
+                }
+                _ => return Err(invalid(clause)),
+            };
+            clauses.push(clause);
+        }
+
+        Ok(Script(clauses))
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, clause) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " and ")?;
+            }
+            match clause {
+                Clause::After(height) => write!(f, "after {height}")?,
+                Clause::Hash(digest) => write!(f, "hash {}", hex::encode(digest))?,
+                Clause::Multisig { required, signers } => {
+                    let signers: Vec<_> = signers.iter().map(Address::to_string).collect();
+                    write!(f, "multisig {required} {}", signers.join(","))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Witness {
+    type Err = ScriptError;
+
+    /// Parse `preimage <hex>` and `sig <address> <signature hex>` items.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut witness = Witness::default();
+        let mut words = s.split_whitespace();
+        while let Some(word) = words.next() {
+            let hex_arg = |arg: Option<&str>| {
+                arg.and_then(|arg| hex::decode(arg).ok())
+                    .ok_or_else(|| invalid(word))
+            };
+            match word {
+                "preimage" => witness.preimage = Some(hex_arg(words.next())?),
+                "sig" => {
+                    let signer = words
+                        .next()
+                        .and_then(|signer| signer.parse().ok())
+                        .ok_or_else(|| invalid(word))?;
+                    witness.signatures.push((signer, hex_arg(words.next())?));
+                }
+                _ => return Err(invalid(word)),
+            }
+        }
+
+        Ok(witness)
+    }
+}
+
+fn invalid(part: &str) -> ScriptError {
+    ScriptError(part.trim().to_string())
+}
+
+#[derive(Debug)]
+pub struct ScriptError(String);
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't make sense of `{}` in a script", self.0)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn checks_time_and_hash_locks() {
+        let lock_id: TransactionId = hex::encode([1; 32]).parse().unwrap();
+        let digest = hex::encode(Sha256::digest(b"open sesame"));
+        let script: Script = format!("after 5 and hash {digest}").parse().unwrap();
+
+        let witness: Witness = format!("preimage {}", hex::encode("open sesame"))
+            .parse()
+            .unwrap();
+        assert!(!script.check(4, &lock_id, &witness), "too early");
+        assert!(script.check(5, &lock_id, &witness));
+        let wrong: Witness = format!("preimage {}", hex::encode("guess"))
+            .parse()
+            .unwrap();
+        assert!(!script.check(5, &lock_id, &wrong));
+        assert!(!script.check(5, &lock_id, &Witness::default()));
+    }
+
+    #[test]
+    fn checks_m_of_n_signatures() {
+        let lock_id: TransactionId = hex::encode([2; 32]).parse().unwrap();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate()).collect();
+        let addresses: Vec<_> = keys
+            .iter()
+            .map(|key| Address::from(&key.public()))
+            .collect();
+        let list = addresses
+            .iter()
+            .map(Address::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let script: Script = format!("multisig 2 {list}").parse().unwrap();
+
+        let sig = |index: usize| {
+            let signature = block_on(message::sign(
+                &keys[index],
+                claim_message(&lock_id).as_bytes(),
+            ))
+            .unwrap();
+            format!("sig {} {} ", addresses[index], hex::encode(signature))
+        };
+        let one: Witness = sig(0).parse().unwrap();
+        assert!(!script.check(1, &lock_id, &one));
+        let same_twice: Witness = (sig(0) + &sig(0)).parse().unwrap();
+        assert!(!script.check(1, &lock_id, &same_twice));
+        let two: Witness = (sig(0) + &sig(2)).parse().unwrap();
+        assert!(script.check(1, &lock_id, &two));
+
+        // signatures for another lock don't count
+        let other: TransactionId = hex::encode([3; 32]).parse().unwrap();
+        assert!(!script.check(1, &other, &two));
+
+        assert_eq!(script.to_string().parse::<Script>().unwrap(), script);
+        assert!(format!("multisig 4 {list}").parse::<Script>().is_err());
+        assert!("after soon".parse::<Script>().is_err());
+    }
+}
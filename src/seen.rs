@@ -0,0 +1,121 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const SEEN_FILE: &str = "seen.txt";
+
+/// Ids of the gossip messages processed most recently, kept in the data dir
+/// so a message delivered again after a restart or reconnect is recognised.
+///
+/// Gossipsub only remembers message ids for a minute and forgets them when
+/// the node stops. The file holds one hex id per line and is compacted to
+/// the newest `capacity` ids once it gets twice that long.
+pub struct SeenCache {
+    path: PathBuf,
+    file: File,
+    capacity: usize,
+    order: VecDeque<Vec<u8>>,
+    ids: HashSet<Vec<u8>>,
+    lines: usize,
+}
+
+impl SeenCache {
+    /// Open the cache in `data_dir`, starting empty if none was saved yet.
+    pub fn open(data_dir: &Path, capacity: usize) -> io::Result<Self> {
+        let path = data_dir.join(SEEN_FILE);
+        let mut cache = SeenCache {
+            file: OpenOptions::new().create(true).append(true).open(&path)?,
+            path,
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            ids: HashSet::new(),
+            lines: 0,
+        };
+
+        // a line cut short by a crash is simply skipped
+        for id in fs::read_to_string(&cache.path)?
+            .lines()
+            .filter_map(|line| hex::decode(line).ok())
+        {
+            cache.remember(id);
+        }
+        cache.compact()?;
+        Ok(cache)
+    }
+
+    /// Record `id` as seen. Returns `false` if it was seen before.
+    pub fn insert(&mut self, id: &[u8]) -> io::Result<bool> {
+        if self.ids.contains(id) {
+            return Ok(false);
+        }
+
+        self.remember(id.to_vec());
+        writeln!(self.file, "{}", hex::encode(id))?;
+        self.lines += 1;
+        if self.lines >= 2 * self.capacity {
+            self.compact()?;
+        }
+        Ok(true)
+    }
+
+    fn remember(&mut self, id: Vec<u8>) {
+        if !self.ids.insert(id.clone()) {
+            return;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+
+    // Rewrite the file with only the ids still remembered.
+    fn compact(&mut self) -> io::Result<()> {
+        let contents: String = self
+            .order
+            .iter()
+            .map(|id| format!("{}\n", hex::encode(id)))
+            .collect();
+        fs::write(&self.path, contents)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.lines = self.order.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_ids_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = SeenCache::open(dir.path(), 10).unwrap();
+        assert!(cache.insert(b"first").unwrap());
+        assert!(!cache.insert(b"first").unwrap());
+        drop(cache);
+
+        let mut cache = SeenCache::open(dir.path(), 10).unwrap();
+        assert!(!cache.insert(b"first").unwrap());
+        assert!(cache.insert(b"second").unwrap());
+    }
+
+    #[test]
+    fn forgets_the_oldest_ids_beyond_capacity() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = SeenCache::open(dir.path(), 3).unwrap();
+        for id in 0..10u8 {
+            assert!(cache.insert(&[id]).unwrap());
+        }
+        drop(cache);
+
+        let lines = fs::read_to_string(dir.path().join(SEEN_FILE)).unwrap();
+        assert!(lines.lines().count() < 6, "the file is compacted");
+
+        let mut cache = SeenCache::open(dir.path(), 3).unwrap();
+        assert!(!cache.insert(&[9]).unwrap());
+        assert!(cache.insert(&[0]).unwrap());
+    }
+}
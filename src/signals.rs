@@ -0,0 +1,26 @@
+use futures::channel::mpsc;
+use futures::stream::{BoxStream, StreamExt};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use signal_hook::low_level;
+use std::{io, thread};
+
+/// SIGINT and SIGTERM, yielded once the first of them arrives so the node
+/// can shut down cleanly. A second one kills the process right away, in case
+/// shutting down hangs.
+pub fn termination() -> io::Result<BoxStream<'static, ()>> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let (requests, shutdown) = mpsc::unbounded();
+    thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(move || {
+            let mut signals = signals.forever();
+            if signals.next().is_some() {
+                let _ = requests.unbounded_send(());
+            }
+            if let Some(signal) = signals.next() {
+                let _ = low_level::emulate_default_handler(signal);
+            }
+        })?;
+    Ok(shutdown.boxed())
+}
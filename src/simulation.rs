@@ -0,0 +1,27 @@
+//! Seeded stand-ins for the node's randomness, so a scenario can be replayed
+//! with the same identities and therefore the same signatures, message ids
+//! and transaction ids.
+//!
+//! Time isn't simulated: round timeouts, gossipsub's heartbeats and message
+//! delivery all run on the real clock and executor, so the order in which
+//! messages arrive can still differ from one run to the next.
+
+use libp2p::identity::{self, ed25519};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+const SEED_CONTEXT: &[u8] = b"educoin/simulation-seed";
+
+/// Identity key of the `node`-th node of a simulation seeded with `seed`.
+pub fn identity_keypair(seed: u64, node: u64) -> identity::Keypair {
+    let mut hasher = Sha256::new();
+    hasher.update(SEED_CONTEXT);
+    hasher.update(seed.to_be_bytes());
+    hasher.update(node.to_be_bytes());
+    let mut secret_bytes = Zeroizing::new(<[u8; 32]>::from(hasher.finalize()));
+
+    // any 32 bytes form a valid ed25519 secret key
+    let secret = ed25519::SecretKey::try_from_bytes(&mut *secret_bytes)
+        .expect("32 bytes are always a valid ed25519 secret key");
+    ed25519::Keypair::from(secret).into()
+}
@@ -0,0 +1,148 @@
+use libp2p::PeerId;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::consensus::Consensus;
+use crate::merkle::Hash;
+use crate::transaction::{self, Transaction};
+
+const SNAPSHOT_FILE: &str = "snapshot.txt";
+
+/// What a node was in the middle of when it was shut down: the round being
+/// voted on, the votes collected for it and the transactions still waiting
+/// in the mempool.
+///
+/// Saved in the data dir as `height <h>`, `round <r>`, `vote <h> <r> <peer
+/// id> <block hash>` and `transaction <hex>` lines, and removed again once
+/// the next start has read it, so a later crash never restores stale state.
+/// Snapshots from before rounds are read as round 0.
+pub struct Snapshot {
+    pub consensus: Consensus,
+    pub mempool: Vec<Transaction>,
+}
+
+/// Write the state of a node shutting down to `data_dir`.
+pub fn save<'a>(
+    data_dir: &Path,
+    consensus: &Consensus,
+    mempool: impl IntoIterator<Item = &'a Transaction>,
+) -> io::Result<()> {
+    let mut contents = format!(
+        "height {}\nround {}\n",
+        consensus.height(),
+        consensus.round()
+    );
+    for (height, round, voter, block) in consensus.recorded_votes() {
+        contents += &format!("vote {height} {round} {voter} {}\n", hex::encode(block));
+    }
+    for transaction in mempool {
+        contents += &format!("transaction {}\n", hex::encode(transaction.to_bytes()));
+    }
+
+    // written aside first, a half written snapshot would be worse than none
+    let path = data_dir.join(SNAPSHOT_FILE);
+    let partial = path.with_extension("partial");
+    fs::write(&partial, contents)?;
+    fs::rename(partial, path)
+}
+
+/// Read and remove the snapshot in `data_dir`, if the node was shut down
+/// cleanly last time.
+pub fn take(data_dir: &Path) -> io::Result<Option<Snapshot>> {
+    let path = data_dir.join(SNAPSHOT_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut height = None;
+    let mut round = 0;
+    let mut votes = Vec::new();
+    let mut mempool = Vec::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let parsed = match line.split_once(' ') {
+            Some(("height", value)) => value.parse().ok().map(|value| height = Some(value)),
+            Some(("round", value)) => value.parse().ok().map(|value| round = value),
+            Some(("vote", vote)) => parse_vote(vote).map(|vote| votes.push(vote)),
+            Some(("transaction", blob)) => transaction::from_hex(blob)
+                .ok()
+                .map(|transaction| mempool.push(transaction)),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(malformed(line));
+        }
+    }
+    let height = height.ok_or_else(|| malformed("no height"))?;
+
+    fs::remove_file(path)?;
+    Ok(Some(Snapshot {
+        consensus: Consensus::resume(height, round, votes),
+        mempool,
+    }))
+}
+
+// `<height> <round> <peer id> <block hash>`, or without the round
+fn parse_vote(vote: &str) -> Option<(u32, u32, PeerId, Hash)> {
+    let (height, round, voter, block) = match vote.split(' ').collect::<Vec<_>>()[..] {
+        [height, round, voter, block] => (height, round.parse().ok()?, voter, block),
+        [height, voter, block] => (height, 0, voter, block),
+        _ => return None,
+    };
+    Some((
+        height.parse().ok()?,
+        round,
+        voter.parse().ok()?,
+        hex::decode(block).ok()?.try_into().ok()?,
+    ))
+}
+
+fn malformed(detail: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed snapshot: {detail}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn restores_the_round_and_mempool_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let voter = PeerId::random();
+        let mut consensus = Consensus::default();
+        consensus.commit();
+        consensus.record_vote(2, 0, voter, [2; 32]);
+        consensus.next_round();
+        consensus.record_vote(2, 1, voter, [2; 32]);
+        consensus.record_vote(3, 0, voter, [3; 32]);
+        let transaction = block_on(Transaction::sign(
+            &Keypair::generate(),
+            "test",
+            b"pending",
+            1,
+        ))
+        .unwrap();
+
+        save(dir.path(), &consensus, std::slice::from_ref(&transaction)).unwrap();
+        let snapshot = take(dir.path()).unwrap().unwrap();
+        assert_eq!(
+            (snapshot.consensus.height(), snapshot.consensus.round()),
+            (2, 1)
+        );
+        assert_eq!(
+            snapshot.consensus.recorded_votes().collect::<Vec<_>>(),
+            vec![(2, 1, voter, [2; 32]), (3, 0, voter, [3; 32])]
+        );
+        assert_eq!(snapshot.mempool.len(), 1);
+        assert_eq!(snapshot.mempool[0].id(), transaction.id());
+
+        assert!(take(dir.path()).unwrap().is_none(), "read only once");
+    }
+}
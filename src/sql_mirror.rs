@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::address::Address;
+use crate::cid;
+use crate::genesis::Genesis;
+use crate::ledger::{Ledger, Transfer};
+use crate::merkle::Hash;
+use crate::transaction::Transaction;
+
+const MIRROR_FILE: &str = "chain.sql";
+
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS blocks (
+    height INTEGER PRIMARY KEY,
+    merkle_root TEXT NOT NULL,
+    committed_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    id TEXT PRIMARY KEY,
+    height INTEGER NOT NULL REFERENCES blocks (height),
+    position INTEGER NOT NULL,
+    sender TEXT NOT NULL,
+    recipient TEXT,
+    amount INTEGER,
+    data BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS balances (
+    address TEXT PRIMARY KEY,
+    balance INTEGER NOT NULL
+);
+DELETE FROM transactions;
+DELETE FROM blocks;
+DELETE FROM balances;
+";
+
+/// SQL script in the data dir that mirrors the chain into SQLite, for
+/// running ad-hoc queries over it: `sqlite3 chain.db < chain.sql`.
+///
+/// The script has a `blocks`, a `transactions` and a `balances` table
+/// (addresses in hex, hashes as CIDs). Each committed block appends a
+/// transaction of statements to it. It starts over with every run of the
+/// node, and so does the database it's loaded into, then gets the stored
+/// chain again as the node replays it, see [`crate::state`].
+pub struct SqlMirror {
+    file: File,
+}
+
+impl SqlMirror {
+    pub fn create(data_dir: &Path, genesis: &Genesis) -> io::Result<Self> {
+        let mut mirror = SqlMirror {
+            file: File::create(data_dir.join(MIRROR_FILE))?,
+        };
+        let mut statements = SCHEMA.to_string();
+        let ledger = Ledger::with_genesis(genesis);
+        for (address, _) in &genesis.allocations {
+            upsert_balance(&mut statements, address, &ledger);
+        }
+        mirror.file.write_all(statements.as_bytes())?;
+        Ok(mirror)
+    }
+
+    /// Append the block committed at `height`, and the `changed` balances
+    /// once `ledger` has applied it.
+    pub fn record_block(
+        &mut self,
+        height: u32,
+        committed_at: SystemTime,
+        merkle_root: &Hash,
+        transactions: &[Transaction],
+        changed: &BTreeSet<Address>,
+        ledger: &Ledger,
+    ) -> io::Result<()> {
+        let committed_at = committed_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut statements = format!(
+            "BEGIN;\nINSERT INTO blocks VALUES ({height}, '{}', {committed_at});\n",
+            cid::encode(merkle_root)
+        );
+
+        for (position, transaction) in transactions.iter().enumerate() {
+            let sender = Address::from(&transaction.public_key);
+            let transfer = Transfer::parse(&transaction.data);
+            let (recipient, amount) = match &transfer {
+                Some(transfer) => (
+                    format!("'{}'", transfer.recipient),
+                    transfer.amount.to_string(),
+                ),
+                None => ("NULL".to_string(), "NULL".to_string()),
+            };
+            writeln!(
+                statements,
+                "INSERT INTO transactions VALUES ('{}', {height}, {position}, '{sender}', {recipient}, {amount}, X'{}');",
+                transaction.id(),
+                hex::encode(&transaction.data)
+            )
+            .expect("writing to a String never fails");
+        }
+        for address in changed {
+            upsert_balance(&mut statements, address, ledger);
+        }
+        statements += "COMMIT;\n";
+
+        self.file.write_all(statements.as_bytes())
+    }
+}
+
+fn upsert_balance(statements: &mut String, address: &Address, ledger: &Ledger) {
+    writeln!(
+        statements,
+        "INSERT INTO balances VALUES ('{address}', {}) ON CONFLICT (address) DO UPDATE SET balance = excluded.balance;",
+        ledger.balance(address)
+    )
+    .expect("writing to a String never fails");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+    use std::fs;
+
+    #[test]
+    fn mirrors_blocks_and_balances() {
+        let dir = tempfile::tempdir().unwrap();
+        let sender = Keypair::generate();
+        let (from, to) = (
+            Address::from(&sender.public()),
+            Address::from(&Keypair::generate().public()),
+        );
+        let genesis = Genesis::with_allocations(&[(from, 100)]);
+        let mut mirror = SqlMirror::create(dir.path(), &genesis).unwrap();
+
+        let sent = format!("send {to} 40");
+        let transaction = block_on(Transaction::sign(&sender, "test", sent.as_bytes(), 1)).unwrap();
+        let mut ledger = Ledger::with_genesis(&genesis);
+        let block = [transaction];
+        let changed = ledger.apply(1, &block);
+        mirror
+            .record_block(1, UNIX_EPOCH, &[7; 32], &block, &changed, &ledger)
+            .unwrap();
+
+        let script = fs::read_to_string(dir.path().join(MIRROR_FILE)).unwrap();
+        let statements: Vec<_> = script
+            .lines()
+            .skip_while(|line| *line != "BEGIN;")
+            .collect();
+        assert_eq!(
+            statements[1],
+            format!(
+                "INSERT INTO blocks VALUES (1, '{}', 0);",
+                cid::encode(&[7; 32])
+            )
+        );
+        assert_eq!(
+            statements[2],
+            format!(
+                "INSERT INTO transactions VALUES ('{}', 1, 0, '{from}', '{to}', 40, X'{}');",
+                block[0].id(),
+                hex::encode(sent)
+            )
+        );
+        assert!(statements
+            .iter()
+            .any(|line| line.starts_with(&format!("INSERT INTO balances VALUES ('{from}', 60)"))));
+        assert!(statements
+            .iter()
+            .any(|line| line.starts_with(&format!("INSERT INTO balances VALUES ('{to}', 40)"))));
+        assert_eq!(statements.last(), Some(&"COMMIT;"));
+    }
+}
@@ -0,0 +1,219 @@
+use libp2p::identity::{self, ed25519};
+use libp2p::PeerId;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::address::Address;
+
+/// Blocks unbonded stake stays locked for when the genesis doesn't say.
+pub const DEFAULT_UNBONDING_PERIOD: u32 = 10;
+
+/// A change of the sender's stake, carried in transaction data as
+/// `bond <amount>` or `unbond <amount>`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StakeChange {
+    /// Move coins from the balance into the stake.
+    Bond(u64),
+    /// Take coins out of the stake, back into the balance once the
+    /// unbonding period has passed.
+    Unbond(u64),
+}
+
+impl StakeChange {
+    pub fn parse(data: &[u8]) -> Option<StakeChange> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.split_whitespace();
+
+        match (words.next(), words.next(), words.next()) {
+            (Some("bond"), Some(amount), None) => Some(StakeChange::Bond(amount.parse().ok()?)),
+            (Some("unbond"), Some(amount), None) => Some(StakeChange::Unbond(amount.parse().ok()?)),
+            _ => None,
+        }
+    }
+}
+
+/// Coins on their way out of a stake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unbonding {
+    pub amount: u64,
+    /// Height of the first block whose balances include the coins again.
+    pub release_height: u32,
+}
+
+/// Stake bonded by each address, which gives validators their weight, and
+/// the stake being unbonded.
+///
+/// Unbonding coins count as neither stake nor balance until their release
+/// height, so a validator can't misbehave and walk away with its stake in
+/// the same block.
+#[derive(Debug, Clone, Default)]
+pub struct Stakes {
+    bonded: BTreeMap<Address, u64>,
+    unbonding: BTreeMap<Address, Vec<Unbonding>>,
+    unbonding_period: u32,
+}
+
+impl Stakes {
+    pub fn new(unbonding_period: u32) -> Self {
+        Stakes {
+            unbonding_period,
+            ..Stakes::default()
+        }
+    }
+
+    pub fn stake(&self, address: &Address) -> u64 {
+        self.bonded.get(address).copied().unwrap_or_default()
+    }
+
+    /// Addresses with any stake bonded, with their stake.
+    pub fn bonded(&self) -> impl Iterator<Item = (&Address, u64)> {
+        self.bonded.iter().map(|(address, stake)| (address, *stake))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.bonded.values().sum()
+    }
+
+    pub fn unbonding(&self, address: &Address) -> &[Unbonding] {
+        self.unbonding.get(address).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn bond(&mut self, address: Address, amount: u64) {
+        let stake = self.bonded.entry(address).or_default();
+        *stake = stake.saturating_add(amount);
+    }
+
+    /// Start unbonding `amount` of the stake of `address` in the block at
+    /// `height`, unless it has less bonded.
+    pub fn unbond(&mut self, address: Address, amount: u64, height: u32) -> bool {
+        let stake = self.stake(&address);
+        if stake < amount {
+            return false;
+        }
+        if stake == amount {
+            self.bonded.remove(&address);
+        } else {
+            self.bonded.insert(address, stake - amount);
+        }
+
+        self.unbonding.entry(address).or_default().push(Unbonding {
+            amount,
+            release_height: height.saturating_add(self.unbonding_period),
+        });
+        true
+    }
+
+    /// Move everything `from` has bonded or is unbonding over to `to`.
+    pub fn move_stake(&mut self, from: &Address, to: Address) {
+        if let Some(stake) = self.bonded.remove(from) {
+            self.bond(to, stake);
+        }
+        if let Some(unbonding) = self.unbonding.remove(from) {
+            self.unbonding.entry(to).or_default().extend(unbonding);
+        }
+    }
+
+    /// Take out the unbonding coins released by the block at `height`.
+    pub fn release(&mut self, height: u32) -> Vec<(Address, u64)> {
+        let mut released = Vec::new();
+        self.unbonding.retain(|address, unbonding| {
+            unbonding.retain(|entry| {
+                let due = entry.release_height <= height;
+                if due {
+                    released.push((*address, entry.amount));
+                }
+                !due
+            });
+            !unbonding.is_empty()
+        });
+        released
+    }
+}
+
+/// Validators whose votes count towards a quorum: the nodes of the addresses
+/// with stake bonded at the last epoch boundary, taken every `epoch_length`
+/// blocks so the set doesn't change in the middle of voting. Bonding is what
+/// registers a validator, unbonding all of its stake what retires it.
+///
+/// An address is the node's identity key, so its peer id follows from it.
+#[derive(Debug, Clone)]
+pub struct ValidatorSet {
+    epoch_length: u32,
+    validators: BTreeSet<PeerId>,
+}
+
+impl ValidatorSet {
+    pub fn new(epoch_length: u32) -> Self {
+        ValidatorSet {
+            epoch_length: epoch_length.max(1),
+            validators: BTreeSet::new(),
+        }
+    }
+
+    /// Take the validators from `stakes` if the block at `height` ends an
+    /// epoch. Returns whether it did.
+    pub fn update(&mut self, height: u32, stakes: &Stakes) -> bool {
+        if !height.is_multiple_of(self.epoch_length) {
+            return false;
+        }
+        self.validators = stakes
+            .bonded()
+            .filter_map(|(address, _)| peer_id(address))
+            .collect();
+        true
+    }
+
+    /// Let the node of `new_key` take the place of the node of `old_key`
+    /// right away, as a rotated key moves its stake with it.
+    pub fn rotate(&mut self, old_key: &Address, new_key: &Address) {
+        let Some(old) = peer_id(old_key) else {
+            return;
+        };
+        if self.validators.remove(&old) {
+            self.validators.extend(peer_id(new_key));
+        }
+    }
+
+    /// Empty until an epoch ends with stake bonded.
+    pub fn validators(&self) -> &BTreeSet<PeerId> {
+        &self.validators
+    }
+
+    pub fn is_validator(&self, peer_id: &PeerId) -> bool {
+        self.validators.contains(peer_id)
+    }
+}
+
+/// Peer id of the node whose identity key is `address`.
+pub fn peer_id(address: &Address) -> Option<PeerId> {
+    let public_key = ed25519::PublicKey::try_from_bytes(address.as_bytes()).ok()?;
+    Some(identity::PublicKey::from(public_key).to_peer_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validators_change_at_epoch_boundaries_only() {
+        let keypair = ed25519::Keypair::generate();
+        let address = Address::from(&keypair.public());
+        let peer = identity::PublicKey::from(keypair.public()).to_peer_id();
+        let mut stakes = Stakes::new(DEFAULT_UNBONDING_PERIOD);
+        let mut validators = ValidatorSet::new(5);
+
+        stakes.bond(address, 10);
+        assert!(!validators.update(3, &stakes));
+        assert!(!validators.is_validator(&peer));
+        assert!(validators.update(5, &stakes));
+        assert!(validators.is_validator(&peer));
+
+        stakes.unbond(address, 10, 6);
+        validators.update(7, &stakes);
+        assert!(
+            validators.is_validator(&peer),
+            "in office until the epoch ends"
+        );
+        validators.update(10, &stakes);
+        assert!(validators.validators().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Everything a node derives from the committed chain: balances, the
+//! transaction index, names, governance, validator keys and the validator
+//! set. It all starts from genesis and follows the blocks one by one, as
+//! they get committed and, when the node starts, from the stored chain.
+
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::assembler::BlockAssembler;
+use crate::block::Block;
+use crate::genesis::Genesis;
+use crate::governance::{Governance, ParameterChange};
+use crate::history::TransactionIndex;
+use crate::ledger::Ledger;
+use crate::names::NameRegistry;
+use crate::sql_mirror::SqlMirror;
+use crate::staking::ValidatorSet;
+use crate::validators::ValidatorKeys;
+
+pub struct ChainState {
+    pub ledger: Ledger,
+    /// With staking epochs only, see [`crate::cli::Cli::epoch_length`].
+    pub validator_set: Option<ValidatorSet>,
+    pub transaction_index: TransactionIndex,
+    pub validator_keys: ValidatorKeys,
+    pub governance: Governance,
+    pub names: NameRegistry,
+    pub sql_mirror: Option<SqlMirror>,
+}
+
+impl ChainState {
+    /// The state at genesis, nothing committed yet.
+    pub fn new(
+        genesis: &Genesis,
+        epoch_length: Option<u32>,
+        sql_mirror: Option<SqlMirror>,
+    ) -> Self {
+        ChainState {
+            ledger: Ledger::with_genesis(genesis),
+            validator_set: epoch_length.map(ValidatorSet::new),
+            transaction_index: TransactionIndex::default(),
+            validator_keys: ValidatorKeys::default(),
+            governance: Governance::default(),
+            names: NameRegistry::default(),
+            sql_mirror,
+        }
+    }
+
+    /// Apply the committed `block`, the one on top of what was applied so
+    /// far. Parameter changes that pass governance go to `assembler`, which
+    /// drops the transactions whose nonce the block used up.
+    pub fn apply(&mut self, block: Block, assembler: &mut BlockAssembler) {
+        let Block {
+            height,
+            timestamp,
+            transactions,
+            merkle_root,
+            ..
+        } = block;
+
+        let mut changed_balances = self.ledger.apply(height, &transactions);
+        // a rotated key takes its coins, stake and seat among the validators along
+        for (old_key, new_key) in self.validator_keys.apply(&transactions) {
+            self.ledger.move_account(&old_key, new_key);
+            if let Some(set) = &mut self.validator_set {
+                set.rotate(&old_key, &new_key);
+            }
+            changed_balances.extend([old_key, new_key]);
+        }
+        if let Some(set) = &mut self.validator_set {
+            if set.update(height, self.ledger.stakes()) {
+                info!(
+                    validators = set.validators().len(),
+                    "staking epoch ended, registered its validators"
+                );
+            }
+        }
+        self.names.apply(&transactions);
+        for outcome in self
+            .governance
+            .apply(height, &transactions, self.ledger.stakes())
+        {
+            if !outcome.passed {
+                info!(proposal = %outcome.proposal, change = %outcome.change, "proposal rejected");
+                continue;
+            }
+            info!(proposal = %outcome.proposal, change = %outcome.change, "proposal passed, applying it");
+            match outcome.change {
+                ParameterChange::BlockSize(size) => assembler.set_block_size(size),
+            }
+        }
+
+        let committed_at = UNIX_EPOCH + Duration::from_secs(timestamp);
+        if let Some(mirror) = &mut self.sql_mirror {
+            if let Err(e) = mirror.record_block(
+                height,
+                committed_at,
+                &merkle_root,
+                &transactions,
+                &changed_balances,
+                &self.ledger,
+            ) {
+                warn!(error = %e, "failed to mirror block into chain.sql");
+            }
+        }
+        self.transaction_index
+            .insert_block(height, committed_at, transactions);
+        assembler.drop_stale(&self.ledger);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Address;
+    use crate::staking;
+    use crate::transaction::Transaction;
+    use crate::validators::KeyRotation;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn rotated_keys_take_their_coins_and_seat_along() {
+        let (old, new) = (Keypair::generate(), Keypair::generate());
+        let (old_key, new_key) = (Address::from(&old.public()), Address::from(&new.public()));
+        let genesis = Genesis::with_allocations(&[(old_key, 100)]);
+        let mut state = ChainState::new(&genesis, Some(1), None);
+        let mut assembler = BlockAssembler::with_capacity(0);
+        let sign = |data: &str, nonce| {
+            block_on(Transaction::sign(&old, "test", data.as_bytes(), nonce)).unwrap()
+        };
+
+        state.apply(
+            Block::new(1, 60, [0; 32], vec![sign("bond 40", 1)]),
+            &mut assembler,
+        );
+        let rotation = block_on(KeyRotation::data(&old.public(), &new)).unwrap();
+        state.apply(
+            Block::new(2, 61, [0; 32], vec![sign(&rotation, 2)]),
+            &mut assembler,
+        );
+
+        let (ledger, stakes) = (&state.ledger, state.ledger.stakes());
+        assert_eq!(
+            (ledger.balance(&old_key), ledger.balance(&new_key)),
+            (0, 60)
+        );
+        assert_eq!((stakes.stake(&old_key), stakes.stake(&new_key)), (0, 40));
+        let set = state.validator_set.as_ref().unwrap();
+        assert!(set.is_validator(&staking::peer_id(&new_key).unwrap()));
+        assert!(!set.is_validator(&staking::peer_id(&old_key).unwrap()));
+    }
+}
@@ -0,0 +1,539 @@
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use lru::LruCache;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc as std_mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::block::{Block, Chain, Tip};
+use crate::merkle::Hash;
+use crate::transaction;
+
+const CHAIN_FILE: &str = "chain.log";
+
+// Blocks kept decoded after they are read back, for peers catching up, who all ask for
+// the same recent blocks.
+const BLOCK_CACHE: usize = 256;
+
+// How often blocks that failed to reach the disk are written again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Append the record of `block` to `buffer`: `height`, `timestamp`,
+/// `prev_hash` and `hash` lines, a `nonce` line for mined blocks, a
+/// `transaction <hex>` line for each transaction in block order, then a
+/// `vote <hex>` line for each vote the block was committed with.
+pub fn encode_block(buffer: &mut String, block: &Block) {
+    let mut write = || -> std::fmt::Result {
+        writeln!(buffer, "height {}", block.height)?;
+        writeln!(buffer, "timestamp {}", block.timestamp)?;
+        writeln!(buffer, "prev_hash {}", hex::encode(block.prev_hash))?;
+        writeln!(buffer, "hash {}", hex::encode(block.hash))?;
+        if block.nonce != 0 {
+            writeln!(buffer, "nonce {}", block.nonce)?;
+        }
+        for transaction in &block.transactions {
+            writeln!(
+                buffer,
+                "transaction {}",
+                hex::encode(transaction.to_bytes())
+            )?;
+        }
+        for vote in &block.votes {
+            writeln!(buffer, "vote {}", hex::encode(vote))?;
+        }
+        Ok(())
+    };
+    write().expect("writing to a String never fails");
+}
+
+/// The block in a record written by [`encode_block`], if it is complete and
+/// its hash matches its contents.
+pub fn decode_block(record: &str) -> Option<Block> {
+    let mut lines = record.lines();
+    let mut field = |name: &str| {
+        let (key, value) = lines.next()?.split_once(' ')?;
+        (key == name).then_some(value)
+    };
+    let height = field("height")?.parse().ok()?;
+    let timestamp = field("timestamp")?.parse().ok()?;
+    let prev_hash = parse_hash(field("prev_hash")?)?;
+    let hash = parse_hash(field("hash")?)?;
+
+    let mut lines = lines.filter(|line| !line.is_empty()).peekable();
+    let nonce = match lines.peek().and_then(|line| line.strip_prefix("nonce ")) {
+        Some(nonce) => {
+            lines.next();
+            nonce.parse().ok()?
+        }
+        None => 0,
+    };
+    let (mut transactions, mut votes) = (Vec::new(), Vec::new());
+    for line in lines {
+        match line.split_once(' ')? {
+            ("transaction", blob) if votes.is_empty() => {
+                transactions.push(transaction::from_hex(blob).ok()?)
+            }
+            ("vote", vote) => votes.push(hex::decode(vote).ok()?),
+            _ => return None,
+        }
+    }
+    let mut block = Block::new(height, timestamp, prev_hash, transactions);
+    block.nonce = nonce;
+    block.votes = votes;
+    (block.hash == hash).then_some(block)
+}
+
+fn parse_hash(hex: &str) -> Option<Hash> {
+    hex::decode(hex).ok()?.try_into().ok()
+}
+
+/// A block on its way to the disk.
+struct PendingBlock {
+    height: u32,
+    contents: String,
+}
+
+/// What the writer reports back about the blocks handed to it.
+pub enum Ack {
+    /// The block is on disk. Carries its buffer back so the next block can reuse it.
+    Written { height: u32, buffer: String },
+    /// A write failed, the writer keeps the block and retries until the disk accepts it.
+    Failing { height: u32 },
+}
+
+/// The committed chain on disk, one record per block appended to
+/// `chain.log`, and an index of where each block's record sits by height and
+/// by block hash.
+///
+/// Records are separated by an empty line. Opening the store reads the whole
+/// file back and checks that every block follows the one before it, from the
+/// genesis block on. A record cut short by a crash ends the chain there and
+/// is cut off the file.
+///
+/// Blocks are written on a writer thread of its own, in the order they were
+/// stored, so a slow or failing disk never holds up the event loop. A failed
+/// write (disk full, read-only filesystem, ...) doesn't stop the node: the
+/// writer keeps the block queued in memory and retries every few seconds,
+/// and the store reports itself unhealthy until everything it was handed is
+/// written. The store only learns how the writes went from the [`Ack`]s,
+/// which have to be passed to [`BlockStore::acknowledge`].
+pub struct BlockStore {
+    path: PathBuf,
+    blocks: std_mpsc::Sender<PendingBlock>,
+    // every stored block, the one at height 1 first
+    records: Vec<Record>,
+    heights: HashMap<Hash, u32>,
+    // where the next record goes
+    end: u64,
+    // stored but not acknowledged as written yet
+    unwritten: usize,
+    failing: bool,
+    // the contents of the last block written, reused to encode the next one
+    spare: String,
+    // decoded blocks by height, the ones read most recently
+    cache: LruCache<u32, Arc<Block>>,
+}
+
+// Where a block's record sits in the chain file.
+struct Record {
+    hash: Hash,
+    timestamp: u64,
+    offset: u64,
+    len: u64,
+}
+
+impl BlockStore {
+    /// Open the chain stored in `dir`, empty if nothing was stored yet.
+    pub fn open(dir: &Path) -> io::Result<(Self, UnboundedReceiver<Ack>)> {
+        let path = dir.join(CHAIN_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let (mut records, mut heights, mut end) = (Vec::new(), HashMap::new(), 0);
+        let mut tip = Block::genesis().hash;
+        while let Some(len) = contents[end as usize..]
+            .find("\n\n")
+            .map(|at| at as u64 + 2)
+        {
+            let height = records.len() as u32 + 1;
+            let record = &contents[end as usize..(end + len) as usize];
+            let Some(block) = decode_block(record) else {
+                break;
+            };
+            if block.height != height || block.prev_hash != tip {
+                break;
+            }
+            tip = block.hash;
+            heights.insert(block.hash, height);
+            records.push(Record {
+                hash: block.hash,
+                timestamp: block.timestamp,
+                offset: end,
+                len,
+            });
+            end += len;
+        }
+        if end < contents.len() as u64 {
+            warn!(
+                alert = "storage",
+                blocks = records.len(),
+                "chain file ends in a broken block, cutting it off"
+            );
+            OpenOptions::new().write(true).open(&path)?.set_len(end)?;
+        }
+        info!(blocks = records.len(), "opened the stored chain");
+
+        let (blocks, queue) = std_mpsc::channel();
+        let (acks, acknowledged) = mpsc::unbounded();
+        let writer = BlockWriter {
+            path: path.clone(),
+            written: end,
+            pending: VecDeque::new(),
+            acks,
+            failing: false,
+        };
+        thread::Builder::new()
+            .name("block-writer".to_string())
+            .spawn(move || writer.run(queue))?;
+
+        let store = BlockStore {
+            path,
+            blocks,
+            records,
+            heights,
+            end,
+            unwritten: 0,
+            failing: false,
+            spare: String::new(),
+            cache: LruCache::new(NonZeroUsize::new(BLOCK_CACHE).expect("the cache holds blocks")),
+        };
+        Ok((store, acknowledged))
+    }
+
+    /// The last block stored, written or not.
+    pub fn tip(&self) -> Tip {
+        let height = self.records.len() as u32;
+        match self.records.last() {
+            Some(record) => Tip {
+                height,
+                hash: record.hash,
+                timestamp: record.timestamp,
+            },
+            None => Chain::new().tip(),
+        }
+    }
+
+    /// Height of the stored block with `hash`.
+    pub fn height_of(&self, hash: &Hash) -> Option<u32> {
+        self.heights.get(hash).copied()
+    }
+
+    /// The block at `height`, from the blocks read back most recently or else
+    /// from disk, see [`BlockStore::read_block`].
+    pub fn block(&mut self, height: u32) -> io::Result<Option<Arc<Block>>> {
+        if let Some(block) = self.cache.get(&height) {
+            return Ok(Some(block.clone()));
+        }
+        let Some(block) = self.read_block(height)? else {
+            return Ok(None);
+        };
+        let block = Arc::new(block);
+        self.cache.put(height, block.clone());
+        Ok(Some(block))
+    }
+
+    /// The block at `height`, read back from disk. `None` for blocks never
+    /// stored and for those still waiting to be written.
+    pub fn read_block(&self, height: u32) -> io::Result<Option<Block>> {
+        if height == 0 {
+            return Ok(Some(Block::genesis()));
+        }
+        let written = self.records.len() - self.unwritten;
+        let Some(record) = self.records[..written].get(height as usize - 1) else {
+            return Ok(None);
+        };
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(record.offset))?;
+        let mut contents = String::new();
+        file.take(record.len).read_to_string(&mut contents)?;
+        decode_block(&contents)
+            .map(Some)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "stored block is corrupt"))
+    }
+
+    /// Hand `block` to the writer. It has to follow the last block stored.
+    pub fn store(&mut self, block: &Block) {
+        let height = block.height;
+        let tip = self.tip();
+        if height != tip.height + 1 || block.prev_hash != tip.hash {
+            error!(
+                alert = "storage",
+                height,
+                stored = tip.height,
+                "block doesn't follow the stored chain, not storing it"
+            );
+            return;
+        }
+
+        let mut contents = std::mem::take(&mut self.spare);
+        contents.clear();
+        encode_block(&mut contents, block);
+        contents.push('\n');
+        let len = contents.len() as u64;
+
+        if self.blocks.send(PendingBlock { height, contents }).is_err() {
+            error!(
+                alert = "storage",
+                height, "block writer is gone, block not written"
+            );
+            self.failing = true;
+        }
+        self.heights.insert(block.hash, height);
+        self.records.push(Record {
+            hash: block.hash,
+            timestamp: block.timestamp,
+            offset: self.end,
+            len,
+        });
+        self.end += len;
+        self.unwritten += 1;
+    }
+
+    pub fn acknowledge(&mut self, ack: Ack) {
+        match ack {
+            Ack::Written { height, buffer } => {
+                debug!(height, "block write acknowledged");
+                self.unwritten = self.unwritten.saturating_sub(1);
+                self.spare = buffer;
+                if self.unwritten == 0 {
+                    self.failing = false;
+                }
+            }
+            Ack::Failing { height } => {
+                debug!(height, "block write failed");
+                self.failing = true;
+            }
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        !self.failing
+    }
+
+    /// Number of blocks waiting to be written.
+    pub fn pending(&self) -> usize {
+        self.unwritten
+    }
+}
+
+/// The writer thread's side of a [`BlockStore`].
+struct BlockWriter {
+    path: PathBuf,
+    // length of the file up to the last record written in full
+    written: u64,
+    pending: VecDeque<PendingBlock>,
+    acks: UnboundedSender<Ack>,
+    failing: bool,
+}
+
+impl BlockWriter {
+    fn run(mut self, queue: std_mpsc::Receiver<PendingBlock>) {
+        loop {
+            // wait for the next block, but only until the next retry while some are stuck
+            let next = if self.pending.is_empty() {
+                queue
+                    .recv()
+                    .map_err(|_| std_mpsc::RecvTimeoutError::Disconnected)
+            } else {
+                queue.recv_timeout(RETRY_INTERVAL)
+            };
+            match next {
+                Ok(block) => self.pending.push_back(block),
+                Err(std_mpsc::RecvTimeoutError::Timeout) => {}
+                // the node is gone, nothing is left to acknowledge to
+                Err(std_mpsc::RecvTimeoutError::Disconnected) => {
+                    self.flush();
+                    return;
+                }
+            }
+            // take whatever else arrived meanwhile, so it's written in the same pass
+            self.pending.extend(queue.try_iter());
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        while let Some(block) = self.pending.front() {
+            match self.write(block) {
+                Ok(()) => {
+                    info!(height = block.height, file = %self.path.display(), "wrote block to disk");
+                    if let Some(block) = self.pending.pop_front() {
+                        self.written += block.contents.len() as u64;
+                        let _ = self.acks.unbounded_send(Ack::Written {
+                            height: block.height,
+                            buffer: block.contents,
+                        });
+                    }
+                }
+                Err(e) => {
+                    warn!(height = block.height, error = %e, "failed to write block to disk");
+                    if !self.failing {
+                        error!(
+                            alert = "storage",
+                            pending = self.pending.len(),
+                            "block storage is failing, queueing blocks in memory"
+                        );
+                        self.failing = true;
+                    }
+                    let _ = self.acks.unbounded_send(Ack::Failing {
+                        height: block.height,
+                    });
+                    return;
+                }
+            }
+        }
+        if self.failing {
+            info!(alert = "storage", "block storage recovered, queue flushed");
+            self.failing = false;
+        }
+    }
+
+    fn write(&self, block: &PendingBlock) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // a half written record would break every record after it, drop it before retrying
+        if let Err(e) = file
+            .write_all(block.contents.as_bytes())
+            .and_then(|()| file.sync_data())
+        {
+            let _ = file.set_len(self.written);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    fn chain_of(length: u32) -> Vec<Block> {
+        let mut chain: Vec<Block> = Vec::new();
+        for height in 1..=length {
+            let prev_hash = chain
+                .last()
+                .map_or(Block::genesis().hash, |block| block.hash);
+            chain.push(Block::new(
+                height,
+                60 + u64::from(height),
+                prev_hash,
+                Vec::new(),
+            ));
+        }
+        chain
+    }
+
+    #[test]
+    fn acknowledges_blocks_once_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, mut acks) = BlockStore::open(dir.path()).unwrap();
+
+        let mut chain = chain_of(2);
+        chain[1].votes = vec![vec![1, 2], vec![3]];
+        for block in &chain {
+            store.store(block);
+        }
+        assert_eq!(store.pending(), 2);
+        assert!(store.block(2).unwrap().is_none(), "not written yet");
+
+        for height in [1, 2] {
+            let ack = block_on(acks.next()).unwrap();
+            assert!(matches!(ack, Ack::Written { height: written, .. } if written == height));
+            store.acknowledge(ack);
+        }
+        assert_eq!(store.pending(), 0);
+        assert!(store.is_healthy());
+        let stored = store.block(2).unwrap().unwrap();
+        assert_eq!(stored.prev_hash, chain[0].hash);
+        assert_eq!(stored.votes, chain[1].votes);
+        assert_eq!(store.height_of(&chain[1].hash), Some(2));
+    }
+
+    #[test]
+    fn serves_blocks_read_back_from_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, mut acks) = BlockStore::open(dir.path()).unwrap();
+        let chain = chain_of(1);
+        store.store(&chain[0]);
+        store.acknowledge(block_on(acks.next()).unwrap());
+
+        let read = store.block(1).unwrap().unwrap();
+        fs::write(dir.path().join(CHAIN_FILE), "").unwrap();
+        assert!(store.read_block(1).is_err(), "the disk copy is gone");
+        let cached = store.block(1).unwrap().unwrap();
+        assert!(Arc::ptr_eq(&read, &cached));
+        assert_eq!(cached.hash, chain[0].hash);
+    }
+
+    #[test]
+    fn reopens_the_chain_up_to_the_last_whole_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CHAIN_FILE);
+        let chain = chain_of(3);
+        let mut contents = String::new();
+        for block in &chain {
+            encode_block(&mut contents, block);
+            contents.push('\n');
+        }
+        // the last block was cut short
+        contents.truncate(contents.len() - 10);
+        fs::write(&path, &contents).unwrap();
+
+        let (mut store, mut acks) = BlockStore::open(dir.path()).unwrap();
+        assert_eq!(
+            store.tip(),
+            Tip {
+                height: 2,
+                hash: chain[1].hash,
+                timestamp: chain[1].timestamp
+            }
+        );
+        assert_eq!(store.block(1).unwrap().unwrap().hash, chain[0].hash);
+
+        // the chain goes on where the broken block was
+        store.store(&chain[2]);
+        store.acknowledge(block_on(acks.next()).unwrap());
+        drop(store);
+        let (store, _) = BlockStore::open(dir.path()).unwrap();
+        assert_eq!(store.tip().hash, chain[2].hash);
+        assert_eq!(store.height_of(&chain[2].hash), Some(3));
+    }
+
+    #[test]
+    fn reports_failing_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, mut acks) = BlockStore::open(&dir.path().join("missing")).unwrap();
+
+        store.store(&chain_of(1)[0]);
+        let ack = block_on(acks.next()).unwrap();
+        assert!(matches!(ack, Ack::Failing { height: 1 }));
+        store.acknowledge(ack);
+        assert!(!store.is_healthy());
+        assert_eq!(store.pending(), 1);
+    }
+}
@@ -0,0 +1,386 @@
+//! Block sync, for nodes that fell behind the chain, e.g. ones that joined
+//! after its first blocks were committed. A node asks one peer for the blocks
+//! after its tip with a `GetBlocks` and gets them back in a `Blocks`, over the
+//! `/educoin/sync/1` protocol rather than gossip since no one else needs them.
+//!
+//! Each message goes over a substream of its own, so a response is just the
+//! next message from the peer that was asked. Only the peer with a request
+//! waiting is listened to. Voted blocks come with the votes they were
+//! committed with and are only taken on a quorum of them, mined ones on
+//! their proof-of-work, and either is checked like any block is before it
+//! is committed.
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use libp2p::core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
+use libp2p::core::{Endpoint, Multiaddr};
+use libp2p::swarm::{
+    ConnectionClosed, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, NotifyHandler,
+    OneShotHandler, PollParameters, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use libp2p::PeerId;
+use prost::Message;
+use std::collections::VecDeque;
+use std::fmt;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{io, iter};
+use tracing::warn;
+
+use crate::block::Block;
+use crate::proto::{self, sync_message};
+use crate::transaction::Transaction;
+
+pub const PROTOCOL: &[u8] = b"/educoin/sync/1";
+/// Most blocks asked for or sent at once, the rest have to be asked for again.
+pub const MAX_BLOCKS: u32 = 64;
+// How long to wait for an answer before asking another peer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Largest message read off a substream, a response of full blocks fits comfortably.
+const MAX_MESSAGE_SIZE: u64 = 16 * 1024 * 1024;
+
+pub fn encode_get_blocks(chain_id: &str, from: u32, to: u32) -> Vec<u8> {
+    encode(sync_message::Message::GetBlocks(proto::GetBlocks {
+        chain_id: chain_id.to_string(),
+        from,
+        to,
+    }))
+}
+
+pub fn encode_blocks<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> Vec<u8> {
+    encode(sync_message::Message::Blocks(proto::Blocks {
+        blocks: blocks
+            .into_iter()
+            .map(|block| proto::Block {
+                height: block.height,
+                timestamp: block.timestamp,
+                prev_hash: block.prev_hash.to_vec(),
+                nonce: block.nonce,
+                votes: block.votes.clone(),
+                transactions: block
+                    .transactions
+                    .iter()
+                    .map(proto::Transaction::from)
+                    .collect(),
+            })
+            .collect(),
+    }))
+}
+
+fn encode(message: sync_message::Message) -> Vec<u8> {
+    proto::SyncMessage {
+        message: Some(message),
+    }
+    .encode_to_vec()
+}
+
+pub enum SyncMessage {
+    /// Asks for the blocks from height `from` to `to`, both included.
+    GetBlocks {
+        from: u32,
+        to: u32,
+    },
+    Blocks(Vec<Block>),
+}
+
+/// The message in `data`, if it is well-formed and any request is for the
+/// chain `chain_id`. Signatures of the transactions are left to check.
+pub fn decode(chain_id: &str, data: &[u8]) -> Option<SyncMessage> {
+    match proto::SyncMessage::decode(data).ok()?.message? {
+        sync_message::Message::GetBlocks(get_blocks) => (get_blocks.chain_id == chain_id)
+            .then_some(SyncMessage::GetBlocks {
+                from: get_blocks.from,
+                to: get_blocks.to,
+            }),
+        sync_message::Message::Blocks(blocks) => {
+            let blocks = blocks
+                .blocks
+                .into_iter()
+                .map(|block| {
+                    let transactions = block
+                        .transactions
+                        .into_iter()
+                        .map(Transaction::try_from)
+                        .collect::<Result<_, _>>()
+                        .ok()?;
+                    let mut decoded = Block::new(
+                        block.height,
+                        block.timestamp,
+                        block.prev_hash.try_into().ok()?,
+                        transactions,
+                    );
+                    decoded.nonce = block.nonce;
+                    decoded.votes = block.votes;
+                    Some(decoded)
+                })
+                .collect::<Option<_>>()?;
+            Some(SyncMessage::Blocks(blocks))
+        }
+    }
+}
+
+pub enum Event {
+    /// `peer` asks for blocks, answer with [`Behaviour::send_blocks`].
+    GetBlocks { peer: PeerId, from: u32, to: u32 },
+    /// `peer` answered [`Behaviour::catch_up`] with `blocks`, in height order.
+    Blocks { peer: PeerId, blocks: Vec<Block> },
+}
+
+// Blocks don't print, show which ones these are.
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::GetBlocks { peer, from, to } => f
+                .debug_struct("GetBlocks")
+                .field("peer", peer)
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            Event::Blocks { peer, blocks } => f
+                .debug_struct("Blocks")
+                .field("peer", peer)
+                .field(
+                    "heights",
+                    &blocks.iter().map(|block| block.height).collect::<Vec<_>>(),
+                )
+                .finish(),
+        }
+    }
+}
+
+/// Asks peers for blocks and answers their requests.
+pub struct Behaviour {
+    chain_id: String,
+    // the peer asked last and when, until it answers
+    waiting_for: Option<(PeerId, Instant)>,
+    actions: VecDeque<ToSwarm<Event, Outbound>>,
+}
+
+impl Behaviour {
+    pub fn new(chain_id: &str) -> Self {
+        Behaviour {
+            chain_id: chain_id.to_string(),
+            waiting_for: None,
+            actions: VecDeque::new(),
+        }
+    }
+
+    /// Ask `peer` for the blocks from height `from` on, unless another peer
+    /// was just asked and may still answer.
+    pub fn catch_up(&mut self, peer: PeerId, from: u32) {
+        if self
+            .waiting_for
+            .is_some_and(|(_, asked_at)| asked_at.elapsed() < REQUEST_TIMEOUT)
+        {
+            return;
+        }
+        self.waiting_for = Some((peer, Instant::now()));
+        let to = from.saturating_add(MAX_BLOCKS - 1);
+        self.send(peer, encode_get_blocks(&self.chain_id, from, to));
+    }
+
+    /// Answer a [`Event::GetBlocks`] of `peer`.
+    pub fn send_blocks<'a>(&mut self, peer: PeerId, blocks: impl IntoIterator<Item = &'a Block>) {
+        self.send(peer, encode_blocks(blocks));
+    }
+
+    fn send(&mut self, peer: PeerId, data: Vec<u8>) {
+        self.actions.push_back(ToSwarm::NotifyHandler {
+            peer_id: peer,
+            handler: NotifyHandler::Any,
+            event: Outbound(data),
+        });
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = OneShotHandler<Inbound, Outbound, HandlerEvent>;
+    type OutEvent = Event;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(OneShotHandler::default())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(OneShotHandler::default())
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        // no answer is coming from a peer that's gone
+        if let FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id,
+            remaining_established: 0,
+            ..
+        }) = event
+        {
+            if self.waiting_for.is_some_and(|(peer, _)| peer == peer_id) {
+                self.waiting_for = None;
+            }
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        let HandlerEvent::Received(data) = event else {
+            return;
+        };
+        match decode(&self.chain_id, &data) {
+            Some(SyncMessage::GetBlocks { from, to }) => self
+                .actions
+                .push_back(ToSwarm::GenerateEvent(Event::GetBlocks { peer, from, to })),
+            Some(SyncMessage::Blocks(blocks)) => {
+                if self.waiting_for.is_none_or(|(asked, _)| asked != peer) {
+                    warn!(%peer, "ignoring blocks that weren't asked for");
+                    return;
+                }
+                self.waiting_for = None;
+                self.actions
+                    .push_back(ToSwarm::GenerateEvent(Event::Blocks { peer, blocks }));
+            }
+            None => warn!(%peer, "dropping malformed sync message"),
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+        match self.actions.pop_front() {
+            Some(action) => Poll::Ready(action),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Reads the message a peer sends on an inbound substream.
+#[derive(Debug, Clone, Default)]
+pub struct Inbound;
+
+/// Writes a message to the peer on an outbound substream.
+#[derive(Debug)]
+pub struct Outbound(Vec<u8>);
+
+#[derive(Debug)]
+pub enum HandlerEvent {
+    Received(Vec<u8>),
+    Sent,
+}
+
+impl From<Vec<u8>> for HandlerEvent {
+    fn from(data: Vec<u8>) -> Self {
+        HandlerEvent::Received(data)
+    }
+}
+
+impl From<()> for HandlerEvent {
+    fn from(_: ()) -> Self {
+        HandlerEvent::Sent
+    }
+}
+
+impl UpgradeInfo for Inbound {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL)
+    }
+}
+
+impl UpgradeInfo for Outbound {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL)
+    }
+}
+
+impl<S> InboundUpgrade<S> for Inbound
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = Vec<u8>;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<Vec<u8>>>;
+
+    fn upgrade_inbound(self, mut socket: S, _: Self::Info) -> Self::Future {
+        async move {
+            let mut data = Vec::new();
+            (&mut socket)
+                .take(MAX_MESSAGE_SIZE)
+                .read_to_end(&mut data)
+                .await?;
+            socket.close().await?;
+            Ok(data)
+        }
+        .boxed()
+    }
+}
+
+impl<S> OutboundUpgrade<S> for Outbound
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = ();
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<()>>;
+
+    fn upgrade_outbound(self, mut socket: S, _: Self::Info) -> Self::Future {
+        async move {
+            socket.write_all(&self.0).await?;
+            socket.close().await
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[test]
+    fn sync_messages_round_trip() {
+        let request = encode_get_blocks("test", 3, 66);
+        assert!(matches!(
+            decode("test", &request),
+            Some(SyncMessage::GetBlocks { from: 3, to: 66 })
+        ));
+        assert!(decode("another-chain", &request).is_none());
+
+        let keypair = Keypair::generate();
+        let transaction = block_on(Transaction::sign(&keypair, "test", b"send", 1)).unwrap();
+        let first = Block::new(1, 60, Block::genesis().hash, vec![transaction]);
+        let mut second = Block::new(2, 61, first.hash, Vec::new());
+        second.nonce = 42;
+        second.votes = vec![b"vote".to_vec()];
+        let hashes = [first.hash, second.hash];
+
+        let Some(SyncMessage::Blocks(blocks)) = decode("test", &encode_blocks(&[first, second]))
+        else {
+            panic!("blocks decode as blocks");
+        };
+        assert!(blocks.iter().map(|block| block.hash).eq(hashes));
+        assert_eq!(blocks[1].nonce, 42, "mined blocks keep their nonce");
+        assert_eq!(blocks[1].votes, [b"vote"], "voted blocks keep their votes");
+    }
+}
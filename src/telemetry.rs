@@ -0,0 +1,96 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::error::Error;
+use tracing::warn;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::dashboard::LogBuffer;
+use crate::logfile::LogFile;
+
+/// Default filter when `RUST_LOG` is not set: our own subsystems at `info`,
+/// libp2p kept quiet unless something goes wrong.
+const DEFAULT_FILTER: &str = "info,libp2p=warn";
+
+/// Service name reported with exported spans.
+const SERVICE_NAME: &str = "educoin-node";
+
+/// Keeps span export running, flushing whatever is still buffered when dropped.
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            if let Err(e) = tracer_provider.shutdown() {
+                warn!(error = %e, "failed to flush exported spans");
+            }
+        }
+    }
+}
+
+/// Install the global tracing subscriber.
+///
+/// Logs go to stderr so stdout stays reserved for replies to stdin commands,
+/// or into `dashboard_logs` when the dashboard owns the terminal.
+/// With a `log_file` they are written there too, without colours.
+/// Use `RUST_LOG` to tune verbosity, e.g. `RUST_LOG=debug` or
+/// `RUST_LOG=info,libp2p_gossipsub=debug`.
+///
+/// With an `otlp_endpoint` (e.g. `http://localhost:4318/v1/traces` for a
+/// local Jaeger) spans are also exported over OTLP/HTTP, so timing across
+/// nodes can be compared side by side.
+pub fn init(
+    dashboard_logs: Option<LogBuffer>,
+    log_file: Option<LogFile>,
+    otlp_endpoint: Option<&str>,
+) -> Result<Telemetry, Box<dyn Error>> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let fmt_layer = tracing_subscriber::fmt::layer();
+    let fmt_layer = match dashboard_logs {
+        Some(logs) => fmt_layer
+            .with_ansi(false)
+            .with_writer(BoxMakeWriter::new(logs)),
+        None => fmt_layer.with_writer(BoxMakeWriter::new(std::io::stderr)),
+    };
+    let file_layer = log_file.map(|log_file| {
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(log_file)
+    });
+
+    let tracer_provider = otlp_endpoint
+        .map(|endpoint| {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()?;
+
+            Ok::<_, Box<dyn Error>>(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                    .build(),
+            )
+        })
+        .transpose()?;
+    let otel_layer = tracer_provider.as_ref().map(|tracer_provider| {
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(SERVICE_NAME))
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(file_layer)
+        .with(otel_layer)
+        .init();
+
+    Ok(Telemetry { tracer_provider })
+}
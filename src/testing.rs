@@ -0,0 +1,1394 @@
+//! In-process networks of nodes wired together over the libp2p memory
+//! transport, so consensus can be exercised end to end in `cargo test`.
+
+use clap::{Parser, ValueEnum};
+use futures::prelude::*;
+use libp2p::core::transport::MemoryTransport;
+use libp2p::core::upgrade;
+use libp2p::identity::{self, ed25519};
+use libp2p::{noise, yamux, Multiaddr, PeerId, Transport};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tempfile::TempDir;
+
+use crate::address::Address;
+use crate::assembler::BLOCK_SIZE;
+use crate::byzantine::Strategy;
+use crate::cli::{Cli, DEFAULT_CHAIN_ID};
+use crate::faults::{Faults, Link};
+use crate::hooks::{self, Hooks};
+use crate::node::{Environment, Node, NodeEvent};
+use crate::relay;
+use crate::simulation;
+use crate::transaction::{Transaction, TransactionId};
+use crate::vote;
+use crate::wallet;
+use scenario::Scenario;
+use workload::{Profile, Workload};
+
+pub mod scenario;
+pub mod workload;
+
+// How long helpers wait for the network before failing the test.
+const TIMEOUT: Duration = Duration::from_secs(30);
+// Topics every node subscribes to, see `node::run`.
+const TOPICS: usize = 4;
+
+// Memory transport addresses are process wide, tests running in parallel must not share them.
+static NEXT_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// A [`Node`] on the memory transport, with faults and hooks to drive it by.
+pub struct TestNode {
+    pub peer_id: PeerId,
+    /// Faults injected into the gossip this node receives.
+    pub faults: Faults,
+    /// Shortcuts past gossip, e.g. to commit a block without votes.
+    pub hooks: Hooks,
+    address: Multiaddr,
+    node: Node,
+    // kept to start the node again after it was killed
+    id_keys: identity::Keypair,
+    fault_seed: u64,
+    args: Vec<String>,
+    data_dir: TempDir,
+}
+
+impl TestNode {
+    async fn start(
+        id_keys: identity::Keypair,
+        dial: Vec<Multiaddr>,
+        fault_seed: u64,
+        args: &[String],
+        data_dir: TempDir,
+    ) -> Self {
+        let peer_id = PeerId::from(id_keys.public());
+        let faults = Faults::new(fault_seed);
+        let transport = MemoryTransport::default()
+            .upgrade(upgrade::Version::V1)
+            .authenticate(noise::Config::new(&id_keys).expect("ed25519 keys work with noise"))
+            .multiplex(yamux::Config::default())
+            .boxed();
+        let address: Multiaddr = format!("/memory/{}", NEXT_PORT.fetch_add(1, Ordering::Relaxed))
+            .parse()
+            .expect("valid memory address");
+
+        let cli = Cli::parse_from(
+            [
+                "educoin".as_ref(),
+                "--data-dir".as_ref(),
+                data_dir.path().as_os_str(),
+                // peers disagreeing on whose turn it is, e.g. right after a crash, cost a round
+                "--round-timeout".as_ref(),
+                "5".as_ref(),
+            ]
+            .into_iter()
+            .chain(args.iter().map(|arg| arg.as_ref())),
+        );
+        let (hooks, hooks_rx) = hooks::channel();
+        let env = Environment {
+            id_keys: id_keys.clone(),
+            transport,
+            listen_on: address.clone(),
+            mdns: false,
+            dial,
+            input: stream::pending().boxed(),
+            shutdown: stream::pending().boxed(),
+            dashboard_logs: None,
+            events: None,
+            faults: Some(faults.clone()),
+            hooks: Some(hooks_rx),
+        };
+        TestNode {
+            peer_id,
+            faults,
+            hooks,
+            address,
+            node: Node::start(cli, env),
+            id_keys,
+            fault_seed,
+            args: args.to_vec(),
+            data_dir,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.node.is_running()
+    }
+
+    /// Enter a line as if typed on the node's stdin.
+    pub fn submit(&self, line: &str) {
+        assert!(self.is_running(), "node is running");
+        self.node.submit(line);
+    }
+
+    /// Wait until the node commits the block at `height`, returning its transactions.
+    pub async fn wait_for_block(&mut self, height: u32) -> Vec<TransactionId> {
+        self.wait_for(|event| match event {
+            NodeEvent::BlockCommitted {
+                height: committed,
+                transactions,
+            } if committed == height => Some(transactions),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Fail if the node commits any block within `period`.
+    pub async fn assert_no_block(&mut self, period: Duration) {
+        let events = self.node.events();
+        let committed = async {
+            while let Some(event) = events.next().await {
+                if let NodeEvent::BlockCommitted { height, .. } = event {
+                    return height;
+                }
+            }
+            panic!("node stopped");
+        };
+
+        if let Ok(height) = async_std::future::timeout(period, committed).await {
+            panic!("node committed block {height}");
+        }
+    }
+
+    // Wait until `peers` other nodes are subscribed to every topic.
+    async fn wait_for_peers(&mut self, peers: usize) {
+        if peers == 0 {
+            return;
+        }
+        let mut subscriptions = HashSet::new();
+        self.wait_for(|event| {
+            if let NodeEvent::PeerSubscribed { peer_id, topic } = event {
+                subscriptions.insert((peer_id, topic));
+            }
+            (subscriptions.len() == peers * TOPICS).then_some(())
+        })
+        .await
+    }
+
+    async fn wait_for<T>(&mut self, mut matches: impl FnMut(NodeEvent) -> Option<T>) -> T {
+        let events = self.node.events();
+        let wait = async {
+            while let Some(event) = events.next().await {
+                if let Some(found) = matches(event) {
+                    return found;
+                }
+            }
+            panic!("node stopped");
+        };
+
+        async_std::future::timeout(TIMEOUT, wait)
+            .await
+            .expect("timed out waiting for the node")
+    }
+}
+
+/// A fully connected network of [`TestNode`]s.
+pub struct TestNetwork {
+    pub nodes: Vec<TestNode>,
+}
+
+impl TestNetwork {
+    /// Start `size` nodes, each dialing every node started before it, and
+    /// wait until all of them see each other on every topic.
+    pub async fn start(size: usize) -> Self {
+        Self::start_with_args(size, |_| Vec::new()).await
+    }
+
+    /// Like [`TestNetwork::start`], passing extra command line flags to the node at each index.
+    pub async fn start_with_args(size: usize, args: impl Fn(usize) -> Vec<String>) -> Self {
+        Self::start_with_keys(
+            (0..size)
+                .map(|_| identity::Keypair::generate_ed25519())
+                .collect(),
+            rand::random(),
+            args,
+        )
+        .await
+    }
+
+    /// Like [`TestNetwork::start`], with the last node misbehaving according to `strategy`.
+    pub async fn start_with_byzantine(size: usize, strategy: Strategy) -> Self {
+        let strategy = strategy
+            .to_possible_value()
+            .expect("strategies are never skipped")
+            .get_name()
+            .to_string();
+        Self::start_with_args(size, |index| {
+            if index == size - 1 {
+                vec!["--byzantine".to_string(), strategy.clone()]
+            } else {
+                Vec::new()
+            }
+        })
+        .await
+    }
+
+    /// Like [`TestNetwork::start`], with identities derived from `seed` so
+    /// the same scenario produces the same peer, message and transaction ids.
+    pub async fn start_seeded(size: usize, seed: u64) -> Self {
+        Self::start_with_keys(
+            (0..size as u64)
+                .map(|node| simulation::identity_keypair(seed, node))
+                .collect(),
+            seed,
+            |_| Vec::new(),
+        )
+        .await
+    }
+
+    // `args` are extra command line flags for the node at each index.
+    async fn start_with_keys(
+        keys: Vec<identity::Keypair>,
+        seed: u64,
+        args: impl Fn(usize) -> Vec<String>,
+    ) -> Self {
+        let size = keys.len();
+        let mut nodes: Vec<TestNode> = Vec::with_capacity(size);
+        for (index, id_keys) in keys.into_iter().enumerate() {
+            let fault_seed = seed.wrapping_add(index as u64);
+            let dial = nodes.iter().map(|node| node.address.clone()).collect();
+            let data_dir = TempDir::new().expect("temporary data dir");
+            nodes.push(TestNode::start(id_keys, dial, fault_seed, &args(index), data_dir).await);
+        }
+
+        for node in &mut nodes {
+            node.wait_for_peers(size - 1).await;
+        }
+
+        TestNetwork { nodes }
+    }
+
+    /// Stop node `index` as if its process was killed.
+    pub async fn kill(&mut self, index: usize) {
+        self.nodes[index].node.kill().await;
+    }
+
+    /// Ask node `index` to shut down, as on SIGTERM, and wait until it has.
+    pub async fn shut_down(&mut self, index: usize) {
+        assert!(self.nodes[index].is_running(), "node is running");
+        if let Err(e) = self.nodes[index].node.shut_down().await {
+            panic!("test node failed: {e}");
+        }
+    }
+
+    /// Start node `index` again after [`TestNetwork::kill`], with its old
+    /// identity and flags but none of its state.
+    pub async fn restart(&mut self, index: usize) {
+        self.start_again(index, TempDir::new().expect("temporary data dir"))
+            .await;
+    }
+
+    /// Start node `index` again after [`TestNetwork::shut_down`], with its
+    /// old identity, flags and data dir.
+    pub async fn resume(&mut self, index: usize) {
+        let data_dir = std::mem::replace(
+            &mut self.nodes[index].data_dir,
+            TempDir::new().expect("temporary data dir"),
+        );
+        self.start_again(index, data_dir).await;
+    }
+
+    async fn start_again(&mut self, index: usize, data_dir: TempDir) {
+        assert!(
+            !self.nodes[index].is_running(),
+            "node {index} is still running"
+        );
+        let dial: Vec<Multiaddr> = self
+            .nodes
+            .iter()
+            .filter(|node| node.is_running())
+            .map(|node| node.address.clone())
+            .collect();
+        let old = &self.nodes[index];
+        let mut node = TestNode::start(
+            old.id_keys.clone(),
+            dial.clone(),
+            old.fault_seed,
+            &old.args,
+            data_dir,
+        )
+        .await;
+        node.wait_for_peers(dial.len()).await;
+        self.nodes[index] = node;
+    }
+
+    /// Let gossip flow between every pair of nodes again, undoing [`TestNetwork::partition`].
+    pub fn heal(&self) {
+        for node in &self.nodes {
+            node.faults.heal();
+        }
+    }
+
+    /// Cut every node in `side` off from every node outside of it, in both directions.
+    pub fn partition(&self, side: &[usize]) {
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (j, other) in self.nodes.iter().enumerate() {
+                if side.contains(&i) != side.contains(&j) {
+                    node.faults.partition(other.peer_id);
+                }
+            }
+        }
+    }
+
+    /// Apply `link` to the gossip going from node `from` to node `to`.
+    pub fn set_link(&self, from: usize, to: usize, link: Link) {
+        self.nodes[to]
+            .faults
+            .set_link(self.nodes[from].peer_id, link);
+    }
+}
+
+#[async_std::test]
+async fn commits_a_block_on_every_node() {
+    let mut network = TestNetwork::start(3).await;
+
+    for i in 0..10 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+
+    // blocks take transactions in whatever order gossip delivered them, only the set is agreed on
+    let expected: HashSet<_> = network.nodes[0]
+        .wait_for_block(1)
+        .await
+        .into_iter()
+        .collect();
+    assert_eq!(expected.len(), 10);
+    for node in &mut network.nodes[1..] {
+        let committed: HashSet<_> = node.wait_for_block(1).await.into_iter().collect();
+        assert_eq!(committed, expected);
+    }
+}
+
+#[async_std::test]
+async fn commits_back_to_back_blocks_from_a_full_mempool() {
+    let mut network = TestNetwork::start(3).await;
+
+    // both blocks' worth arrive before the first one commits
+    for i in 0..20 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+        assert_eq!(node.wait_for_block(2).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn submits_a_batch_from_a_file() {
+    let mut network = TestNetwork::start(2).await;
+
+    // one entry signed elsewhere and delegated to node 0, the rest signed by its wallet
+    let keypair = ed25519::Keypair::generate();
+    let signed = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"signed offline", 1)
+        .await
+        .unwrap();
+    let delegation = relay::delegate(&keypair, &network.nodes[0].peer_id)
+        .await
+        .unwrap();
+    let mut entries = vec![serde_json::json!({
+        "blob": hex::encode(signed.to_bytes()),
+        "delegation": hex::encode(delegation),
+    })];
+    entries.extend((1..10).map(|i| serde_json::json!({ "data": format!("batched {i}") })));
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("txs.json");
+    std::fs::write(&path, serde_json::to_string(&entries).unwrap()).unwrap();
+    network.nodes[0].submit(&format!("/submit --file {}", path.display()));
+
+    for node in &mut network.nodes {
+        let committed = node.wait_for_block(1).await;
+        assert_eq!(committed.len(), 10);
+        assert!(committed.contains(&signed.id()));
+    }
+}
+
+#[async_std::test]
+async fn faucet_serves_requests_from_other_nodes() {
+    let key_dir = TempDir::new().unwrap();
+    let key_file = key_dir.path().join("faucet.key");
+    let faucet_key = ed25519::Keypair::generate();
+    wallet::save_key_file(&key_file, &faucet_key).unwrap();
+    let genesis = format!("{}=1000", Address::from(&faucet_key.public()));
+
+    let mut network = TestNetwork::start_with_args(3, |index| {
+        let mut args = vec!["--genesis-balance".to_string(), genesis.clone()];
+        if index == 0 {
+            args.extend(["--faucet-key".to_string(), key_file.display().to_string()]);
+        }
+        args
+    })
+    .await;
+
+    for byte in 0..10u8 {
+        network.nodes[1].submit(&format!("/faucet {}", hex::encode([byte; 32])));
+    }
+
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn faucet_dispenses_over_rpc() {
+    let key_dir = TempDir::new().unwrap();
+    let key_file = key_dir.path().join("faucet.key");
+    let faucet_key = ed25519::Keypair::generate();
+    wallet::save_key_file(&key_file, &faucet_key).unwrap();
+    let rpc = free_local_address();
+    let _network = TestNetwork::start_with_args(1, |_| {
+        vec![
+            "--genesis-balance".to_string(),
+            format!("{}=1000", Address::from(&faucet_key.public())),
+            "--faucet-key".to_string(),
+            key_file.display().to_string(),
+            "--rpc-listen".to_string(),
+            rpc.to_string(),
+        ]
+    })
+    .await;
+    let recipient = Address::from(&ed25519::Keypair::generate().public());
+
+    let request =
+        serde_json::json!({"id": 1, "method": "faucet", "params": [recipient.to_string()]});
+    let answer = rpc_call(rpc, request.clone()).await;
+    assert!(answer["result"]
+        .as_str()
+        .unwrap()
+        .contains("is sending coins"));
+    let answer = rpc_call(rpc, request).await;
+    assert!(answer["error"]
+        .as_str()
+        .unwrap()
+        .ends_with("already received coins from the faucet"));
+}
+
+/// A localhost address nothing listens on yet, for a node to serve on.
+fn free_local_address() -> std::net::SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// Send the JSON-RPC `request` line to the node serving on `address`, returning its answer.
+async fn rpc_call(address: std::net::SocketAddr, request: serde_json::Value) -> serde_json::Value {
+    let stream = async_std::future::timeout(TIMEOUT, async {
+        loop {
+            match async_std::net::TcpStream::connect(address).await {
+                Ok(stream) => break stream,
+                // the node may not be listening yet
+                Err(_) => async_std::task::sleep(Duration::from_millis(50)).await,
+            }
+        }
+    })
+    .await
+    .expect("the node serves RPC");
+    let mut line = request.to_string();
+    line.push('\n');
+    (&stream).write_all(line.as_bytes()).await.unwrap();
+    let mut answer = String::new();
+    futures::io::BufReader::new(&stream)
+        .read_line(&mut answer)
+        .await
+        .unwrap();
+    serde_json::from_str(&answer).unwrap()
+}
+
+// The first alert of `kind` POSTed to a `webhook_endpoint`.
+async fn wait_for_alert(
+    received: std::sync::mpsc::Receiver<serde_json::Value>,
+    kind: &'static str,
+) -> serde_json::Value {
+    async_std::task::spawn_blocking(move || loop {
+        let alert = received.recv_timeout(TIMEOUT).unwrap();
+        if alert["kind"] == kind {
+            return alert;
+        }
+    })
+    .await
+}
+
+/// A bare HTTP endpoint on localhost, returning its URL and the JSON bodies POSTed to it.
+fn webhook_endpoint() -> (String, std::sync::mpsc::Receiver<serde_json::Value>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let (bodies, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let _ = reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            if bodies.send(serde_json::from_slice(&body).unwrap()).is_err() {
+                return;
+            }
+        }
+    });
+    (url, received)
+}
+
+#[async_std::test]
+async fn webhooks_hear_of_blocks_and_transactions() {
+    let (url, received) = webhook_endpoint();
+    let mut network = TestNetwork::start_with_args(2, |index| match index {
+        1 => vec![
+            "--block-webhook".to_string(),
+            url.clone(),
+            "--webhook-transactions".to_string(),
+        ],
+        _ => Vec::new(),
+    })
+    .await;
+
+    for i in 0..10 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+    let committed = network.nodes[1].wait_for_block(1).await;
+
+    // deliveries run on threads of their own, the block may arrive before its transactions
+    let notifications = async_std::task::spawn_blocking(move || {
+        (0..11)
+            .map(|_| received.recv_timeout(TIMEOUT).unwrap())
+            .collect::<Vec<_>>()
+    })
+    .await;
+    let ids = |transactions: Vec<&serde_json::Value>| -> HashSet<String> {
+        transactions
+            .into_iter()
+            .map(|transaction| transaction["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let expected: HashSet<_> = committed.iter().map(TransactionId::to_string).collect();
+
+    let block = notifications
+        .iter()
+        .find(|notification| notification["event"] == "block")
+        .unwrap();
+    assert_eq!(block["height"], 1);
+    assert_eq!(
+        ids(block["transactions"].as_array().unwrap().iter().collect()),
+        expected
+    );
+    let accepted = notifications
+        .iter()
+        .filter(|notification| notification["event"] == "transaction")
+        .collect();
+    assert_eq!(ids(accepted), expected);
+}
+
+#[async_std::test]
+async fn hooks_commit_without_gossip() {
+    let mut network = TestNetwork::start(1).await;
+    let node = &mut network.nodes[0];
+    let keypair = ed25519::Keypair::generate();
+
+    let mut injected = Vec::new();
+    for i in 0..3 {
+        let data = format!("injected {i}");
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), i + 1)
+            .await
+            .unwrap();
+        injected.push(transaction.id());
+        node.hooks.inject_transaction(transaction);
+    }
+    node.hooks.produce_block();
+
+    assert_eq!(node.wait_for_block(1).await, injected);
+}
+
+#[async_std::test]
+async fn transactions_submitted_to_a_node_get_committed() {
+    let mut network = TestNetwork::start(2).await;
+    let node = &mut network.nodes[0];
+    let keypair = node.id_keys.clone().try_into_ed25519().unwrap();
+
+    let mut submitted = Vec::new();
+    for i in 0..BLOCK_SIZE {
+        let data = format!("submitted {i}");
+        let nonce = i as u64 + 1;
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), nonce)
+            .await
+            .unwrap();
+        submitted.push(transaction.id());
+        node.node.submit_transaction(&transaction);
+    }
+
+    // transactions are verified in parallel, so they may reach the block in any order
+    let submitted: HashSet<_> = submitted.into_iter().collect();
+    for node in &mut network.nodes {
+        let committed: HashSet<_> = node.wait_for_block(1).await.into_iter().collect();
+        assert_eq!(committed, submitted);
+    }
+}
+
+#[async_std::test]
+async fn blocks_are_validated_before_they_are_persisted() {
+    let mut network = TestNetwork::start(1).await;
+    let node = &mut network.nodes[0];
+    let keypair = ed25519::Keypair::generate();
+
+    // a forced block skips voting, and with it the checks done before a vote
+    let forged = Transaction::sign(&keypair, "another-chain", b"forged", 1)
+        .await
+        .unwrap();
+    node.hooks.inject_transaction(forged);
+    node.hooks.produce_block();
+    node.assert_no_block(Duration::from_millis(300)).await;
+
+    // the forged one's nonce was never used
+    let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"genuine", 1)
+        .await
+        .unwrap();
+    let genuine = transaction.id();
+    node.hooks.inject_transaction(transaction);
+    node.hooks.produce_block();
+    assert_eq!(node.wait_for_block(1).await, vec![genuine]);
+}
+
+#[async_std::test]
+async fn blacklisted_addresses_never_make_it_into_a_block() {
+    let keypair = ed25519::Keypair::generate();
+    let address = Address::from(&keypair.public()).to_string();
+    let mut network = TestNetwork::start_with_args(1, |_| {
+        vec!["--blacklist-address".to_string(), address.clone()]
+    })
+    .await;
+    let node = &mut network.nodes[0];
+
+    let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"rogue script", 1)
+        .await
+        .unwrap();
+    node.hooks.inject_transaction(transaction);
+    node.hooks.produce_block();
+    node.assert_no_block(Duration::from_millis(300)).await;
+}
+
+#[async_std::test]
+async fn a_clean_shutdown_keeps_the_round_and_mempool() {
+    let mut network = TestNetwork::start(1).await;
+    let keypair = ed25519::Keypair::generate();
+    network.nodes[0].hooks.produce_block();
+    network.nodes[0].wait_for_block(1).await;
+
+    let mut pending = Vec::new();
+    for i in 0..2 {
+        let data = format!("pending {i}");
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), i + 1)
+            .await
+            .unwrap();
+        pending.push(transaction.id());
+        network.nodes[0].hooks.inject_transaction(transaction);
+    }
+    // hooks and the shutdown request race, let the node admit the transactions first
+    network.nodes[0]
+        .assert_no_block(Duration::from_millis(300))
+        .await;
+    network.shut_down(0).await;
+
+    network.resume(0).await;
+    network.nodes[0].hooks.produce_block();
+    assert_eq!(network.nodes[0].wait_for_block(2).await, pending);
+}
+
+#[async_std::test]
+async fn balances_survive_a_restart() {
+    let keypair = ed25519::Keypair::generate();
+    let sender = Address::from(&keypair.public());
+    let recipient = Address::from(&ed25519::Keypair::generate().public());
+    let mut network = TestNetwork::start_with_args(1, |_| {
+        vec!["--genesis-balance".to_string(), format!("{sender}=100")]
+    })
+    .await;
+    let data = format!("send {recipient} 30");
+    let transfer = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), 1)
+        .await
+        .unwrap();
+    network.nodes[0].hooks.inject_transaction(transfer);
+    network.nodes[0].hooks.produce_block();
+    network.nodes[0].wait_for_block(1).await;
+    network.shut_down(0).await;
+
+    network.resume(0).await;
+    let hooks = &network.nodes[0].hooks;
+    assert_eq!(hooks.balance(recipient).await, 30);
+    assert_eq!(hooks.balance(sender).await, 70);
+}
+
+#[async_std::test]
+async fn stored_blocks_are_checked_again_on_start() {
+    let keypair = ed25519::Keypair::generate();
+    let mut network = TestNetwork::start(1).await;
+    let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"hello", 1)
+        .await
+        .unwrap();
+    network.nodes[0].hooks.inject_transaction(transaction);
+    network.nodes[0].hooks.produce_block();
+    network.nodes[0].wait_for_block(1).await;
+    network.shut_down(0).await;
+
+    // signed for another chain, as far as the node is concerned now
+    network.nodes[0].args = vec!["--chain-id".to_string(), "another-chain".to_string()];
+    network.resume(0).await;
+    let error = network.nodes[0].node.shut_down().await.unwrap_err();
+    assert!(
+        error.contains("stored block 1 doesn't check out"),
+        "{error}"
+    );
+}
+
+#[async_std::test]
+async fn replayed_transfers_move_coins_once() {
+    let keypair = ed25519::Keypair::generate();
+    let sender = Address::from(&keypair.public());
+    let recipient = Address::from(&ed25519::Keypair::generate().public());
+    let mut network = TestNetwork::start_with_args(1, |_| {
+        vec!["--genesis-balance".to_string(), format!("{sender}=100")]
+    })
+    .await;
+    let node = &mut network.nodes[0];
+    let data = format!("send {recipient} 30");
+    let transfer = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), 1)
+        .await
+        .unwrap();
+    let replayed = Transaction::decode(transfer.to_bytes().into()).unwrap();
+    node.hooks.inject_transaction(transfer);
+    node.hooks.produce_block();
+    node.wait_for_block(1).await;
+
+    // refused at admission, and left out of the next block when slipped past it
+    node.node.submit_transaction(&replayed);
+    node.hooks.inject_transaction(replayed);
+    node.hooks.produce_block();
+    assert!(node.wait_for_block(2).await.is_empty());
+    assert_eq!(node.hooks.balance(recipient).await, 30);
+    assert_eq!(node.hooks.balance(sender).await, 70);
+}
+
+#[async_std::test]
+async fn answers_balance_queries_over_rpc() {
+    let keypair = ed25519::Keypair::generate();
+    let sender = Address::from(&keypair.public());
+    let rpc = free_local_address();
+    let mut network = TestNetwork::start_with_args(1, |_| {
+        vec![
+            "--genesis-balance".to_string(),
+            format!("{sender}=100"),
+            "--rpc-listen".to_string(),
+            rpc.to_string(),
+        ]
+    })
+    .await;
+    let recipient = Address::from(&ed25519::Keypair::generate().public());
+    let data = format!("send {recipient} 30");
+    let transfer = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), 1)
+        .await
+        .unwrap();
+
+    let balance = |address: Address| serde_json::json!({"id": 7, "method": "balance", "params": [address.to_string()]});
+    assert_eq!(
+        rpc_call(rpc, balance(sender)).await,
+        serde_json::json!({"id": 7, "result": {"address": sender.to_string(), "confirmed": 100, "pending": 100}})
+    );
+    // calls and hooks reach the node independently, ask once the block is in
+    network.nodes[0].hooks.inject_transaction(transfer);
+    network.nodes[0].hooks.produce_block();
+    network.nodes[0].wait_for_block(1).await;
+    let answer = rpc_call(rpc, balance(sender)).await;
+    assert_eq!(answer["result"]["confirmed"], 70);
+    assert_eq!(answer["result"]["pending"], 70);
+    let answer = rpc_call(rpc, balance(recipient)).await;
+    assert_eq!(answer["result"]["confirmed"], 30);
+    let answer = rpc_call(rpc, serde_json::json!({"id": 8, "method": "mint"})).await;
+    assert_eq!(answer["error"], "unknown method `mint`");
+}
+
+#[async_std::test]
+async fn rpc_takes_address_book_labels() {
+    let rpc = free_local_address();
+    let _network =
+        TestNetwork::start_with_args(1, |_| vec!["--rpc-listen".to_string(), rpc.to_string()])
+            .await;
+    let bob = Address::from(&ed25519::Keypair::generate().public()).to_string();
+
+    let call = |method: &str, params: &[&str]| {
+        rpc_call(
+            rpc,
+            serde_json::json!({"id": 1, "method": method, "params": params}),
+        )
+    };
+    let answer = call("alias", &["bob", &bob]).await;
+    assert_eq!(answer["result"]["address"], bob);
+    assert_eq!(call("balance", &["bob"]).await["result"]["address"], bob);
+    assert_eq!(
+        call("aliases", &[]).await["result"],
+        serde_json::json!({ "bob": bob })
+    );
+    assert!(call("balance", &["carol"]).await["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("address book error"));
+}
+
+#[async_std::test]
+async fn bootstrap_peers_are_dialed_on_start() {
+    let mut network = TestNetwork::start(1).await;
+    let first = &network.nodes[0];
+    let bootstrap = format!("{}/p2p/{}", first.address, first.peer_id);
+
+    let mut joiner = TestNode::start(
+        identity::Keypair::generate_ed25519(),
+        Vec::new(),
+        rand::random(),
+        &["--bootstrap".to_string(), bootstrap],
+        TempDir::new().expect("temporary data dir"),
+    )
+    .await;
+    joiner.wait_for_peers(1).await;
+    network.nodes[0].wait_for_peers(1).await;
+}
+
+#[async_std::test]
+async fn late_joiners_catch_up_before_voting() {
+    let mut network = TestNetwork::start(2).await;
+    let keypair = network.nodes[0].id_keys.clone().try_into_ed25519().unwrap();
+    // voted on, so the block comes with the votes a late joiner takes it on
+    for i in 0..BLOCK_SIZE {
+        network.nodes[0].submit(&format!("before the sync {i}"));
+    }
+    for node in &mut network.nodes {
+        node.wait_for_block(1).await;
+    }
+    // node 1 comes back without the block, it can't vote for the next one until it fetched it
+    network.kill(1).await;
+    network.restart(1).await;
+
+    let node = &network.nodes[0];
+    for i in 0..BLOCK_SIZE {
+        let data = format!("after the sync {i}");
+        // nonces go on from the block the wallet signed
+        let nonce = (BLOCK_SIZE + i) as u64 + 1;
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), nonce)
+            .await
+            .unwrap();
+        node.node.submit_transaction(&transaction);
+    }
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(2).await.len(), BLOCK_SIZE);
+    }
+}
+
+#[async_std::test]
+async fn late_joiners_skip_blocks_without_votes() {
+    let mut network = TestNetwork::start(2).await;
+    // committed without votes, nothing shows a late joiner it was agreed on
+    network.nodes[0].hooks.produce_block();
+    network.nodes[0].wait_for_block(1).await;
+    network.kill(1).await;
+    network.restart(1).await;
+
+    let node = &network.nodes[0];
+    let keypair = node.id_keys.clone().try_into_ed25519().unwrap();
+    for i in 0..BLOCK_SIZE {
+        let data = format!("after the sync {i}");
+        let nonce = i as u64 + 1;
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), nonce)
+            .await
+            .unwrap();
+        node.node.submit_transaction(&transaction);
+    }
+    network.nodes[1]
+        .assert_no_block(Duration::from_secs(2))
+        .await;
+}
+
+#[async_std::test]
+async fn injected_votes_count_towards_quorum() {
+    let mut network = TestNetwork::start(2).await;
+    // block 1 is the second validator's turn in peer id order
+    network.nodes.sort_by_key(|node| node.peer_id);
+    let other = network.nodes[0].peer_id;
+    let node = &mut network.nodes[1];
+    let keypair = ed25519::Keypair::generate();
+
+    for i in 0..10 {
+        let data = format!("injected {i}");
+        let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), i + 1)
+            .await
+            .unwrap();
+        node.hooks.inject_transaction(transaction);
+    }
+    node.assert_no_block(Duration::from_millis(300)).await;
+
+    node.hooks.inject_vote(1, other);
+    assert_eq!(node.wait_for_block(1).await.len(), 10);
+}
+
+#[async_std::test]
+async fn votes_overtaking_the_batch_commit_nothing_early() {
+    let mut network = TestNetwork::start(2).await;
+    // block 1 is the second validator's turn in peer id order
+    network.nodes.sort_by_key(|node| node.peer_id);
+    let keypair = ed25519::Keypair::generate();
+    for (index, node) in network.nodes.iter().enumerate() {
+        // the proposer gets the whole batch, the other validator all but its last transaction
+        let held = if index == 1 { 10 } else { 9 };
+        for i in 0..held {
+            let data = format!("overtaken {i}");
+            let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), i + 1)
+                .await
+                .unwrap();
+            node.hooks.inject_transaction(transaction);
+        }
+    }
+
+    // the proposal and the proposer's vote reach the other validator ahead of the last
+    // transaction, it can't vote yet and one vote of two is no quorum
+    for node in &mut network.nodes {
+        node.assert_no_block(Duration::from_millis(500)).await;
+    }
+
+    let last = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"overtaken 9", 10)
+        .await
+        .unwrap();
+    network.nodes[0].hooks.inject_transaction(last);
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn conflicting_votes_raise_an_alert_with_both_votes() {
+    let (url, received) = webhook_endpoint();
+    let network = TestNetwork::start_with_args(2, |index| match index {
+        1 => vec!["--alert-webhook".to_string(), url.clone()],
+        _ => Vec::new(),
+    })
+    .await;
+
+    // node 0 votes for two different blocks in the same round
+    let keypair = network.nodes[0].id_keys.clone().try_into_ed25519().unwrap();
+    let votes: Vec<_> = [[1; 32], [2; 32]]
+        .iter()
+        .map(|block| vote::encode(DEFAULT_CHAIN_ID, 1, 0, block, &keypair))
+        .collect();
+    for vote in &votes {
+        network.nodes[0].hooks.publish_raw("vote", vote);
+    }
+
+    let alert = wait_for_alert(received, "equivocation").await;
+    assert_eq!(alert["height"], 1);
+    let audit = fs::read_to_string(network.nodes[1].data_dir.path().join("audit.jsonl")).unwrap();
+    let evidence: serde_json::Value = audit
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .find(|entry: &serde_json::Value| entry["event"] == "equivocation")
+        .unwrap();
+    // either vote may arrive first
+    let kept: HashSet<_> = evidence["votes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|vote| vote.as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(kept, votes.iter().map(hex::encode).collect());
+}
+
+#[async_std::test]
+async fn advancing_the_clock_trips_the_stall_watchdog() {
+    let mut network =
+        TestNetwork::start_with_args(1, |_| vec!["--stall-timeout".to_string(), "60".to_string()])
+            .await;
+    let node = &mut network.nodes[0];
+
+    node.hooks.advance_clock(Duration::from_secs(61));
+    let idle = node
+        .wait_for(|event| match event {
+            NodeEvent::Stalled { idle } => Some(idle),
+            _ => None,
+        })
+        .await;
+    assert!(idle >= Duration::from_secs(60));
+}
+
+#[async_std::test]
+async fn malformed_gossip_gets_the_sender_blacklisted() {
+    let mut network = TestNetwork::start_with_args(2, |_| {
+        vec!["--malformed-limit".to_string(), "3".to_string()]
+    })
+    .await;
+
+    let garbage = network.nodes[0].peer_id;
+    for i in 0..3u8 {
+        // a lone byte is never a valid protobuf message
+        network.nodes[0].hooks.publish_raw("transaction", &[i]);
+    }
+    let blacklisted = network.nodes[1]
+        .wait_for(|event| match event {
+            NodeEvent::PeerBlacklisted { peer_id } => Some(peer_id),
+            _ => None,
+        })
+        .await;
+    assert_eq!(blacklisted, garbage);
+}
+
+#[async_std::test]
+async fn transactions_of_other_keys_need_a_delegation() {
+    let mut network = TestNetwork::start_with_args(2, |_| {
+        vec!["--malformed-limit".to_string(), "1".to_string()]
+    })
+    .await;
+
+    // signed by a key node 0 doesn't hold, published as if it were node 0's own
+    let keypair = ed25519::Keypair::generate();
+    let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, b"picked up elsewhere", 1)
+        .await
+        .unwrap();
+    network.nodes[0]
+        .hooks
+        .publish_raw("transaction", &relay::encode(&transaction, None));
+
+    let blacklisted = network.nodes[1]
+        .wait_for(|event| match event {
+            NodeEvent::PeerBlacklisted { peer_id } => Some(peer_id),
+            _ => None,
+        })
+        .await;
+    assert_eq!(blacklisted, network.nodes[0].peer_id);
+}
+
+#[async_std::test]
+async fn seeded_networks_replay_identically() {
+    let mut first = TestNetwork::start_seeded(2, 7).await;
+    let mut second = TestNetwork::start_seeded(2, 7).await;
+
+    let peer_ids = |network: &TestNetwork| -> Vec<_> {
+        network.nodes.iter().map(|node| node.peer_id).collect()
+    };
+    assert_eq!(peer_ids(&first), peer_ids(&second));
+
+    for i in 0..10 {
+        first.nodes[0].submit(&format!("transaction {i}"));
+        second.nodes[0].submit(&format!("transaction {i}"));
+    }
+    let first_block: HashSet<_> = first.nodes[1].wait_for_block(1).await.into_iter().collect();
+    let second_block: HashSet<_> = second.nodes[1]
+        .wait_for_block(1)
+        .await
+        .into_iter()
+        .collect();
+    assert_eq!(first_block, second_block);
+}
+
+#[async_std::test]
+async fn commits_despite_latency_and_reordering() {
+    let mut network = TestNetwork::start(3).await;
+    let link = Link {
+        latency: Duration::from_millis(50),
+        jitter: Duration::from_millis(200),
+        ..Link::default()
+    };
+    for from in 0..3 {
+        for to in 0..3 {
+            network.set_link(from, to, link);
+        }
+    }
+
+    for i in 0..10 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn mined_blocks_commit_without_votes() {
+    let mut network = TestNetwork::start_with_args(3, |_| {
+        ["--consensus", "pow", "--difficulty", "8"]
+            .map(String::from)
+            .to_vec()
+    })
+    .await;
+
+    for i in 0..10 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn minority_partition_blocks_commits() {
+    let mut network = TestNetwork::start(3).await;
+    // block 1 is the second validator's turn in peer id order, keep it with the majority
+    network.nodes.sort_by_key(|node| node.peer_id);
+    network.partition(&[2]);
+
+    for i in 0..10 {
+        network.nodes[1].submit(&format!("transaction {i}"));
+    }
+    // two of three validators are a quorum, one isn't
+    for node in &mut network.nodes[..2] {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+    network.nodes[2]
+        .assert_no_block(Duration::from_millis(700))
+        .await;
+}
+
+#[async_std::test]
+async fn unanimous_quorum_stalls_on_a_partition() {
+    let mut network =
+        TestNetwork::start_with_args(3, |_| vec!["--quorum".to_string(), "1/1".to_string()]).await;
+    network.partition(&[0]);
+
+    for i in 0..10 {
+        network.nodes[1].submit(&format!("transaction {i}"));
+    }
+    // every peer has to vote, so neither side can commit without the other
+    for node in &mut network.nodes {
+        node.assert_no_block(Duration::from_millis(700)).await;
+    }
+}
+
+#[async_std::test]
+async fn withholding_validator_is_outvoted() {
+    let mut network = TestNetwork::start_with_byzantine(3, Strategy::Withholding).await;
+
+    for i in 0..10 {
+        network.nodes[0].submit(&format!("transaction {i}"));
+    }
+    // the withholder itself still gets everybody else's votes
+    for node in &mut network.nodes {
+        assert_eq!(node.wait_for_block(1).await.len(), 10);
+    }
+}
+
+#[async_std::test]
+async fn connected_non_validators_never_get_a_turn() {
+    let keys: Vec<_> = (0..3).map(|_| ed25519::Keypair::generate()).collect();
+    let genesis: Vec<_> = keys[..2]
+        .iter()
+        .flat_map(|keypair| {
+            [
+                "--genesis-balance".to_string(),
+                format!("{}=100", Address::from(&keypair.public())),
+            ]
+        })
+        .collect();
+    let mut network = TestNetwork::start_with_keys(
+        keys.iter().cloned().map(identity::Keypair::from).collect(),
+        rand::random(),
+        |_| {
+            let mut args = vec!["--epoch-length".to_string(), "1".to_string()];
+            args.extend(genesis.iter().cloned());
+            args
+        },
+    )
+    .await;
+
+    // the first two nodes bond stake in block 1, which ends the epoch and makes them the
+    // only validators, while the third stays connected
+    let sender = ed25519::Keypair::generate();
+    let bonds = keys[..2]
+        .iter()
+        .map(|keypair| (keypair, "bond 50".to_string(), 1));
+    let fillers = (0..38).map(|i| (&sender, format!("filler {i}"), i + 1));
+    let (first, later): (Vec<_>, Vec<_>) = bonds
+        .chain(fillers)
+        .partition(|(_, data, nonce)| data.starts_with("bond") || *nonce <= 8);
+    for node in &network.nodes {
+        for (keypair, data, nonce) in &first {
+            let transaction =
+                Transaction::sign(*keypair, DEFAULT_CHAIN_ID, data.as_bytes(), *nonce)
+                    .await
+                    .unwrap();
+            node.hooks.inject_transaction(transaction);
+        }
+    }
+    for node in &mut network.nodes {
+        node.wait_for_block(1).await;
+    }
+
+    // only the validators hold the next blocks, the third node couldn't propose them on a
+    // turn of its own
+    for node in &network.nodes[..2] {
+        for (keypair, data, nonce) in &later {
+            let transaction =
+                Transaction::sign(*keypair, DEFAULT_CHAIN_ID, data.as_bytes(), *nonce)
+                    .await
+                    .unwrap();
+            node.hooks.inject_transaction(transaction);
+        }
+    }
+    // every height in a full turn around the three nodes gets committed
+    for height in 2..=4 {
+        for node in &mut network.nodes[..2] {
+            assert_eq!(node.wait_for_block(height).await.len(), 10);
+        }
+    }
+}
+
+// Start three nodes, the second one proposing block 1 and misbehaving according to `strategy`,
+// and give the transactions of block 1 to all but the first one, which sends its alerts to
+// `alert_webhook`. That node can't vote, and with every vote needed no node commits, so it
+// hears the whole round.
+async fn start_watched_round(strategy: Strategy, alert_webhook: &str) -> TestNetwork {
+    let strategy = strategy
+        .to_possible_value()
+        .expect("strategies are never skipped")
+        .get_name()
+        .to_string();
+    let mut keys: Vec<_> = (0..3)
+        .map(|_| identity::Keypair::generate_ed25519())
+        .collect();
+    // block 1 is the second validator's turn in peer id order
+    keys.sort_by_key(|keypair| keypair.public().to_peer_id());
+    let network = TestNetwork::start_with_keys(keys, rand::random(), |index| {
+        let mut args = vec!["--quorum".to_string(), "1/1".to_string()];
+        match index {
+            0 => args.extend(["--alert-webhook".to_string(), alert_webhook.to_string()]),
+            1 => args.extend(["--byzantine".to_string(), strategy.clone()]),
+            _ => {}
+        }
+        args
+    })
+    .await;
+
+    let keypair = ed25519::Keypair::generate();
+    for i in 0..10 {
+        let data = format!("watched {i}");
+        for node in &network.nodes[1..] {
+            let transaction = Transaction::sign(&keypair, DEFAULT_CHAIN_ID, data.as_bytes(), i + 1)
+                .await
+                .unwrap();
+            node.hooks.inject_transaction(transaction);
+        }
+    }
+    network
+}
+
+#[async_std::test]
+async fn double_votes_raise_an_alert() {
+    let (url, received) = webhook_endpoint();
+    let network = start_watched_round(Strategy::DoubleVotes, &url).await;
+
+    let alert = wait_for_alert(received, "equivocation").await;
+    assert_eq!(alert["height"], 1);
+    let double_voter = network.nodes[1].peer_id.to_string();
+    assert!(alert["detail"].as_str().unwrap().starts_with(&double_voter));
+}
+
+#[async_std::test]
+async fn conflicting_proposals_raise_an_alert() {
+    let (url, received) = webhook_endpoint();
+    let network = start_watched_round(Strategy::ConflictingProposals, &url).await;
+
+    let alert = wait_for_alert(received, "equivocation").await;
+    assert_eq!(alert["height"], 1);
+    let equivocator = network.nodes[1].peer_id.to_string();
+    let detail = alert["detail"].as_str().unwrap();
+    assert!(detail.starts_with(&format!("{equivocator} proposed")));
+}
+
+#[async_std::test]
+async fn invalid_signatures_are_not_voted_for() {
+    let mut network = TestNetwork::start_with_byzantine(3, Strategy::InvalidSignatures).await;
+
+    for i in 0..10 {
+        network.nodes[2].submit(&format!("transaction {i}"));
+    }
+    for node in &mut network.nodes {
+        node.assert_no_block(Duration::from_millis(700)).await;
+    }
+}
+
+async fn assert_block_agreed_under(workload: Workload) {
+    let mut network = TestNetwork::start(3).await;
+    workload.run(&network.nodes).await;
+
+    let expected: HashSet<_> = network.nodes[0]
+        .wait_for_block(1)
+        .await
+        .into_iter()
+        .collect();
+    assert_eq!(expected.len(), 10);
+    for node in &mut network.nodes[1..] {
+        let committed: HashSet<_> = node.wait_for_block(1).await.into_iter().collect();
+        assert_eq!(committed, expected);
+    }
+}
+
+#[async_std::test]
+async fn commits_under_steady_load() {
+    assert_block_agreed_under(Workload {
+        profile: Profile::Steady { per_second: 50 },
+        transactions: 10,
+        seed: 1,
+    })
+    .await;
+}
+
+#[async_std::test]
+async fn commits_under_bursty_load() {
+    assert_block_agreed_under(Workload {
+        profile: Profile::Bursty {
+            size: 4,
+            pause: Duration::from_millis(100),
+        },
+        transactions: 10,
+        seed: 2,
+    })
+    .await;
+}
+
+#[async_std::test]
+async fn commits_with_skewed_senders() {
+    assert_block_agreed_under(Workload {
+        profile: Profile::Skewed { hot_share: 0.8 },
+        transactions: 10,
+        seed: 3,
+    })
+    .await;
+}
+
+#[async_std::test]
+async fn bundled_scenarios_run() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let scenario = Scenario::load(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        scenario.run().await;
+    }
+}
+
+/// Play a lesson demo, e.g.
+/// `EDUCOIN_SCENARIO=scenarios/crash.scenario cargo test play_scenario -- --ignored --nocapture`.
+#[async_std::test]
+#[ignore = "needs EDUCOIN_SCENARIO"]
+async fn play_scenario() {
+    let path = std::env::var_os("EDUCOIN_SCENARIO").expect("EDUCOIN_SCENARIO is not set");
+    let scenario = Scenario::load(Path::new(&path)).unwrap_or_else(|e| panic!("{e}"));
+    scenario.run().await;
+}
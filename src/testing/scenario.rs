@@ -0,0 +1,219 @@
+//! Scripted lesson demos, run against a harness network step by step.
+//!
+//! A scenario file starts with the network size and lists one step per line,
+//! `#` starts a comment:
+//!
+//! ```text
+//! nodes 3
+//! submit 0 10 hello      # node 0 sends `hello 0` .. `hello 9`
+//! wait-block 1 1         # until node 1 commits block 1
+//! partition 2            # cut node 2 off from the others
+//! pause 500              # milliseconds
+//! heal
+//! kill 2
+//! resume 2               # start node 2 again, with a fresh state
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::TestNetwork;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Submit `count` transactions `<text> 0`, `<text> 1`, ... to `node`.
+    Submit {
+        node: usize,
+        count: usize,
+        text: String,
+    },
+    Pause(Duration),
+    /// Cut these nodes off from the rest of the network.
+    Partition(Vec<usize>),
+    Heal,
+    WaitForBlock {
+        node: usize,
+        height: u32,
+    },
+    Kill(usize),
+    /// Start a killed node again.
+    Resume(usize),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Submit { node, count, text } => write!(f, "submit {node} {count} {text}"),
+            Step::Pause(pause) => write!(f, "pause {}", pause.as_millis()),
+            Step::Partition(side) => {
+                write!(f, "partition")?;
+                side.iter().try_for_each(|node| write!(f, " {node}"))
+            }
+            Step::Heal => write!(f, "heal"),
+            Step::WaitForBlock { node, height } => write!(f, "wait-block {node} {height}"),
+            Step::Kill(node) => write!(f, "kill {node}"),
+            Step::Resume(node) => write!(f, "resume {node}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub nodes: usize,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        fs::read_to_string(path)
+            .map_err(|e| ScenarioError {
+                line: 0,
+                reason: format!("failed to read {}: {e}", path.display()),
+            })?
+            .parse()
+    }
+
+    /// Start the network and play every step in order, narrating on stdout.
+    pub async fn run(&self) {
+        println!("starting {} nodes", self.nodes);
+        let mut network = TestNetwork::start(self.nodes).await;
+
+        for step in &self.steps {
+            println!("> {step}");
+            match step {
+                Step::Submit { node, count, text } => {
+                    for i in 0..*count {
+                        network.nodes[*node].submit(&format!("{text} {i}"));
+                    }
+                }
+                Step::Pause(pause) => async_std::task::sleep(*pause).await,
+                Step::Partition(side) => network.partition(side),
+                Step::Heal => network.heal(),
+                Step::WaitForBlock { node, height } => {
+                    let transactions = network.nodes[*node].wait_for_block(*height).await;
+                    println!(
+                        "  node {node} committed block {height} with {} transactions",
+                        transactions.len()
+                    );
+                }
+                Step::Kill(node) => network.kill(*node).await,
+                Step::Resume(node) => network.restart(*node).await,
+            }
+        }
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut nodes = None;
+        let mut steps = Vec::new();
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |reason: &str| ScenarioError {
+                line: line_number,
+                reason: reason.to_string(),
+            };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mut args = rest.split_whitespace();
+            let mut node = || -> Result<usize, ScenarioError> {
+                let node = args
+                    .next()
+                    .and_then(|node| node.parse().ok())
+                    .ok_or_else(|| error("expected a node index"))?;
+                match nodes {
+                    Some(size) if node < size => Ok(node),
+                    Some(size) => Err(error(&format!("there are only {size} nodes"))),
+                    None => Err(error("`nodes` has to come first")),
+                }
+            };
+
+            let step = match command {
+                "nodes" if nodes.is_none() => {
+                    let size = rest
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|&size| size > 0)
+                        .ok_or_else(|| error("expected the number of nodes"))?;
+                    nodes = Some(size);
+                    continue;
+                }
+                "nodes" => return Err(error("`nodes` can only be given once")),
+                "submit" => {
+                    let node = node()?;
+                    let mut args = rest.split_whitespace().skip(1);
+                    let count = args
+                        .next()
+                        .and_then(|count| count.parse().ok())
+                        .ok_or_else(|| error("expected the number of transactions"))?;
+                    let text = args.collect::<Vec<_>>().join(" ");
+                    if text.is_empty() {
+                        return Err(error("expected the transaction text"));
+                    }
+                    Step::Submit { node, count, text }
+                }
+                "pause" => Step::Pause(Duration::from_millis(
+                    rest.trim()
+                        .parse()
+                        .map_err(|_| error("expected milliseconds"))?,
+                )),
+                "partition" => {
+                    let side = rest
+                        .split_whitespace()
+                        .map(|_| node())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if side.is_empty() {
+                        return Err(error("expected the nodes to cut off"));
+                    }
+                    Step::Partition(side)
+                }
+                "heal" => Step::Heal,
+                "wait-block" => {
+                    let node = node()?;
+                    let height = rest
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|height| height.parse().ok())
+                        .ok_or_else(|| error("expected a block height"))?;
+                    Step::WaitForBlock { node, height }
+                }
+                "kill" => Step::Kill(node()?),
+                "resume" => Step::Resume(node()?),
+                other => return Err(error(&format!("unknown step `{other}`"))),
+            };
+            steps.push(step);
+        }
+
+        let nodes = nodes.ok_or(ScenarioError {
+            line: 0,
+            reason: "missing `nodes`".to_string(),
+        })?;
+        Ok(Scenario { nodes, steps })
+    }
+}
+
+#[derive(Debug)]
+pub struct ScenarioError {
+    /// 1-based, 0 for errors about the file as a whole.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ScenarioError {}
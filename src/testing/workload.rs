@@ -0,0 +1,58 @@
+//! Synthetic transaction load for harness networks.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+use super::TestNode;
+
+/// Shape of the load.
+#[derive(Debug, Clone, Copy)]
+pub enum Profile {
+    /// Evenly spaced transactions, from every node in turn.
+    Steady { per_second: u32 },
+    /// `size` transactions at once, then quiet for `pause`, from every node in turn.
+    Bursty { size: usize, pause: Duration },
+    /// A `hot_share` (in `0.0..=1.0`) of the transactions comes from the
+    /// first node, the rest from random other nodes.
+    Skewed { hot_share: f64 },
+}
+
+/// Submits `transactions` synthetic transactions to a network following a
+/// [`Profile`], with sender choice drawn from `seed`.
+pub struct Workload {
+    pub profile: Profile,
+    pub transactions: usize,
+    pub seed: u64,
+}
+
+impl Workload {
+    /// Drive the load into `nodes`, returning once every transaction has been submitted.
+    pub async fn run(&self, nodes: &[TestNode]) {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        for i in 0..self.transactions {
+            let sender = match self.profile {
+                Profile::Steady { .. } | Profile::Bursty { .. } => i % nodes.len(),
+                Profile::Skewed { hot_share } => {
+                    if nodes.len() == 1 || rng.gen_bool(hot_share.clamp(0.0, 1.0)) {
+                        0
+                    } else {
+                        rng.gen_range(1..nodes.len())
+                    }
+                }
+            };
+            nodes[sender].submit(&format!("workload {} {i}", self.seed));
+
+            match self.profile {
+                Profile::Steady { per_second } => {
+                    async_std::task::sleep(Duration::from_secs(1) / per_second.max(1)).await;
+                }
+                Profile::Bursty { size, pause } if (i + 1) % size.max(1) == 0 => {
+                    async_std::task::sleep(pause).await;
+                }
+                Profile::Bursty { .. } | Profile::Skewed { .. } => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use libp2p::gossipsub::{self, TopicHash};
+use libp2p::PeerId;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// What the local node knows about one remote peer.
+#[derive(Debug, Serialize)]
+pub struct PeerTopology {
+    pub peer_id: String,
+    pub connected: bool,
+    /// Topics the peer told us it subscribes to.
+    pub topics: Vec<String>,
+    /// Topics for which the peer is in our gossipsub mesh.
+    pub mesh: Vec<String>,
+}
+
+/// A snapshot of the network as seen from the local node, for rendering the
+/// live graph with Graphviz or any JSON tooling.
+#[derive(Debug, Serialize)]
+pub struct Topology {
+    pub local_peer_id: String,
+    pub topics: Vec<String>,
+    pub peers: Vec<PeerTopology>,
+}
+
+impl Topology {
+    pub fn collect<'a>(
+        local_peer_id: &PeerId,
+        gossipsub: &gossipsub::Behaviour,
+        connected: impl Iterator<Item = &'a PeerId>,
+    ) -> Self {
+        let connected: BTreeSet<_> = connected.collect();
+        let topics: Vec<TopicHash> = gossipsub.topics().cloned().collect();
+
+        let peers = gossipsub
+            .all_peers()
+            .map(|(peer_id, peer_topics)| PeerTopology {
+                peer_id: peer_id.to_string(),
+                connected: connected.contains(peer_id),
+                topics: peer_topics.iter().map(|topic| topic.to_string()).collect(),
+                mesh: topics
+                    .iter()
+                    .filter(|topic| gossipsub.mesh_peers(topic).any(|peer| peer == peer_id))
+                    .map(|topic| topic.to_string())
+                    .collect(),
+            })
+            .collect();
+
+        Topology {
+            local_peer_id: local_peer_id.to_string(),
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+            peers,
+        }
+    }
+
+    /// Render as a Graphviz graph: solid edges are mesh links (labelled with
+    /// their topics), dashed ones are connections outside of the mesh.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph educoin {\n");
+        let _ = writeln!(
+            dot,
+            "  \"{}\" [label=\"{} (local)\\n{}\", style=bold];",
+            self.local_peer_id,
+            short(&self.local_peer_id),
+            self.topics.join(", ")
+        );
+
+        for peer in &self.peers {
+            let _ = writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\\n{}\"];",
+                peer.peer_id,
+                short(&peer.peer_id),
+                peer.topics.join(", ")
+            );
+
+            if !peer.mesh.is_empty() {
+                let _ = writeln!(
+                    dot,
+                    "  \"{}\" -- \"{}\" [label=\"{}\"];",
+                    self.local_peer_id,
+                    peer.peer_id,
+                    peer.mesh.join(", ")
+                );
+            } else if peer.connected {
+                let _ = writeln!(
+                    dot,
+                    "  \"{}\" -- \"{}\" [style=dashed];",
+                    self.local_peer_id, peer.peer_id
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+// Peer ids are long, the tail is enough to tell them apart on a drawing.
+fn short(peer_id: &str) -> &str {
+    &peer_id[peer_id.len().saturating_sub(8)..]
+}
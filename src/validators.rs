@@ -0,0 +1,191 @@
+use libp2p::identity::{self, ed25519::PublicKey};
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet};
+
+use crate::address::Address;
+use crate::signer::{Signer, SignerError};
+use crate::transaction::Transaction;
+
+// Prefix of the message the new key signs, so a rotation proof can't be mistaken for anything else.
+const ROTATION_PROOF_CONTEXT: &[u8] = b"educoin/rotate-key";
+
+/// An on-chain request to replace the signing key of a validator, carried in
+/// transaction data as `rotate-key <new key> <proof>`.
+///
+/// The transaction itself is signed by the old key, and `proof` is a signature
+/// by the new key over the old one, so nobody can bind a key they don't hold.
+#[derive(Debug)]
+pub struct KeyRotation {
+    pub new_key: PublicKey,
+    pub proof: Vec<u8>,
+}
+
+impl KeyRotation {
+    /// Build the data of a rotation transaction to be signed by `old_key`.
+    pub async fn data(old_key: &PublicKey, new_signer: &dyn Signer) -> Result<String, SignerError> {
+        let proof = new_signer.sign(&proof_message(old_key)).await?;
+
+        Ok(format!(
+            "rotate-key {} {}",
+            hex::encode(new_signer.public_key().to_bytes()),
+            hex::encode(proof)
+        ))
+    }
+
+    pub fn parse(data: &[u8]) -> Option<KeyRotation> {
+        let data = std::str::from_utf8(data).ok()?;
+        let mut words = data.split_whitespace();
+
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("rotate-key"), Some(new_key), Some(proof), None) => Some(KeyRotation {
+                new_key: PublicKey::try_from_bytes(&hex::decode(new_key).ok()?).ok()?,
+                proof: hex::decode(proof).ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn is_proven_for(&self, old_key: &PublicKey) -> bool {
+        self.new_key.verify(&proof_message(old_key), &self.proof)
+    }
+}
+
+fn proof_message(old_key: &PublicKey) -> Vec<u8> {
+    [ROTATION_PROOF_CONTEXT, old_key.to_bytes().as_slice()].concat()
+}
+
+/// Tracks which signing key currently speaks for each validator identity.
+///
+/// A validator's identity is the first key it ever used. Once a key has been
+/// rotated out it is retired for good, so transactions and votes signed with
+/// it are no longer accepted, and its coins and stake move over to the new
+/// key, see [`crate::state::ChainState::apply`].
+#[derive(Default)]
+pub struct ValidatorKeys {
+    identity_of: HashMap<Address, Address>,
+    current_key: HashMap<Address, Address>,
+    retired_keys: HashSet<Address>,
+    retired_peers: HashSet<PeerId>,
+}
+
+impl ValidatorKeys {
+    /// Apply the key rotations in a committed block, returning the old and
+    /// new key of each that took effect, in block order.
+    pub fn apply(&mut self, transactions: &[Transaction]) -> Vec<(Address, Address)> {
+        let mut rotated = Vec::new();
+        for transaction in transactions {
+            let Some(rotation) = KeyRotation::parse(&transaction.data) else {
+                continue;
+            };
+
+            let old_key = Address::from(&transaction.public_key);
+            let new_key = Address::from(&rotation.new_key);
+            if self.is_retired(&old_key)
+                || self.is_retired(&new_key)
+                || !rotation.is_proven_for(&transaction.public_key)
+            {
+                continue;
+            }
+
+            let identity = self.identity(&old_key);
+            self.retired_keys.insert(old_key);
+            self.retired_peers
+                .insert(PeerId::from(identity::PublicKey::from(
+                    transaction.public_key.clone(),
+                )));
+            self.identity_of.insert(new_key, identity);
+            self.current_key.insert(identity, new_key);
+            rotated.push((old_key, new_key));
+        }
+        rotated
+    }
+
+    /// The identity that `key` signs for, which is `key` itself unless it was rotated in.
+    pub fn identity(&self, key: &Address) -> Address {
+        self.identity_of.get(key).copied().unwrap_or(*key)
+    }
+
+    /// The key currently speaking for `identity`.
+    pub fn current_key(&self, identity: &Address) -> Address {
+        self.current_key.get(identity).copied().unwrap_or(*identity)
+    }
+
+    pub fn is_retired(&self, key: &Address) -> bool {
+        self.retired_keys.contains(key)
+    }
+
+    /// Whether `peer_id` belongs to a node whose identity key was rotated out.
+    pub fn is_retired_peer(&self, peer_id: &PeerId) -> bool {
+        self.retired_peers.contains(peer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p::identity::ed25519::Keypair;
+
+    // A rotation from `old` to `new`, signed by `old`.
+    fn rotation(old: &Keypair, new: &Keypair) -> Transaction {
+        let data = block_on(KeyRotation::data(&old.public(), new)).unwrap();
+        block_on(Transaction::sign(old, "test", data.as_bytes(), 1)).unwrap()
+    }
+
+    #[test]
+    fn rotations_keep_the_identity_and_retire_the_old_key() {
+        let (first, second, third) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+        let address = |keypair: &Keypair| Address::from(&keypair.public());
+        let mut keys = ValidatorKeys::default();
+        assert_eq!(keys.identity(&address(&first)), address(&first));
+
+        let rotated = keys.apply(&[rotation(&first, &second)]);
+        assert_eq!(rotated, [(address(&first), address(&second))]);
+        let rotated = keys.apply(&[rotation(&second, &third)]);
+        assert_eq!(rotated, [(address(&second), address(&third))]);
+
+        assert_eq!(keys.identity(&address(&third)), address(&first));
+        assert_eq!(keys.current_key(&address(&first)), address(&third));
+        assert!(keys.is_retired(&address(&first)) && keys.is_retired(&address(&second)));
+        assert!(!keys.is_retired(&address(&third)));
+        let peer = PeerId::from(identity::PublicKey::from(first.public()));
+        assert!(keys.is_retired_peer(&peer));
+    }
+
+    #[test]
+    fn retired_and_unproven_keys_dont_rotate() {
+        let (old, new, other) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+        let mut keys = ValidatorKeys::default();
+
+        // proven by another key than the one bound
+        let data = block_on(KeyRotation::data(&old.public(), &other))
+            .unwrap()
+            .replace(
+                &hex::encode(other.public().to_bytes()),
+                &hex::encode(new.public().to_bytes()),
+            );
+        let unproven = block_on(Transaction::sign(&old, "test", data.as_bytes(), 1)).unwrap();
+        assert!(keys.apply(&[unproven]).is_empty());
+        assert_eq!(keys.apply(&[rotation(&old, &new)]).len(), 1);
+        assert!(
+            keys.apply(&[rotation(&old, &other)]).is_empty(),
+            "a retired key can't rotate again"
+        );
+        assert!(
+            keys.apply(&[rotation(&other, &old)]).is_empty(),
+            "nor be rotated back in"
+        );
+        assert_eq!(
+            keys.current_key(&Address::from(&old.public())),
+            Address::from(&new.public())
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use futures::channel::mpsc::{self, Receiver};
+use futures::executor::block_on;
+use futures::SinkExt;
+use libp2p::PeerId;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::{io, thread};
+
+use crate::error::lock;
+use crate::transaction::Transaction;
+
+/// A gossiped transaction after its signature was checked.
+pub struct Verified {
+    pub peer_id: PeerId,
+    pub transaction: Transaction,
+    pub valid: bool,
+}
+
+/// Checks transaction signatures on a pool of threads, so a flood of gossip
+/// doesn't hold up the event loop. Results come back on the receiver
+/// returned by [`VerifierPool::start`], in whatever order they finish.
+///
+/// Both directions are bounded: workers wait while `capacity` results are
+/// unread, and once `capacity` jobs are waiting new ones are refused, so a
+/// flood can't grow the node's memory without limit.
+pub struct VerifierPool {
+    jobs: std_mpsc::SyncSender<(PeerId, Transaction)>,
+}
+
+impl VerifierPool {
+    /// Start `workers` threads verifying transactions for the chain `chain_id`.
+    pub fn start(
+        workers: usize,
+        capacity: usize,
+        chain_id: &str,
+    ) -> io::Result<(Self, Receiver<Verified>)> {
+        let (jobs, queue) = std_mpsc::sync_channel::<(PeerId, Transaction)>(capacity);
+        let queue = Arc::new(Mutex::new(queue));
+        let (results, verified) = mpsc::channel(capacity);
+        let chain_id: Arc<str> = chain_id.into();
+
+        for index in 0..workers.max(1) {
+            let queue = Arc::clone(&queue);
+            let chain_id = Arc::clone(&chain_id);
+            let mut results = results.clone();
+            thread::Builder::new()
+                .name(format!("verifier-{index}"))
+                .spawn(move || loop {
+                    // the lock is only held while waiting for the next job, never while verifying
+                    let job = lock(&queue).recv();
+                    let Ok((peer_id, transaction)) = job else {
+                        return;
+                    };
+                    let valid = transaction.verify(&chain_id);
+                    let verified = Verified {
+                        peer_id,
+                        transaction,
+                        valid,
+                    };
+                    if block_on(results.send(verified)).is_err() {
+                        return;
+                    }
+                })?;
+        }
+
+        Ok((VerifierPool { jobs }, verified))
+    }
+
+    /// Queue `transaction`, received from `peer_id`, for verification.
+    /// Returns `false` when the queue is full and the transaction was dropped.
+    pub fn verify(&self, peer_id: PeerId, transaction: Transaction) -> bool {
+        self.jobs.try_send((peer_id, transaction)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::ed25519::Keypair;
+
+    #[async_std::test]
+    async fn refuses_work_once_results_pile_up() {
+        let (pool, _verified) = VerifierPool::start(1, 2, "test").unwrap();
+        let keypair = Keypair::generate();
+
+        let mut refused = 0;
+        for i in 0..100 {
+            let data = format!("transaction {i}");
+            let transaction = Transaction::sign(&keypair, "test", data.as_bytes(), i + 1)
+                .await
+                .unwrap();
+            if !pool.verify(PeerId::random(), transaction) {
+                refused += 1;
+            }
+        }
+
+        // nobody reads the results, so at most a few jobs fit in the queues
+        assert!(refused >= 90, "only {refused} transactions were refused");
+    }
+}
@@ -0,0 +1,133 @@
+//! Votes are protobuf `Vote`s naming the chain, the voted height and round,
+//! the hash of the block proposed for it and the voter's peer id, signed by
+//! the voter's identity key. Gossipsub signs the message it travels in as
+//! well, but the vote's own signature holds up wherever the vote ends up.
+//! Nodes ignore votes for any chain but their own.
+
+use libp2p::identity::{self, ed25519::Keypair};
+use libp2p::PeerId;
+use prost::Message;
+
+use crate::merkle::Hash;
+use crate::proto;
+
+// Prefix of the signed bytes, so a vote signature can't be passed off as any other.
+const VOTE_CONTEXT: &[u8] = b"educoin/vote/v1\n";
+// Multihash code of peer ids that carry their public key inline, as ed25519 ones do.
+const IDENTITY_MULTIHASH: u64 = 0;
+
+/// A validator's vote for the block with `block_hash` in `round` of `height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vote {
+    pub height: u32,
+    pub round: u32,
+    pub block_hash: Hash,
+    pub voter: PeerId,
+}
+
+/// Vote as `keypair`'s peer for the block with `block_hash` in `round` of `height`.
+pub fn encode(
+    chain_id: &str,
+    height: u32,
+    round: u32,
+    block_hash: &Hash,
+    keypair: &Keypair,
+) -> Vec<u8> {
+    let signature = keypair.sign(&signed_bytes(chain_id, height, round, block_hash));
+    proto::Vote {
+        chain_id: chain_id.to_string(),
+        height,
+        round,
+        voter: PeerId::from(identity::PublicKey::from(keypair.public())).to_bytes(),
+        block_hash: block_hash.to_vec(),
+        signature,
+    }
+    .encode_to_vec()
+}
+
+/// The vote in `data`, if it is a well-formed vote by `voter` on the chain
+/// `chain_id`, signed by the voter.
+pub fn decode(chain_id: &str, data: &[u8], voter: &PeerId) -> Option<Vote> {
+    let vote = proto::Vote::decode(data).ok()?;
+    if vote.chain_id != chain_id || vote.voter != voter.to_bytes() {
+        return None;
+    }
+
+    let block_hash = vote.block_hash.try_into().ok()?;
+    public_key(voter)?
+        .verify(
+            &signed_bytes(chain_id, vote.height, vote.round, &block_hash),
+            &vote.signature,
+        )
+        .then_some(Vote {
+            height: vote.height,
+            round: vote.round,
+            block_hash,
+            voter: *voter,
+        })
+}
+
+/// The vote in `data`, if it is a well-formed vote on the chain `chain_id`
+/// signed by the voter it names, for votes passed on by someone else, e.g.
+/// with the block they committed.
+pub fn decode_relayed(chain_id: &str, data: &[u8]) -> Option<Vote> {
+    let voter = PeerId::from_bytes(&proto::Vote::decode(data).ok()?.voter).ok()?;
+    decode(chain_id, data, &voter)
+}
+
+// "educoin/vote/v1\n" || chain id || "\n" || height || round (4 bytes each, big endian) ||
+// block hash
+fn signed_bytes(chain_id: &str, height: u32, round: u32, block_hash: &Hash) -> Vec<u8> {
+    [
+        VOTE_CONTEXT,
+        chain_id.as_bytes(),
+        b"\n",
+        &height.to_be_bytes(),
+        &round.to_be_bytes(),
+        block_hash,
+    ]
+    .concat()
+}
+
+// The key `peer_id` was derived from, if the peer id holds it.
+fn public_key(peer_id: &PeerId) -> Option<identity::PublicKey> {
+    let multihash = peer_id.as_ref();
+    if multihash.code() != IDENTITY_MULTIHASH {
+        return None;
+    }
+    identity::PublicKey::try_decode_protobuf(multihash.digest()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn votes_hold_the_voters_signature() {
+        let keypair = Keypair::generate();
+        let voter = PeerId::from(identity::PublicKey::from(keypair.public()));
+        let data = encode("test", 3, 1, &[7; 32], &keypair);
+
+        assert_eq!(
+            decode("test", &data, &voter),
+            Some(Vote {
+                height: 3,
+                round: 1,
+                block_hash: [7; 32],
+                voter,
+            })
+        );
+        assert_eq!(decode("another-chain", &data, &voter), None);
+        assert_eq!(decode_relayed("test", &data), decode("test", &data, &voter));
+        let other = PeerId::random();
+        assert_eq!(decode("test", &data, &other), None);
+
+        // another block or round under the same signature
+        let mut forged = proto::Vote::decode(&data[..]).unwrap();
+        forged.block_hash = vec![8; 32];
+        assert_eq!(decode("test", &forged.encode_to_vec(), &voter), None);
+        let mut forged = proto::Vote::decode(&data[..]).unwrap();
+        forged.round = 2;
+        assert_eq!(decode("test", &forged.encode_to_vec(), &voter), None);
+    }
+}
@@ -0,0 +1,279 @@
+use libp2p::identity::ed25519::{self, Keypair, PublicKey};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, io};
+use zeroize::Zeroizing;
+
+use crate::signer::Signer;
+
+/// Label of the account backed by the node identity key itself.
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+// Mixed into the derivation hash so account keys can't collide with any other
+// use of the wallet seed.
+const DERIVATION_CONTEXT: &[u8] = b"educoin/account";
+
+/// A single signing account held by the wallet.
+pub struct Account {
+    pub label: String,
+    /// Derivation index, `None` for accounts backed by an external signer.
+    pub index: Option<u32>,
+    pub signer: Box<dyn Signer>,
+}
+
+impl Account {
+    pub fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+}
+
+/// A wallet holds several signing accounts derived from one seed, so a single
+/// node can act as multiple users.
+///
+/// Account 0 is always the node identity key; every further account is derived
+/// deterministically as `sha256(context || seed || index)`, which means the
+/// same seed always yields the same accounts.
+///
+/// The wallet owns the node identity key, the node signs its votes with it
+/// through [`Wallet::identity`]. The seed is wiped when the wallet is
+/// dropped, as are the account keys.
+pub struct Wallet {
+    seed: Zeroizing<[u8; 32]>,
+    // backs the default account as well
+    identity: Arc<Keypair>,
+    next_index: u32,
+    accounts: Vec<Account>,
+}
+
+impl Wallet {
+    /// Create a wallet seeded from the node identity keypair, which becomes
+    /// its default account.
+    pub fn from_keypair(keypair: Keypair) -> Self {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        seed.copy_from_slice(&Zeroizing::new(keypair.to_bytes())[..32]);
+
+        let identity = Arc::new(keypair);
+        let default_account = Account {
+            label: DEFAULT_ACCOUNT.to_string(),
+            index: Some(0),
+            signer: Box::new(identity.clone()),
+        };
+
+        Wallet {
+            seed,
+            identity,
+            next_index: 1,
+            accounts: vec![default_account],
+        }
+    }
+
+    /// Derive the next account and register it under `label`.
+    pub fn derive_account(&mut self, label: &str) -> Result<&Account, WalletError> {
+        if self.account(label).is_some() {
+            return Err(WalletError::DuplicateLabel(label.to_string()));
+        }
+
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let keypair = derive_keypair(&self.seed, index);
+        self.accounts.push(Account {
+            label: label.to_string(),
+            index: Some(index),
+            signer: Box::new(keypair),
+        });
+
+        Ok(&self.accounts[self.accounts.len() - 1])
+    }
+
+    /// Register an account whose keys live in an external signer,
+    /// e.g. a remote signing service or a hardware wallet simulator.
+    pub fn add_account(
+        &mut self,
+        label: &str,
+        signer: Box<dyn Signer>,
+    ) -> Result<&Account, WalletError> {
+        if self.account(label).is_some() {
+            return Err(WalletError::DuplicateLabel(label.to_string()));
+        }
+
+        self.accounts.push(Account {
+            label: label.to_string(),
+            index: None,
+            signer,
+        });
+
+        Ok(&self.accounts[self.accounts.len() - 1])
+    }
+
+    /// Look up an account by its label.
+    pub fn account(&self, label: &str) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.label == label)
+    }
+
+    /// The node identity key, the default account's.
+    pub fn identity(&self) -> &Keypair {
+        &self.identity
+    }
+
+    /// The account used when no `--from` selector is given.
+    pub fn default_account(&self) -> &Account {
+        &self.accounts[0]
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter()
+    }
+}
+
+fn derive_keypair(seed: &[u8; 32], index: u32) -> Keypair {
+    let mut hasher = Sha256::new();
+    hasher.update(DERIVATION_CONTEXT);
+    hasher.update(seed);
+    hasher.update(index.to_be_bytes());
+    let mut secret_bytes = Zeroizing::new(<[u8; 32]>::from(hasher.finalize()));
+
+    // any 32 bytes form a valid ed25519 secret key
+    let secret = ed25519::SecretKey::try_from_bytes(&mut *secret_bytes)
+        .expect("32 bytes are always a valid ed25519 secret key");
+    Keypair::from(secret)
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    DuplicateLabel(String),
+    UnknownAccount(String),
+    MissingLabel,
+    InvalidFee(String),
+}
+
+impl std::fmt::Display for WalletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalletError::DuplicateLabel(label) => write!(f, "account `{label}` already exists"),
+            WalletError::UnknownAccount(label) => write!(f, "no account named `{label}`"),
+            WalletError::MissingLabel => write!(f, "`--from` requires an account label"),
+            WalletError::InvalidFee(fee) => write!(f, "`--fee` requires an amount, got `{fee}`"),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+/// Split an optional `--from <label>` selector off the front of a stdin line,
+/// returning the selected label (if any) and the remaining transaction data.
+pub fn parse_from_selector(line: &str) -> Result<(Option<&str>, &str), WalletError> {
+    match line.strip_prefix("--from") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => {
+            let rest = rest.trim_start();
+            let (label, data) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if label.is_empty() {
+                return Err(WalletError::MissingLabel);
+            }
+            Ok((Some(label), data.trim_start()))
+        }
+        _ => Ok((None, line)),
+    }
+}
+
+/// Split an optional `--fee <amount>` off the front of transaction data,
+/// following any `--from` selector, returning the fee (0 if none) and the
+/// remaining data.
+pub fn parse_fee(data: &str) -> Result<(u64, &str), WalletError> {
+    match data.strip_prefix("--fee") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => {
+            let rest = rest.trim_start();
+            let (fee, data) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let fee = fee
+                .parse()
+                .map_err(|_| WalletError::InvalidFee(fee.to_string()))?;
+            Ok((fee, data.trim_start()))
+        }
+        _ => Ok((0, data)),
+    }
+}
+
+/// Write the secret half of `keypair` to `path` as hex.
+pub fn save_key_file(path: &Path, keypair: &Keypair) -> io::Result<()> {
+    let keypair_bytes = Zeroizing::new(keypair.to_bytes());
+    let contents = Zeroizing::new(hex::encode(&keypair_bytes[..32]) + "\n");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // only the owner gets to read the secret
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// Read a key file produced by [`save_key_file`]. Every copy of the secret
+/// made along the way is wiped.
+pub fn load_key_file(path: &Path) -> io::Result<Keypair> {
+    let contents = Zeroizing::new(fs::read_to_string(path)?);
+
+    hex::decode(contents.trim())
+        .ok()
+        .map(Zeroizing::new)
+        .and_then(|mut secret_bytes| ed25519::SecretKey::try_from_bytes(&mut **secret_bytes).ok())
+        .map(Keypair::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "key file is not a valid ed25519 secret key",
+            )
+        })
+}
+
+/// The key in the key file at `path`, generating one there if there is no
+/// such file yet, so the node keeps its identity across restarts.
+pub fn load_or_generate_key_file(path: &Path) -> io::Result<Keypair> {
+    match load_key_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let keypair = Keypair::generate();
+            save_key_file(path, &keypair)?;
+            Ok(keypair)
+        }
+        loaded => loaded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fees_are_split_off_the_data() {
+        assert_eq!(parse_fee("send bob 5").unwrap(), (0, "send bob 5"));
+        assert_eq!(parse_fee("--fee 3  send bob 5").unwrap(), (3, "send bob 5"));
+        assert!(matches!(
+            parse_fee("--fee send bob 5"),
+            Err(WalletError::InvalidFee(fee)) if fee == "send"
+        ));
+    }
+
+    #[test]
+    fn the_default_account_is_the_identity_key() {
+        let keypair = Keypair::generate();
+        let public = keypair.public();
+        let wallet = Wallet::from_keypair(keypair);
+        assert_eq!(wallet.identity().public(), public);
+        assert_eq!(wallet.default_account().public_key(), public);
+    }
+
+    #[test]
+    fn key_files_are_generated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.key");
+
+        let generated = load_or_generate_key_file(&path).unwrap();
+        let loaded = load_or_generate_key_file(&path).unwrap();
+        assert_eq!(generated.public(), loaded.public());
+
+        fs::write(&path, "not a key").unwrap();
+        assert_eq!(
+            load_or_generate_key_file(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
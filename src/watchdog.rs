@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+/// Notices when the node has gone quiet: no blocks, votes or gossip messages
+/// processed for longer than `timeout`.
+pub struct Watchdog {
+    timeout: Duration,
+    last_activity: Instant,
+    stalled: bool,
+}
+
+impl Watchdog {
+    pub fn new(timeout: Duration) -> Self {
+        Watchdog {
+            timeout,
+            last_activity: Instant::now(),
+            stalled: false,
+        }
+    }
+
+    /// Record that the node made progress.
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+        self.stalled = false;
+    }
+
+    /// Pretend `by` passed since the last progress.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn advance(&mut self, by: Duration) {
+        self.last_activity = self
+            .last_activity
+            .checked_sub(by)
+            .unwrap_or(self.last_activity);
+    }
+
+    /// Returns how long the node has been idle the first time it crosses the
+    /// timeout, and `None` otherwise, so a stall is only reported once.
+    pub fn check(&mut self) -> Option<Duration> {
+        let idle = self.last_activity.elapsed();
+        if self.stalled || idle < self.timeout {
+            return None;
+        }
+
+        self.stalled = true;
+        Some(idle)
+    }
+}
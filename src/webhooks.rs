@@ -0,0 +1,69 @@
+use serde::Serialize;
+use tracing::warn;
+
+use crate::export;
+use crate::transaction::Transaction;
+
+/// Body of a webhook request: what happened, and the block or transaction
+/// in the shape `/export --format json` uses.
+#[derive(Serialize)]
+struct Notification<T> {
+    event: &'static str,
+    #[serde(flatten)]
+    body: T,
+}
+
+/// POSTs every committed block as JSON to a list of URLs, and optionally
+/// every transaction accepted into the mempool, for apps that want to follow
+/// the chain without talking libp2p.
+///
+/// Requests are sent on background threads, like [`crate::alerts::Alerts`],
+/// so they may arrive out of order and a dead endpoint only costs a warning.
+pub struct Webhooks {
+    urls: Vec<String>,
+    transactions: bool,
+}
+
+impl Webhooks {
+    pub fn new(urls: Vec<String>, transactions: bool) -> Self {
+        Webhooks { urls, transactions }
+    }
+
+    pub fn block_committed(&self, height: u32, transactions: &[Transaction]) {
+        if self.urls.is_empty() {
+            return;
+        }
+        self.post(Notification {
+            event: "block",
+            body: export::Block::new(height, transactions),
+        });
+    }
+
+    pub fn transaction_accepted(&self, transaction: &Transaction) {
+        if self.urls.is_empty() || !self.transactions {
+            return;
+        }
+        self.post(Notification {
+            event: "transaction",
+            body: export::Transaction::new(transaction),
+        });
+    }
+
+    fn post(&self, notification: Notification<impl Serialize>) {
+        let body = match serde_json::to_value(notification) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!(error = %e, "failed to encode webhook notification");
+                return;
+            }
+        };
+        for url in self.urls.clone() {
+            let body = body.clone();
+            async_std::task::spawn_blocking(move || {
+                if let Err(e) = ureq::post(&url).send_json(body) {
+                    warn!(%url, error = %e, "failed to deliver webhook");
+                }
+            });
+        }
+    }
+}